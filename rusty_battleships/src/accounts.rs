@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read, Write};
+use std::result;
+
+use crypto::digest::Digest;
+use crypto::pbkdf2::{pbkdf2_check, pbkdf2_simple};
+use crypto::sha2::Sha256;
+use crypto::util::fixed_time_eq;
+use rand::{OsRng, Rng};
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json::{self, Json};
+
+type Result<T> = result::Result<T, String>;
+
+/// Iterations of PBKDF2-HMAC-SHA256 per password, each login blocks the server for a few milliseconds
+const PBKDF2_ROUNDS: u32 = 20000;
+
+/// Number of tokens that stay valid per account, older ones are forgotten when new ones are issued
+pub const MAX_TOKENS: usize = 5;
+
+struct Account {
+    // salted hash in the format of `pbkdf2_simple`
+    password: String,
+    // SHA-256 of the tokens issued for the account, oldest first
+    tokens: Vec<String>,
+}
+
+/**
+ * Registered players, whose nicknames are reserved for them. Passwords and tokens are only stored
+ * as hashes, in a JSON file that is rewritten on every change.
+ */
+pub struct Accounts {
+    path: String,
+    accounts: BTreeMap<String, Account>,
+}
+
+fn hash_token(token: &str) -> String {
+    let mut sha = Sha256::new();
+    sha.input_str(token);
+    sha.result_str()
+}
+
+fn string_list(value: Option<&Json>) -> Option<Vec<String>> {
+    value.and_then(|value| value.as_array())
+         .and_then(|values| values.iter().map(|value| value.as_string().map(|s| s.to_owned())).collect())
+}
+
+impl Accounts {
+    /// Reads the accounts from the file at `path`, which is created if it does not exist
+    pub fn open(path: &str) -> Result<Accounts> {
+        let mut accounts = Accounts { path: path.to_owned(), accounts: BTreeMap::new() };
+        let mut text = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut text)) {
+            Ok(_) => {},
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {
+                // better to find out now that the file cannot be written than at the first registration
+                try!(accounts.save().map_err(|e| format!("Could not create {}: {}", path, e)));
+                return Ok(accounts);
+            },
+            Err(e) => return Err(format!("Could not read {}: {}", path, e)),
+        }
+
+        let invalid = |what: &str| format!("{}: {}", path, what);
+        let data = try!(Json::from_str(&text).map_err(|e| invalid(&e.to_string())));
+        let entries = try!(data.find("accounts").and_then(|entries| entries.as_object())
+                               .ok_or(invalid("accounts has to be an object")));
+        for (nickname, entry) in entries {
+            let password = try!(entry.find("password").and_then(|password| password.as_string())
+                                     .ok_or(invalid(&format!("the password of {} is missing", nickname))));
+            let tokens = try!(string_list(entry.find("tokens"))
+                                   .ok_or(invalid(&format!("the tokens of {} have to be a list of strings", nickname))));
+            accounts.accounts.insert(nickname.clone(), Account { password: password.to_owned(), tokens: tokens });
+        }
+        Ok(accounts)
+    }
+
+    pub fn exists(&self, nickname: &String) -> bool {
+        self.accounts.contains_key(nickname)
+    }
+
+    /**
+     * Creates an account, unless the nickname is registered already.
+     * @return A token to log in with, None if the nickname is taken
+     */
+    pub fn register(&mut self, nickname: &String, password: &str) -> io::Result<Option<String>> {
+        if self.exists(nickname) {
+            return Ok(None);
+        }
+        let hash = try!(pbkdf2_simple(password, PBKDF2_ROUNDS));
+        self.accounts.insert(nickname.clone(), Account { password: hash, tokens: vec![] });
+        let token = self.issue_token(nickname);
+        if token.is_err() {
+            self.accounts.remove(nickname);
+        }
+        token.map(Some)
+    }
+
+    /**
+     * Checks the password and issues a new token.
+     * @return A new token to log in with, None if the password is wrong
+     */
+    pub fn log_in_with_password(&mut self, nickname: &String, password: &str) -> io::Result<Option<String>> {
+        let valid = match self.accounts.get(nickname) {
+            Some(account) => pbkdf2_check(password, &account.password).unwrap_or(false),
+            None => false,
+        };
+        if !valid {
+            return Ok(None);
+        }
+        self.issue_token(nickname).map(Some)
+    }
+
+    pub fn log_in_with_token(&self, nickname: &String, token: &str) -> bool {
+        let hash = hash_token(token);
+        match self.accounts.get(nickname) {
+            Some(account) => account.tokens.iter().any(|known| fixed_time_eq(known.as_bytes(), hash.as_bytes())),
+            None => false,
+        }
+    }
+
+    /**
+     * Creates a random token for the account and saves its hash. If the file cannot be written,
+     * the token is taken back, so nobody is told about a change that would be lost on a restart.
+     */
+    fn issue_token(&mut self, nickname: &String) -> io::Result<String> {
+        let mut bytes = [0u8; 16];
+        try!(OsRng::new()).fill_bytes(&mut bytes);
+        let token = bytes.to_hex();
+        let previous = self.accounts[nickname].tokens.clone();
+        {
+            let account = self.accounts.get_mut(nickname).unwrap();
+            account.tokens.push(hash_token(&token));
+            if account.tokens.len() > MAX_TOKENS {
+                account.tokens.remove(0);
+            }
+        }
+        if let Err(e) = self.save() {
+            self.accounts.get_mut(nickname).unwrap().tokens = previous;
+            return Err(e);
+        }
+        Ok(token)
+    }
+
+    /// Replaces the file as a whole, so it is never left half written.
+    fn save(&self) -> io::Result<()> {
+        let mut entries = BTreeMap::new();
+        for (nickname, account) in &self.accounts {
+            let mut entry = BTreeMap::new();
+            entry.insert("password".to_owned(), Json::String(account.password.clone()));
+            entry.insert("tokens".to_owned(), Json::Array(account.tokens.iter().cloned().map(Json::String).collect()));
+            entries.insert(nickname.clone(), Json::Object(entry));
+        }
+        let mut data = BTreeMap::new();
+        data.insert("accounts".to_owned(), Json::Object(entries));
+
+        let temporary = format!("{}.tmp", self.path);
+        {
+            let mut file = try!(File::create(&temporary));
+            try!(write!(file, "{}", json::as_pretty_json(&Json::Object(data))));
+            try!(file.sync_all());
+        }
+        fs::rename(&temporary, &self.path)
+    }
+}
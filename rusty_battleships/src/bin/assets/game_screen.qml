@@ -23,6 +23,8 @@ Item {
 			y: -1
 			horizontal: false
 			reverse: false
+			ability: ""
+			cooldown: 0
 		}
 		ListElement {
 			name: "Battleship"
@@ -32,6 +34,8 @@ Item {
 			y: -1
 			horizontal: false
 			reverse: false
+			ability: ""
+			cooldown: 0
 		}
 		ListElement {
 			name: "Cruiser"
@@ -41,6 +45,8 @@ Item {
 			y: -1
 			horizontal: false
 			reverse: false
+			ability: ""
+			cooldown: 0
 		}
 		ListElement {
 			name: "Destroyer"
@@ -50,6 +56,8 @@ Item {
 			y: -1
 			horizontal: false
 			reverse: false
+			ability: ""
+			cooldown: 0
 		}
 		ListElement {
 			name: "Submarine"
@@ -59,6 +67,8 @@ Item {
 			y: -1
 			horizontal: false
 			reverse: false
+			ability: ""
+			cooldown: 0
 		}
     }
 
@@ -77,6 +87,7 @@ Item {
                 property bool moveAllowed: false
                 property int moveDirection: -1
                 property int moveShip: -1
+                property int moveSteps: 1
                 property bool placement_phase: true
 
                 Grid {
@@ -190,6 +201,44 @@ Item {
                     try_move(3);
                 }
             }
+            Button {
+                text: "Auto place"
+                visible: board.placement_phase
+                enabled: board.active
+                onClicked: {
+                    auto_place();
+                }
+            }
+        }
+
+        RowLayout {
+            ComboBox {
+                id: targetSelector
+                // free-for-all only: whose board is shown and shot at
+                model: []
+                visible: model.length > 0
+                onActivated: {
+                    bridge.select_target(model[index]);
+                }
+            }
+            ComboBox {
+                id: weaponSelector
+                // index 2..5: torpedo travelling north, east, south, west
+                model: ["Shot", "Radar sweep", "Torpedo ∧", "Torpedo >", "Torpedo ∨", "Torpedo <"]
+                enabled: chargesLabel.radar > 0 || chargesLabel.torpedo > 0
+            }
+            CheckBox {
+                id: abilityCheckBox
+                text: "Use ship ability"
+                enabled: board.moveShip !== -1 && shipModel.get(board.moveShip).ability !== ""
+                         && shipModel.get(board.moveShip).cooldown === 0
+            }
+            Text {
+                id: chargesLabel
+                property int radar: 0
+                property int torpedo: 0
+                text: "Radar: " + radar + ", Torpedoes: " + torpedo
+            }
         }
 
         RowLayout {
@@ -204,6 +253,16 @@ Item {
                     title: "Hit points"
                     width: 80
                 }
+                TableViewColumn {
+                    role: "ability"
+                    title: "Ability"
+                    width: 100
+                }
+                TableViewColumn {
+                    role: "cooldown"
+                    title: "Cooldown"
+                    width: 70
+                }
                 model: shipModel
             }
 
@@ -218,6 +277,11 @@ Item {
                     property int count: 0
                     text: "Destroyed: " + count + "/5"
                 }
+                Text {
+                    id: placementErrorLabel
+                    color: "red"
+                    visible: text !== ""
+                }
             }
         }
 	}
@@ -231,7 +295,20 @@ Item {
         if (board.active && !board.placement_phase) {
 	        var x = index % 10;
 	        var y = Math.floor(index / 10);
-	        bridge.move_and_shoot(x, y, board.moveShip, board.moveDirection);
+	        var weapon = weaponSelector.currentIndex;
+	        if (abilityCheckBox.checked && abilityCheckBox.enabled) {
+	            bridge.use_ability(x, y, board.moveShip, Math.max(board.moveDirection, 0));
+	        } else if (weapon === 1 && chargesLabel.radar > 0) {
+	            undo_move();
+	            bridge.radar_sweep(x, y);
+	        } else if (weapon > 1 && chargesLabel.torpedo > 0) {
+	            undo_move();
+	            bridge.fire_torpedo(x, y, weapon - 2);
+	        } else {
+	            bridge.move_and_shoot(x, y, board.moveShip, board.moveDirection);
+	        }
+	        weaponSelector.currentIndex = 0;
+	        abilityCheckBox.checked = false;
 	        board.moveAllowed = false;
 	        board.active = false;
         }
@@ -321,16 +398,37 @@ Item {
 		}
 
 		if ([0, 1, 2, 3, 4].filter(function(i) { return shipModel.get(i).x === -1; }).length === 0) {
-		    board.active = false;
-		    board.placement_phase = false;
-		    var placement = [];
-		    for (var i = 0; i < 5; i++) {
-		        placement.push(shipModel.get(i));
-		    }
-		    bridge.handle_placement(JSON.stringify(placement));
+		    submit_placement();
 		}
     }
 
+    function auto_place() {
+        var positions = eval(bridge.get_random_placement());
+        clearBoard();
+        for (var i = 0; i < 5; i++) {
+            var ship = shipModel.get(i);
+            ship.x = positions[i].x;
+            ship.y = positions[i].y;
+            ship.horizontal = positions[i].horizontal;
+            ship.reverse = positions[i].reverse;
+            draw_ship(i);
+        }
+        board.currentX = -1;
+        board.currentY = -1;
+        submit_placement();
+    }
+
+    function submit_placement() {
+        board.active = false;
+        board.placement_phase = false;
+        placementErrorLabel.text = "";
+        var placement = [];
+        for (var i = 0; i < 5; i++) {
+            placement.push(shipModel.get(i));
+        }
+        bridge.handle_placement(JSON.stringify(placement));
+    }
+
     function draw_ship(index) {
         console.assert(index >= 0 && index < 5);
 
@@ -341,12 +439,16 @@ Item {
         }
 
         var buttonIndex = 10 * ship.y + ship.x;
+        var damage = bridge.get_ship_damage(index);
 
         for (var i = 0; i < ship.length; i++) {
 	        var button = boardButtons.itemAt(buttonIndex);
 
-	        // special case for submarine so it's easy to identify
-	        if (index === 4) {
+	        // damaged segments move along with the ship
+	        if (damage & (1 << i)) {
+	            button.textColor = "red";
+	        } else if (index === 4) {
+	            // special case for submarine so it's easy to identify
 	            button.textColor = "grey";
 	        }
 
@@ -387,14 +489,32 @@ Item {
 
     function try_move(direction) {
         console.assert(direction > -1 && direction < 4);
-        if (bridge.can_move_in_direction(board.moveShip, direction)) {
+        if (abilityCheckBox.checked && shipModel.get(board.moveShip).ability === "DoubleMove") {
+            if (bridge.can_double_move(board.moveShip, direction)) {
+                move(direction);
+                move(direction);
+                board.moveSteps = 2;
+            } else {
+                console.log("Invalid move");
+            }
+        } else if (bridge.can_move_in_direction(board.moveShip, direction)) {
 	        move(direction);
+	        board.moveSteps = 1;
         } else {
             // TODO: show error message
             console.log("Invalid move");
         }
     }
 
+    // special weapons cannot be combined with a move
+    function undo_move() {
+        if (!board.moveAllowed && board.moveDirection !== -1) {
+            for (var i = 0; i < board.moveSteps; i++) {
+                move((board.moveDirection + 2) % 4);
+            }
+        }
+    }
+
     function move(direction) {
         board.moveAllowed = false;
 		board.moveDirection = direction;
@@ -438,6 +558,21 @@ Item {
 
         hitCounter.count = bridge.get_hits();
         destroyedCounter.count = bridge.get_destroyed();
+        var cooldowns = eval(bridge.get_cooldowns());
+        for (var i = 0; i < 5; i++) {
+            shipModel.get(i).ability = bridge.get_ship_ability(i);
+            shipModel.get(i).cooldown = cooldowns[i];
+        }
+        chargesLabel.radar = bridge.get_radar_charges();
+        chargesLabel.torpedo = bridge.get_torpedo_charges();
+    }
+
+    function updateTargets() {
+        var opponents = eval(bridge.get_opponents());
+        if (opponents.toString() !== targetSelector.model.toString()) {
+            targetSelector.model = opponents;
+            targetSelector.currentIndex = opponents.indexOf(bridge.get_target());
+        }
     }
 
     function updateState() {
@@ -466,7 +601,14 @@ Item {
 
                 // reset last move, if ship was moved
                 if (!board.moveAllowed) {
-                    move((board.moveDirection + 2) % 4);
+                    undo_move();
+                }
+            }
+        } else if (state === "PlacingShips") {
+            if (!board.placement_phase) {
+                var errors = eval(bridge.get_placement_errors());
+                if (errors.length > 0) {
+                    handle_placement_errors(errors);
                 }
             }
         } else if (state === "Available") {
@@ -474,9 +616,34 @@ Item {
         }
     }
 
+    // the server rejected our placement: the offending ships have to be placed again
+    function handle_placement_errors(errors) {
+        var messages = [];
+        for (var i = 0; i < errors.length; i++) {
+            var names = errors[i].ships.map(function(id) { return shipModel.get(id).name; });
+            messages.push(names.join(" and ") + ": " + errors[i].kind);
+            for (var j = 0; j < errors[i].ships.length; j++) {
+                shipModel.get(errors[i].ships[j]).x = -1;
+            }
+        }
+        placementErrorLabel.text = messages.join("\n");
+
+        clearBoard();
+        for (var i = 0; i < 5; i++) {
+            if (shipModel.get(i).x !== -1) {
+                draw_ship(i);
+            }
+        }
+        board.currentX = -1;
+        board.currentY = -1;
+        board.placement_phase = true;
+        board.active = true;
+    }
+
     function activate() {
         timer.triggered.connect(updateBoards);
         timer.triggered.connect(updateHitPoints);
+        timer.triggered.connect(updateTargets);
         timer.triggered.connect(updateState);
         // TODO: pass opponent info and set title text accordingly
         visible = true;
@@ -485,6 +652,7 @@ Item {
     function deactivate() {
         timer.triggered.disconnect(updateBoards);
         timer.triggered.disconnect(updateHitPoints);
+        timer.triggered.disconnect(updateTargets);
         timer.triggered.disconnect(updateState);
 
         // reset board
@@ -506,6 +674,13 @@ Item {
 
         hitCounter.count = 0;
         destroyedCounter.count = 0;
+        chargesLabel.radar = 0;
+        chargesLabel.torpedo = 0;
+        weaponSelector.currentIndex = 0;
+        abilityCheckBox.checked = false;
+        targetSelector.model = [];
+        placementErrorLabel.text = "";
+        board.moveSteps = 1;
 
         visible = false;
     }
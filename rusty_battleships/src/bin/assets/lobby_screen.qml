@@ -69,38 +69,99 @@ Item {
                             italic: true
                             pointSize: 8
                         }
-                        text: ready ? "ready" : "not ready"
+                        text: (selected && freeForAllCheckbox.checked ? "selected, " : "")
+                              + (ready ? "ready" : "not ready")
                     }
                 }
             }
 
             onClicked: {
-                bridge.send_challenge(name);
-                screen.gameStarted();
+                if (freeForAllCheckbox.checked) {
+                    lobbyModel.setProperty(index, "selected", !selected);
+                } else {
+                    bridge.send_challenge(name);
+                    screen.gameStarted();
+                }
             }
         }
     }
 
-    CheckBox {
-        id: waitCheckbox
+    RowLayout {
         anchors.bottom: parent.bottom
-        text: "Wait for challenge from another player"
 
-        onCheckedChanged: {
-            bridge.set_ready_state(checked);
+        CheckBox {
+            id: waitCheckbox
+            text: "Wait for challenge from another player"
+
+            onCheckedChanged: {
+                bridge.set_ready_state(checked);
+            }
         }
+
+        CheckBox {
+            id: freeForAllCheckbox
+            text: "Free-for-all"
+            enabled: !waitCheckbox.checked
+        }
+
+        Button {
+            text: "Challenge selected players"
+            visible: freeForAllCheckbox.checked
+            enabled: selectedPlayers().length > 0
+
+            onClicked: {
+                bridge.send_multi_challenge(JSON.stringify(selectedPlayers()));
+                screen.gameStarted();
+            }
+        }
+
+        // with three players selected, one of them can be picked as ally for a team game
+        ComboBox {
+            id: allySelector
+            visible: freeForAllCheckbox.checked && model.length === 3
+            model: []
+        }
+
+        Button {
+            text: "Team up"
+            visible: allySelector.visible
+
+            onClicked: {
+                var ally = allySelector.model[allySelector.currentIndex];
+                var opponents = allySelector.model.filter(function (name) { return name !== ally; });
+                bridge.send_team_challenge(ally, JSON.stringify(opponents));
+                screen.gameStarted();
+            }
+        }
+    }
+
+    function selectedPlayers() {
+        var players = [];
+        for (var i = 0; i < lobbyModel.count; i++) {
+            var player = lobbyModel.get(i);
+            if (player.selected && player.ready) {
+                players.push(player.name);
+            }
+        }
+        return players;
     }
 
 
     function updateLobby() {
         var lobby = eval(bridge.update_lobby());
+        var selected = selectedPlayers();
         lobbyModel.clear();
         lobby.available_players.map(function (player_name) {
             lobbyModel.append({
                 name: player_name,
-                ready: lobby.ready_players.indexOf(player_name) !== -1
+                ready: lobby.ready_players.indexOf(player_name) !== -1,
+                selected: selected.indexOf(player_name) !== -1
             });
         });
+        var players = selectedPlayers();
+        if (players.toString() !== allySelector.model.toString()) {
+            allySelector.model = players;
+        }
     }
 
 
@@ -117,6 +178,7 @@ Item {
         timer.triggered.connect(updateLobby);
         timer.triggered.connect(checkGameStarted);
         waitCheckbox.checked = false;
+        freeForAllCheckbox.checked = false;
         visible = true;
     }
 
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, BufWriter, Read, Result, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+extern crate argparse;
+use argparse::{ArgumentParser, Print, Store, StoreOption, StoreTrue};
+
+extern crate rusty_battleships;
+use rusty_battleships::bot::{Bot, CHALLENGE_DELAY_MS};
+use rusty_battleships::message::{redacted, serialize_message, deserialize_message, Message, Outcome};
+use rusty_battleships::external::{self, TEXT_PREFIX};
+use rusty_battleships::strategy::{Strategy, STRATEGY_NAMES};
+
+macro_rules! description {
+    () => ( "rusty battleships: headless computer player" )
+}
+macro_rules! version {
+    () => ( env!("CARGO_PKG_VERSION") )
+}
+macro_rules! version_string {
+    () => ( concat!(description!(), " v", version!()) )
+}
+
+/// Time to wait before connecting again after the connection was lost
+const RECONNECT_DELAY_MS: u64 = 5000;
+
+struct Config {
+    hostname: String,
+    port: u16,
+    nickname: String,
+    password: Option<String>,
+    strategy: String,
+    challenge: Option<String>,
+    games: usize,
+    seed: Option<usize>,
+    stdio: bool,
+}
+
+fn send_message<W: Write>(msg: Message, stream: &mut W, verbose: bool) -> Result<()> {
+    if verbose {
+        println!(">>> {:?}", redacted(&msg));
+    }
+    try!(stream.write(&serialize_message(msg)[..]));
+    try!(stream.flush());
+    Ok(())
+}
+
+/// Plays a single game from logging in until it is over
+fn play_game<R: Read, W: Write>(bot: &mut Bot, reader: &mut R, writer: &mut W, verbose: bool) -> Result<Outcome> {
+    try!(send_message(bot.connect(), writer, verbose));
+    loop {
+        let msg = try!(deserialize_message(reader));
+        if verbose {
+            println!("<<< {:?}", redacted(&msg));
+        }
+        for request in try!(bot.handle_message(msg)) {
+            try!(send_message(request, writer, verbose));
+        }
+        // try again until the challenged player is ready to play
+        if let Some(request) = bot.retry_challenge() {
+            thread::sleep(Duration::from_millis(CHALLENGE_DELAY_MS));
+            try!(send_message(request, writer, verbose));
+        }
+        if let Some(outcome) = bot.get_outcome() {
+            return Ok(outcome);
+        }
+    }
+}
+
+fn create_bot(config: &Config, strategy: Box<Strategy>) -> Bot {
+    let mut bot = Bot::new(config.nickname.clone(), strategy, config.challenge.clone());
+    if let Some(ref password) = config.password {
+        bot.set_password(password.clone());
+    }
+    return bot;
+}
+
+fn create_strategy(config: &Config, seed: Option<usize>) -> Box<Strategy> {
+    match external::from_spec(&config.strategy, seed) {
+        Ok(strategy) => strategy,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+fn connect_and_play(config: &Config, bot: &mut Bot) -> Result<Outcome> {
+    let stream = try!(TcpStream::connect((&config.hostname[..], config.port)));
+    let mut reader = BufReader::new(try!(stream.try_clone()));
+    let mut writer = BufWriter::new(stream);
+    return play_game(bot, &mut reader, &mut writer, true);
+}
+
+fn main() {
+    let mut config = Config {
+        hostname: "127.0.0.1".to_owned(),
+        port: 5000,
+        nickname: "Bot".to_owned(),
+        password: None,
+        strategy: "probability".to_owned(),
+        challenge: None,
+        games: 0,
+        seed: None,
+        stdio: false,
+    };
+
+    {  // this block limits scope of borrows by ap.refer() method
+        let mut ap = ArgumentParser::new();
+        ap.set_description(description!());
+        ap.refer(&mut config.hostname).add_argument("HOST", Store, "hostname or IP address of the server");
+        ap.refer(&mut config.port).add_option(&["-p", "--port"], Store, "port the server listens on");
+        ap.refer(&mut config.nickname).add_option(&["-n", "--nickname"], Store,
+            "nickname to log in with, a number is appended if it is taken (default Bot)");
+        ap.refer(&mut config.password).add_option(&["--password"], StoreOption,
+            "log in with the account of the nickname, which is registered if it does not exist yet");
+        ap.refer(&mut config.strategy).add_option(&["-s", "--strategy"], Store,
+            "strategy to play with: random, hunt-target, probability or tracker (default probability), \
+             or text: followed by the command line of a program speaking the text protocol");
+        ap.refer(&mut config.challenge).add_option(&["-c", "--challenge"], StoreOption,
+            "challenge the given player instead of waiting to be challenged");
+        ap.refer(&mut config.games).add_option(&["-g", "--games"], Store,
+            "number of games to play before exiting, 0 to play forever (default 0)");
+        ap.refer(&mut config.seed).add_option(&["--seed"], StoreOption,
+            "seed for the strategy, to play the same way every time");
+        ap.refer(&mut config.stdio).add_option(&["--stdio"], StoreTrue,
+            "play a single game speaking the protocol over stdin and stdout instead of connecting, \
+             e.g. in a tournament");
+        ap.add_option(&["-v", "--version"], Print(version_string!().to_owned()),
+        "show version number");
+        ap.parse_args_or_exit();
+    }
+
+    if !STRATEGY_NAMES.contains(&&config.strategy[..]) && !config.strategy.starts_with(TEXT_PREFIX) {
+        println!("Unknown strategy {}, choose one of: {} or {}<command>", config.strategy,
+                 STRATEGY_NAMES.join(", "), TEXT_PREFIX);
+        std::process::exit(1);
+    }
+
+    if config.stdio {
+        let strategy = create_strategy(&config, config.seed);
+        let mut bot = create_bot(&config, strategy);
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        // the game is over once the other side closes the pipe
+        let _ = play_game(&mut bot, &mut stdin.lock(), &mut stdout.lock(), false);
+        return;
+    }
+
+    let mut results: HashMap<Outcome, usize> = HashMap::new();
+    let mut games_played = 0;
+    while config.games == 0 || games_played < config.games {
+        let seed = config.seed.map(|seed| seed + games_played);
+        let strategy = create_strategy(&config, seed);
+        let mut bot = create_bot(&config, strategy);
+
+        // every game is played on a new connection, so the bot is back in the lobby afterwards
+        match connect_and_play(&config, &mut bot) {
+            Ok(outcome) => {
+                println!("Game over: {:?}", outcome);
+                games_played += 1;
+                *results.entry(outcome).or_insert(0) += 1;
+                println!("Played {} games: {} victories, {} defeats, {} draws", games_played,
+                         results.get(&Outcome::Victory).unwrap_or(&0),
+                         results.get(&Outcome::Defeat).unwrap_or(&0),
+                         results.get(&Outcome::Draw).unwrap_or(&0));
+            },
+            Err(e) => {
+                println!("Connection lost: {}. Reconnecting in {} seconds.", e, RECONNECT_DELAY_MS / 1000);
+                thread::sleep(Duration::from_millis(RECONNECT_DELAY_MS));
+            },
+        }
+    }
+}
@@ -8,7 +8,7 @@ extern crate byteorder;
 use byteorder::{ByteOrder, BigEndian, ReadBytesExt};
 
 mod client_;
-use client_::state::{LobbyList, State, Status};
+use client_::state::{BoardUpdate, LobbyList, State, Status};
 
 #[macro_use]
 extern crate qmlrs;
@@ -20,7 +20,12 @@ use rustc_serialize::json::{Json};
 
 extern crate rusty_battleships;
 use rusty_battleships::message::{Message, Direction, ShipPlacement};
-use rusty_battleships::board::{Board, DumbBoard, W, H};
+use rusty_battleships::board::{Board, W, H};
+use rusty_battleships::knowledge::{CellKnowledge, OpponentBoard};
+use rusty_battleships::game::Charges;
+use rusty_battleships::placement::{self, Bias, PlacementError};
+use rusty_battleships::rules::Rules;
+use rusty_battleships::ship::Ability;
 use rusty_battleships::timer::timer_periodic;
 
 extern crate time;
@@ -75,6 +80,22 @@ Q_OBJECT! { Assets:
 }
 
 
+#[derive(Clone, Eq, Hash, PartialEq, RustcEncodable)]
+struct ShipPosition {
+    x: isize,
+    y: isize,
+    horizontal: bool,
+    reverse: bool,
+}
+
+
+#[derive(Clone, Eq, Hash, PartialEq, RustcEncodable)]
+struct PlacementProblem {
+    kind: String,
+    ships: Vec<u8>,
+}
+
+
 #[derive(Clone, Eq, Hash, PartialEq, RustcEncodable)]
 struct Server {
     ip: [u8; 4],
@@ -95,13 +116,19 @@ struct Bridge {
     disconnect_sender : mpsc::Sender<bool>, //For the State object!
     disconnect_receiver : mpsc::Receiver<bool>,
 
-    board_sender: mpsc::Sender<(Board, DumbBoard, u8, u8)>, //For the State object!
-    board_receiver: mpsc::Receiver<(Board, DumbBoard, u8, u8)>,
+    board_sender: mpsc::Sender<BoardUpdate>, //For the State object!
+    board_receiver: mpsc::Receiver<BoardUpdate>,
 
     my_board: Option<Board>,
-    their_board: Option<DumbBoard>,
+    their_board: Option<OpponentBoard>,
     hits: u8,
     destroyed: u8,
+    charges: Charges,
+    cooldowns: Option<[u8; 5]>,
+    // free-for-all only: boards of the remaining opponents and the one currently aimed at
+    opponent_boards: HashMap<String, OpponentBoard>,
+    target: Option<String>,
+    placement_errors: Vec<PlacementProblem>,
 
     state: Status,
     features_list: Vec<String>,
@@ -138,6 +165,12 @@ impl Bridge {
                         self.state = tuple.0;
                         self.last_rcvd_msg = Some(tuple.1.clone());
                     },
+                    Message::AccountRequiredResponse => {
+                        println!("The server only admits players with an account.");
+                        response_received = true;
+                        self.state = tuple.0;
+                        self.last_rcvd_msg = Some(tuple.1.clone());
+                    },
                     Message::PlayerJoinedUpdate { .. } | Message::PlayerLeftUpdate { .. } |
                     Message::PlayerReadyUpdate { .. } | Message::PlayerNotReadyUpdate { .. } => {
                         // ignore
@@ -174,12 +207,51 @@ impl Bridge {
     }
 
     fn update_boards(&mut self) {
-        while let Ok((ref my_board, ref their_board, hits, destroyed)) =
+        while let Ok((ref my_board, ref their_board, hits, destroyed, charges, cooldowns, ref opponent_boards)) =
                 self.board_receiver.try_recv() {
             self.my_board = Some(my_board.clone());
             self.their_board = Some(their_board.clone());
             self.hits = hits;
             self.destroyed = destroyed;
+            self.charges = charges;
+            self.cooldowns = cooldowns;
+            self.opponent_boards = opponent_boards.clone();
+        }
+
+        // aim at another opponent once the current target has been eliminated
+        let target_gone = match self.target {
+            Some(ref target) => !self.opponent_boards.contains_key(target),
+            None => true,
+        };
+        if target_gone {
+            self.target = self.get_opponent_names().into_iter().next();
+        }
+    }
+
+    fn get_opponent_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.opponent_boards.keys().cloned().collect();
+        names.sort();
+        return names;
+    }
+
+    /**
+     * returns the remaining opponents of a free-for-all game as JSON array,
+     * empty for two-player games
+     */
+    fn get_opponents(&mut self) -> String {
+        self.update_boards();
+        return json::encode(&self.get_opponent_names()).unwrap();
+    }
+
+    fn get_target(&mut self) -> String {
+        self.update_boards();
+        return self.target.clone().unwrap_or(String::new());
+    }
+
+    fn select_target(&mut self, nickname: String) {
+        self.update_boards();
+        if self.opponent_boards.contains_key(&nickname) {
+            self.target = Some(nickname);
         }
     }
 
@@ -192,24 +264,82 @@ impl Bridge {
         self.ui_sender.as_mut().unwrap()
             .send(Message::ChallengePlayerRequest { username: username })
             .unwrap();
-        if let Ok(tuple) = self.msg_update_receiver.try_recv() {
-            self.state = tuple.0;
-            self.last_rcvd_msg = Some(tuple.1);
+        if let Ok((status, msg)) = self.msg_update_receiver.try_recv() {
+            self.handle_msg_update(status, msg);
         }
     }
 
+    /**
+     * challenges all players in the given JSON array to a free-for-all game
+     */
+    fn send_multi_challenge(&mut self, usernames_json: String) {
+        let usernames = Bridge::parse_names(&usernames_json);
+        println!(">>> UI: Sending challenge request for {:?} ...", usernames);
+        self.ui_sender.as_mut().unwrap()
+            .send(Message::ChallengePlayersRequest { usernames: usernames })
+            .unwrap();
+        if let Ok((status, msg)) = self.msg_update_receiver.try_recv() {
+            self.handle_msg_update(status, msg);
+        }
+    }
+
+    /**
+     * challenges the two players in the given JSON array to a team game against us and our ally
+     */
+    fn send_team_challenge(&mut self, ally: String, opponents_json: String) {
+        let opponents = Bridge::parse_names(&opponents_json);
+        println!(">>> UI: Sending team challenge request with {} for {:?} ...", ally, opponents);
+        self.ui_sender.as_mut().unwrap()
+            .send(Message::ChallengeTeamRequest { ally: ally, opponents: opponents })
+            .unwrap();
+        if let Ok((status, msg)) = self.msg_update_receiver.try_recv() {
+            self.handle_msg_update(status, msg);
+        }
+    }
+
+    fn parse_names(names_json: &str) -> Vec<String> {
+        let data = Json::from_str(names_json).unwrap();
+        return data.as_array().unwrap().iter()
+            .map(|name| name.as_string().unwrap().to_owned())
+            .collect();
+    }
+
+    fn handle_msg_update(&mut self, status: Status, msg: Message) {
+        if let Message::InvalidPlacementResponse { ref errors } = msg {
+            self.placement_errors = errors.iter().map(|error| PlacementProblem {
+                kind: match *error {
+                    PlacementError::OutOfBounds { .. } => "out of bounds",
+                    PlacementError::Overlap { .. } => "overlap",
+                    PlacementError::Touching { .. } => "touching",
+                }.to_owned(),
+                ships: error.ships(),
+            }).collect();
+        }
+        self.state = status;
+        self.last_rcvd_msg = Some(msg);
+    }
+
+    /**
+     * returns the problems the server found with our placement as JSON array of objects with
+     * the kind of problem and the indices of the offending ships, and forgets about them
+     */
+    fn get_placement_errors(&mut self) -> String {
+        self.poll_state();
+        let errors = json::encode(&self.placement_errors).unwrap();
+        self.placement_errors.clear();
+        return errors;
+    }
+
     fn poll_state(&mut self) -> String {
-        while let Ok(tuple) = self.msg_update_receiver.try_recv() {
-            self.state = tuple.0;
-            self.last_rcvd_msg = Some(tuple.1);
+        while let Ok((status, msg)) = self.msg_update_receiver.try_recv() {
+            self.handle_msg_update(status, msg);
         }
         format!("{:?}", self.state)
     }
 
     fn poll_log(&mut self) -> String {
-        while let Ok(tuple) = self.msg_update_receiver.try_recv() {
-            self.state = tuple.0;
-            self.last_rcvd_msg = Some(tuple.1);
+        while let Ok((status, msg)) = self.msg_update_receiver.try_recv() {
+            self.handle_msg_update(status, msg);
         }
 
         return match self.last_rcvd_msg {
@@ -283,7 +413,21 @@ impl Bridge {
      * ship_index: -1 for no movement and 0..4 for ship
      */
     fn move_and_shoot(&mut self, x: i64, y: i64, ship_index: i64, direction_index: i64) {
-        if ship_index == -1 {
+        self.update_boards();
+        if let Some(target) = self.target.clone() {
+            let message = if ship_index == -1 {
+                Message::ShootAtRequest { nickname: target, x: x as u8, y: y as u8 }
+            } else {
+                Message::MoveAndShootAtRequest {
+                    nickname: target,
+                    x: x as u8,
+                    y: y as u8,
+                    id: ship_index as u8,
+                    direction: Bridge::index_to_direction(direction_index)
+                }
+            };
+            self.ui_sender.as_mut().unwrap().send(message).unwrap();
+        } else if ship_index == -1 {
             self.ui_sender.as_mut().unwrap()
                 .send(Message::ShootRequest { x: x as u8, y: y as u8 })
                 .unwrap();
@@ -297,14 +441,82 @@ impl Bridge {
         }
     }
 
+    /**
+     * radar sweep of the 3x3 area centered at (x, y) on opponent board
+     */
+    fn radar_sweep(&mut self, x: i64, y: i64) {
+        self.ui_sender.as_mut().unwrap()
+            .send(Message::RadarRequest { x: x as u8, y: y as u8 })
+            .unwrap();
+    }
+
+    /**
+     * torpedo fired from (x, y) on opponent board, travelling in direction 0..3
+     */
+    fn fire_torpedo(&mut self, x: i64, y: i64, direction_index: i64) {
+        self.ui_sender.as_mut().unwrap().send(Message::TorpedoRequest {
+            x: x as u8,
+            y: y as u8,
+            direction: Bridge::index_to_direction(direction_index)
+        }).unwrap();
+    }
+
+    /**
+     * uses the ability of ship_index; (x, y) is the target on the opponent board
+     * direction_index is only relevant for double moves
+     */
+    fn use_ability(&mut self, x: i64, y: i64, ship_index: i64, direction_index: i64) {
+        self.ui_sender.as_mut().unwrap().send(Message::AbilityRequest {
+            x: x as u8,
+            y: y as u8,
+            id: ship_index as u8,
+            direction: Bridge::index_to_direction(direction_index)
+        }).unwrap();
+    }
+
+    /**
+     * returns the name of the ability of ship_index, or "" if it has none
+     */
+    fn get_ship_ability(&mut self, ship_index: i64) -> String {
+        self.update_boards();
+        if self.cooldowns.is_none() {
+            return String::new();
+        }
+        match self.my_board.as_ref().and_then(|board| board.get_ships().get(ship_index as usize)) {
+            Some(ship) => match ship.ability {
+                Some(Ability::Reconnaissance) => String::from("Reconnaissance"),
+                Some(Ability::Submerge) => String::from("Submerge"),
+                Some(Ability::DoubleMove) => String::from("DoubleMove"),
+                None => String::new(),
+            },
+            None => String::new(),
+        }
+    }
+
+    /**
+     * returns the remaining cooldown of all ships' abilities, e.g. "[0,0,0,2,0]"
+     */
+    fn get_cooldowns(&mut self) -> String {
+        self.update_boards();
+        return json::encode(&self.cooldowns.unwrap_or([0; 5])).unwrap();
+    }
+
+    /**
+     * returns bool as {0, 1}
+     */
+    fn can_double_move(&mut self, ship_index: i64, direction_index: i64) -> bool {
+        self.update_boards();
+        let mut cloned_board = self.my_board.as_ref().unwrap().clone();
+        return cloned_board.double_move_ship(ship_index as u8, Bridge::index_to_direction(direction_index));
+    }
+
     /**
      * returns bool as {0, 1}
      */
     fn can_move_in_direction(&mut self, ship_index: i64, direction_index: i64) -> bool {
         self.update_boards();
         let mut cloned_board = self.my_board.as_ref().unwrap().clone();
-        cloned_board.move_ship(ship_index as u8, Bridge::index_to_direction(direction_index));
-        return cloned_board.compute_state().is_some();
+        return cloned_board.move_ship(ship_index as u8, Bridge::index_to_direction(direction_index));
     }
 
     fn set_ready_state(&mut self, ready: i64) {
@@ -350,12 +562,33 @@ impl Bridge {
         println!("{:?}", placements);
     }
 
+    /// returns a random placement of the fleet in the format of `handle_placement` as JSON
+    fn get_random_placement(&self) -> String {
+        let rules = Rules {
+            no_touching: self.features_list.contains(&"No Touching Ships".to_owned()),
+            ..Rules::default()
+        };
+        let positions: Vec<ShipPosition> = placement::random_fleet(&rules, Bias::Uniform, None).iter()
+            .map(|ship| ShipPosition {
+                x: ship.x,
+                y: ship.y,
+                horizontal: ship.is_horizontal(),
+                reverse: ship.is_reverse(),
+            }).collect();
+        return json::encode(&positions).unwrap();
+    }
+
     fn get_opp_board(&mut self) -> String {
         self.update_boards();
         let mut result = String::new();
+        // in free-for-all games the board of the current target is shown
+        let displayed_board = match self.target {
+            Some(ref target) => self.opponent_boards.get(target),
+            None => self.their_board.as_ref(),
+        };
         for y in 0..H {
             for x in 0..W {
-                if let Some(ref board) = self.their_board {
+                if let Some(board) = displayed_board {
                     // if !board.is_visible_at(x, y) {
                     //     result.push('"');
                     // } else {
@@ -365,8 +598,14 @@ impl Bridge {
                     //         result.push('-');
                     //     }
                     // }
-                    result.push( if !board.is_visible_at(x, y) { '"' } else {
-                        if board.has_ship_at(x, y) { 'X' } else { '-' }
+                    result.push(match board.get(x, y) {
+                        CellKnowledge::Unknown => '"',
+                        CellKnowledge::Water => '-',
+                        CellKnowledge::Stale => '.',
+                        CellKnowledge::Ship => 'O',
+                        CellKnowledge::Hit => 'X',
+                        CellKnowledge::Sunk => '#',
+                        CellKnowledge::LastSeen => '*',
                     });
                 } else {
                     result.push('?');
@@ -427,6 +666,18 @@ impl Bridge {
         return json::encode(&hps).unwrap();
     }
 
+    /**
+     * returns the damaged segments of a ship on my board as bit mask,
+     * bit i being set if segment i (counted from the ship's origin) has been hit
+     */
+    fn get_ship_damage(&mut self, ship_index: i64) -> i64 {
+        self.update_boards();
+        match self.my_board {
+            Some(ref board) => board.get_ships().get(ship_index as usize).map_or(0, |ship| ship.damage as i64),
+            None => 0,
+        }
+    }
+
     fn get_hits(&mut self) -> i64 {
         self.update_boards();
         self.hits as i64
@@ -436,11 +687,23 @@ impl Bridge {
         self.update_boards();
         self.destroyed as i64
     }
+
+    fn get_radar_charges(&mut self) -> i64 {
+        self.update_boards();
+        self.charges.radar as i64
+    }
+
+    fn get_torpedo_charges(&mut self) -> i64 {
+        self.update_boards();
+        self.charges.torpedo as i64
+    }
 }
 
 Q_OBJECT! { Bridge:
     slot fn send_login_request(String);
     slot fn send_challenge(String);
+    slot fn send_multi_challenge(String);
+    slot fn send_team_challenge(String, String);
     slot fn poll_state();
     slot fn update_lobby();
     slot fn poll_log();
@@ -449,6 +712,7 @@ Q_OBJECT! { Bridge:
     slot fn discover_servers();
     slot fn get_features_list();
     slot fn handle_placement(String);
+    slot fn get_random_placement();
     slot fn move_and_shoot(i64, i64, i64, i64);
     slot fn connection_closed();
     slot fn set_ready_state(i64);
@@ -458,13 +722,26 @@ Q_OBJECT! { Bridge:
     slot fn get_ship_at(i64, i64);
     slot fn get_my_board_visibility();
     slot fn get_ships_hps();
+    slot fn get_ship_damage(i64);
     slot fn get_hits();
     slot fn get_destroyed();
+    slot fn radar_sweep(i64, i64);
+    slot fn fire_torpedo(i64, i64, i64);
+    slot fn get_radar_charges();
+    slot fn get_torpedo_charges();
+    slot fn use_ability(i64, i64, i64, i64);
+    slot fn get_ship_ability(i64);
+    slot fn get_cooldowns();
+    slot fn can_double_move(i64, i64);
+    slot fn get_opponents();
+    slot fn get_placement_errors();
+    slot fn get_target();
+    slot fn select_target(String);
 }
 
 fn tcp_loop(hostname: String, port: i64, rcv_ui_update: mpsc::Receiver<Message>,
     tx_message_update: mpsc::Sender<(Status, Message)>, tx_lobby_update: mpsc::Sender<LobbyList>,
-    tx_board_update: mpsc::Sender<(Board, DumbBoard, u8, u8)>, tx_disconnect_update: mpsc::Sender<bool>)
+    tx_board_update: mpsc::Sender<BoardUpdate>, tx_disconnect_update: mpsc::Sender<bool>)
         -> bool {
 
     //Connect to the specified address and port.
@@ -499,7 +776,7 @@ fn main() {
 
     let (tx_lobby_update, rcv_lobby_update) = mpsc::channel();
     let (tx_udp_discovery, rcv_udp_discovery) = mpsc::channel();
-    let (tx_board_update, rcv_board_update) : (mpsc::Sender<(Board, DumbBoard, u8, u8)>, mpsc::Receiver<(Board, DumbBoard, u8, u8)>) = mpsc::channel();
+    let (tx_board_update, rcv_board_update) : (mpsc::Sender<BoardUpdate>, mpsc::Receiver<BoardUpdate>) = mpsc::channel();
 
     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
 	let socket_send = socket.try_clone().unwrap();
@@ -546,6 +823,11 @@ fn main() {
         their_board: None,
         hits: 0,
         destroyed: 0,
+        charges: Charges { radar: 0, torpedo: 0 },
+        cooldowns: None,
+        opponent_boards: HashMap::new(),
+        target: None,
+        placement_errors: vec![],
         ui_sender: None,
         msg_update_sender: tx_message_update, //For the State object!
         msg_update_receiver: rcv_main,
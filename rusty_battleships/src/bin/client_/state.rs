@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::io::{BufReader, BufWriter, Write};
 use std::net::{TcpStream};
 use std::sync::mpsc::{self, Sender, Receiver};
@@ -9,14 +10,23 @@ use rustc_serialize::Encodable;
 
 use client_::lobby::ClientLobby;
 
-use rusty_battleships::message::{serialize_message, deserialize_message, Message, ShipPlacement, Direction, Reason};
-use rusty_battleships::ship::{Ship};
-use rusty_battleships::board::{Board, DumbBoard};
+use rusty_battleships::message::{serialize_message, deserialize_message, Message, ShipPlacement, Direction, Outcome, Reason};
+use rusty_battleships::placement::PlacementError;
+use rusty_battleships::rules::Rules;
+use rusty_battleships::ship::{Ability, Ship, class_ability};
+use rusty_battleships::board::Board;
+use rusty_battleships::knowledge::OpponentBoard;
+use rusty_battleships::game::Charges;
 use rusty_battleships::timer::timer_periodic;
 
 
 const TICK_DURATION_MS: u64 = 100;
 
+/* Board state sent to the UI thread: own board, opponent board, hits, destroyed ships, special
+ * weapon charges, ability cooldowns per ship (None if ship abilities are disabled) and the boards
+ * of all remaining opponents in a free-for-all game */
+pub type BoardUpdate = (Board, OpponentBoard, u8, u8, Charges, Option<[u8; 5]>, HashMap<String, OpponentBoard>);
+
 
 #[derive(Clone, Debug, PartialEq, RustcEncodable)]
 pub struct LobbyList {
@@ -63,14 +73,23 @@ pub struct State {
     their_afks : u8,
     hits: u8,
     destroyed: u8,
+    charges: Charges,
+    pending_torpedo: Option<(u8, u8, Direction)>,
+    cooldowns: Option<[u8; 5]>,
+    // free-for-all and team games: shots name their target and turns are announced by the server
+    multiplayer: bool,
+    ally: Option<String>,
+    // multiplayer only: opponent name -> board, and the opponent our last shot was aimed at
+    opponent_boards: HashMap<String, OpponentBoard>,
+    pending_target: Option<String>,
     my_board : Option<Board>,
-    their_board : Option<DumbBoard>,
+    their_board : Option<OpponentBoard>,
     pub buff_reader : BufReader<TcpStream>,
     buff_writer : BufWriter<TcpStream>,
     ui_update_receiver : Receiver<Message>,
     ui_update_sender : Sender<(Status, Message)>,
     lobby_update_sender : Sender<LobbyList>,
-    board_update_sender : Sender<(Board, DumbBoard, u8, u8)>,
+    board_update_sender : Sender<BoardUpdate>,
     disconnect_update_sender : Sender<bool>
 }
 
@@ -91,7 +110,7 @@ impl State {
     pub fn new(rec_ui_update: Receiver<Message>,
                 tx_ui_update: Sender<(Status, Message)>,
                 tx_lobby_update: Sender<LobbyList>,
-                tx_board_update: Sender<(Board, DumbBoard, u8, u8)>,
+                tx_board_update: Sender<BoardUpdate>,
                 tx_disconnect_update: Sender<bool>,
                 buff_reader: BufReader<TcpStream>,
                 buff_writer: BufWriter<TcpStream>) -> State {
@@ -104,6 +123,13 @@ impl State {
             their_afks : 3,
             hits : 0,
             destroyed: 0,
+            charges: Charges { radar: 0, torpedo: 0 },
+            pending_torpedo: None,
+            cooldowns: None,
+            multiplayer: false,
+            ally: None,
+            opponent_boards: HashMap::new(),
+            pending_target: None,
             my_board : None,
             their_board : None,
             buff_reader : buff_reader,
@@ -116,6 +142,16 @@ impl State {
         }
     }
 
+    /* The rules the client has to apply itself, as far as the server's features tell. */
+    fn rules(&self) -> Rules {
+        let has_feature = |feature: &str| self.lobby.feature_list.iter().any(|f| f == feature);
+        Rules {
+            no_touching: has_feature("No Touching Ships"),
+            damaged_segments_miss: has_feature("Damaged Segments Miss"),
+            ..Rules::default()
+        }
+    }
+
     pub fn get_features(&mut self) -> bool {
         send_message(Message::GetFeaturesRequest, &mut self.buff_writer);
         self.status = Status::AwaitFeatures;
@@ -197,6 +233,40 @@ impl State {
         )
     }
 
+    /* Challenges several players at once to a free-for-all game */
+    pub fn challenge_players(&mut self, opponents: Vec<String>) -> bool {
+        self.change_status(
+            Status::Available,
+            Some(Status::AwaitGameStart),
+            Message::ChallengePlayersRequest { usernames: opponents.clone() },
+            |state| {
+                state.init_multiplayer(&opponents, None);
+                true
+            }
+        )
+    }
+
+    /* Challenges two opponents to a game against us and our ally */
+    pub fn challenge_team(&mut self, ally: String, opponents: Vec<String>) -> bool {
+        self.change_status(
+            Status::Available,
+            Some(Status::AwaitGameStart),
+            Message::ChallengeTeamRequest { ally: ally.clone(), opponents: opponents.clone() },
+            |state| {
+                state.init_multiplayer(&opponents, Some(ally.clone()));
+                true
+            }
+        )
+    }
+
+    fn init_multiplayer(&mut self, opponents: &Vec<String>, ally: Option<String>) {
+        self.multiplayer = opponents.len() > 1;
+        self.ally = ally;
+        if self.multiplayer {
+            self.opponent_boards = opponents.iter().map(|name| (name.clone(), OpponentBoard::new())).collect();
+        }
+    }
+
     //FIXME: Change return value to Result<(),String)>
     pub fn place_ships(&mut self, ships: [ShipPlacement; 5]) -> bool {
         self.change_status(
@@ -212,6 +282,10 @@ impl State {
                         length: cmp::max(5-i, 2),
                         direction: ships[i].direction,
                         health_points: cmp::max(5-i, 2),
+                        damage: 0,
+                        // the server only accepts abilities if enabled by its rules
+                        ability: class_ability(i),
+                        submerged: false,
                     };
                     ship_vec.push(s);
                 }
@@ -221,7 +295,7 @@ impl State {
                 }
                 state.my_board = my_board;
                 state.my_board.as_mut().unwrap().compute_state();
-                state.their_board = Some(DumbBoard::new());
+                state.their_board = Some(OpponentBoard::new());
                 true
             }
         )
@@ -248,6 +322,120 @@ impl State {
         );
     }
 
+    fn shoot_at(&mut self, target: String, x: u8, y: u8) {
+        self.change_status(
+            Status::Planning,
+            None,
+            Message::ShootAtRequest { nickname: target.clone(), x: x, y: y },
+            |state| {
+                state.pending_target = Some(target.clone());
+                true
+            }
+        );
+    }
+
+    fn move_and_shoot_at(&mut self, target: String, x: u8, y: u8, id: u8, direction: Direction) {
+        self.change_status(
+            Status::Planning,
+            None,
+            Message::MoveAndShootAtRequest { nickname: target.clone(), id: id, direction: direction, x: x, y: y },
+            |state| {
+                state.pending_target = Some(target.clone());
+                state.my_board.as_mut().unwrap().move_ship(id, direction);
+                true
+            }
+        );
+    }
+
+    fn radar(&mut self, x: u8, y: u8) {
+        if self.charges.radar == 0 {
+            println!("No radar sweeps left.");
+            return;
+        }
+        self.change_status(
+            Status::Planning,
+            None,
+            Message::RadarRequest { x: x, y: y },
+            |state| {
+                state.charges.radar -= 1;
+                true
+            }
+        );
+    }
+
+    fn torpedo(&mut self, x: u8, y: u8, direction: Direction) {
+        if self.charges.torpedo == 0 {
+            println!("No torpedoes left.");
+            return;
+        }
+        self.change_status(
+            Status::Planning,
+            None,
+            Message::TorpedoRequest { x: x, y: y, direction: direction },
+            |state| {
+                state.charges.torpedo -= 1;
+                state.pending_torpedo = Some((x, y, direction));
+                true
+            }
+        );
+    }
+
+    fn use_ability(&mut self, x: u8, y: u8, id: u8, direction: Direction) {
+        self.change_status(
+            Status::Planning,
+            None,
+            Message::AbilityRequest { id: id, direction: direction, x: x, y: y },
+            |state| {
+                let board = state.my_board.as_mut().unwrap();
+                match board.get_ships()[id as usize].ability {
+                    Some(Ability::Submerge) => board.submerge_ship(id),
+                    Some(Ability::DoubleMove) => board.double_move_ship(id, direction),
+                    _ => true,
+                }
+            }
+        );
+    }
+
+    /* Our turn starts: submerged ships surface again. */
+    fn begin_my_turn(&mut self) {
+        self.my_turn = true;
+        if let Some(ref mut board) = self.my_board {
+            board.surface_ships();
+        }
+    }
+
+    /* Our turn is over: ships the opponents moved before are no longer close by. */
+    fn end_my_turn(&mut self) {
+        if self.my_turn {
+            if let Some(ref mut board) = self.their_board {
+                board.next_turn();
+            }
+            for board in self.opponent_boards.values_mut() {
+                board.next_turn();
+            }
+        }
+        self.my_turn = false;
+    }
+
+    /* The board our last shot was aimed at */
+    fn target_board(&mut self) -> Option<&mut OpponentBoard> {
+        match self.pending_target {
+            Some(ref target) => self.opponent_boards.get_mut(target),
+            None => self.their_board.as_mut(),
+        }
+    }
+
+    /* Marks the cells our last torpedo passed before exploding at (x, y) as water. */
+    fn mark_torpedo_path(&mut self, x: u8, y: u8) {
+        if let Some((start_x, start_y, direction)) = self.pending_torpedo.take() {
+            if let Some(ref mut board) = self.their_board {
+                for (path_x, path_y) in Board::torpedo_path(start_x, start_y, direction, x, y) {
+                    board.set_water(path_x, path_y);
+                }
+            }
+        }
+    }
+
     pub fn handle_get_features_response(&mut self, features: Vec<String>) {
         self.handle_response(
             Status::AwaitFeatures,
@@ -276,6 +464,86 @@ impl State {
         )
     }
 
+    pub fn handle_free_for_all_start_update(&mut self, nicknames: Vec<String>) {
+        self.handle_response(
+            Status::Waiting,
+            Status::PlacingShips,
+            |state| { state.init_multiplayer(&nicknames, None); },
+            "FreeForAllStartUpdate"
+        )
+    }
+
+    pub fn handle_team_game_start_update(&mut self, ally: &str, opponents: Vec<String>) {
+        self.handle_response(
+            Status::Waiting,
+            Status::PlacingShips,
+            |state| { state.init_multiplayer(&opponents, Some(ally.to_owned())); },
+            "TeamGameStartUpdate"
+        )
+    }
+
+    /* Multiplayer: the server announces whose turn it is after every move. */
+    pub fn handle_player_turn_update(&mut self, nickname: &str) {
+        if self.status == Status::OpponentPlacing || self.status == Status::Planning
+                || self.status == Status::OpponentPlanning {
+            if nickname == self.lobby.player_name {
+                self.begin_my_turn();
+                self.status = Status::Planning;
+            } else {
+                self.end_my_turn();
+                self.status = Status::OpponentPlanning;
+            }
+        } else {
+            let error_message: String = format!("ERROR: I did not expect a PlayerTurnUpdate! CUR_STATE={:?}", self.status);
+            send_message(Message::ReportErrorRequest { errormessage: error_message }, &mut self.buff_writer);
+        }
+    }
+
+    /* Multiplayer: every player learns about all shots fired. Shots at our ally are not tracked. */
+    pub fn handle_player_shot_update(&mut self, target: &str, x: u8, y: u8, hit: bool) {
+        if target == self.lobby.player_name {
+            if let Some(ref mut board) = self.my_board {
+                if hit {
+                    board.hit(x as usize, y as usize);
+                } else {
+                    board.set_visible_at(x as usize, y as usize);
+                }
+            }
+        } else if let Some(board) = self.opponent_boards.get_mut(target) {
+            if hit {
+                board.set_damaged(x, y);
+            } else {
+                board.set_water(x, y);
+            }
+        }
+    }
+
+    pub fn handle_board_visible_update(&mut self, nickname: &str, x: u8, y: u8) {
+        if let Some(board) = self.opponent_boards.get_mut(nickname) {
+            board.set_ship(x, y);
+        }
+    }
+
+    pub fn handle_board_invisible_update(&mut self, nickname: &str, x: u8, y: u8) {
+        if let Some(board) = self.opponent_boards.get_mut(nickname) {
+            board.set_water(x, y);
+        }
+    }
+
+    pub fn handle_board_damaged_update(&mut self, nickname: &str, x: u8, y: u8) {
+        if let Some(board) = self.opponent_boards.get_mut(nickname) {
+            board.set_damaged(x, y);
+        }
+    }
+
+    /* Eliminated players keep watching the game until it is over. */
+    pub fn handle_player_eliminated_update(&mut self, nickname: &str) {
+        if nickname == self.lobby.player_name {
+            println!("Our fleet has been destroyed. Watching the remaining captains fight it out.");
+        }
+        self.opponent_boards.remove(nickname);
+    }
+
     /* Program flow guideline: Set your values when you're sending the Requests and hand over to
      * the usual message loop. If everythin goes the way it's meant to go, all's fine. If not, then
      * we'll panic anyway. */
@@ -298,6 +566,10 @@ impl State {
         self.handle_response(Status::Register, Status::Unregistered, |_| {}, "NameTakenResponse");
     }
 
+    pub fn handle_account_required_response(&mut self) {
+        self.handle_response(Status::Register, Status::Unregistered, |_| {}, "AccountRequiredResponse");
+    }
+
     pub fn handle_no_such_player_response(&mut self, _: &str) {
         self.handle_response(Status::AwaitGameStart, Status::Available, |_| {}, "NoSuchPlayerResponse"); 
     }
@@ -306,15 +578,29 @@ impl State {
         self.handle_response(Status::AwaitGameStart, Status::Available, |_| {}, "NotWaitingResponse");
     }
 
+    /* The server rejected our placement: place the ships again. */
+    pub fn handle_invalid_placement_response(&mut self, errors: Vec<PlacementError>) {
+        self.handle_response(
+            Status::PlacingShips,
+            Status::PlacingShips,
+            |state| {
+                println!("Invalid placement: {:?}", errors);
+                state.my_board = None;
+            },
+            "InvalidPlacementResponse"
+        );
+    }
+
     pub fn handle_hit_response(&mut self, x: u8, y: u8) {
         self.handle_response(
             Status::Planning,
             Status::OpponentPlacing,
             |state| {
-                if let Some(ref mut board) = state.their_board {
-                    board.set_ship(x, y);
+                state.mark_torpedo_path(x, y);
+                if let Some(board) = state.target_board() {
+                    board.set_damaged(x, y);
                 }
-                state.my_turn = false;
+                state.end_my_turn();
                 state.hits += 1;
             },
             "HitResponse"
@@ -329,7 +615,7 @@ impl State {
                 if let Some(ref mut board) = state.my_board {
                     board.hit(x as usize, y as usize);
                 }
-                state.my_turn = true;
+                state.begin_my_turn();
             },
             "EnemyHitUpdate"
         );
@@ -340,10 +626,11 @@ impl State {
             Status::Planning,
             Status::OpponentPlacing,
             |state| {
-                if let Some(ref mut board) = state.their_board {
+                state.mark_torpedo_path(x, y);
+                if let Some(board) = state.target_board() {
                     board.set_water(x, y);
                 }
-                state.my_turn = false;
+                state.end_my_turn();
             },
             "MissResponse"
         );
@@ -357,7 +644,7 @@ impl State {
                 if let Some(ref mut board) = state.my_board {
                     board.set_visible_at(x as usize, y as usize);
                 }
-                state.my_turn = true;
+                state.begin_my_turn();
             },
             "EnemyMissUpdate"
         );
@@ -368,10 +655,11 @@ impl State {
             Status::Planning,
             Status::OpponentPlacing,
             |state| {
-                if let Some(ref mut board) = state.their_board {
-                    board.set_water(x, y);
+                state.mark_torpedo_path(x, y);
+                if let Some(board) = state.target_board() {
+                    board.set_sunk(x, y);
                 }
-                state.my_turn = false;
+                state.end_my_turn();
                 state.hits += 1;
                 state.destroyed += 1;
             },
@@ -379,11 +667,85 @@ impl State {
         );
     }
 
+    pub fn handle_radar_response(&mut self, x: u8, y: u8, ship_detected: bool) {
+        self.handle_response(
+            Status::Planning,
+            Status::OpponentPlanning,
+            |state| {
+                println!("Radar sweep around ({}, {}): {}", x, y,
+                         if ship_detected { "contact!" } else { "nothing" });
+                state.end_my_turn();
+            },
+            "RadarResponse"
+        );
+    }
+
+    pub fn handle_enemy_radar_update(&mut self) {
+        self.handle_response(
+            Status::OpponentPlanning,
+            Status::Planning,
+            |state| { state.begin_my_turn(); },
+            "EnemyRadarUpdate"
+        );
+    }
+
+    pub fn handle_enemy_torpedo_update(&mut self, x: u8, y: u8, direction: Direction) {
+        self.handle_response(
+            Status::OpponentPlanning,
+            Status::Planning,
+            |state| {
+                let rules = state.rules();
+                if let Some(ref mut board) = state.my_board {
+                    board.torpedo(x as usize, y as usize, direction, &rules);
+                }
+                state.begin_my_turn();
+            },
+            "EnemyTorpedoUpdate"
+        );
+    }
+
+    pub fn handle_recon_response(&mut self, x: u8, y: u8) {
+        self.handle_response(
+            Status::Planning,
+            Status::OpponentPlanning,
+            |state| {
+                // every revealed cell follows as an update of the opponent's board
+                println!("Reconnaissance around ({}, {}).", x, y);
+                state.end_my_turn();
+            },
+            "ReconResponse"
+        );
+    }
+
+    pub fn handle_enemy_recon_update(&mut self, x: u8, y: u8) {
+        self.handle_response(
+            Status::OpponentPlanning,
+            Status::Planning,
+            |state| {
+                if let Some(ref mut board) = state.my_board {
+                    board.reveal_area(x as usize, y as usize);
+                }
+                state.begin_my_turn();
+            },
+            "EnemyReconUpdate"
+        );
+    }
+
+    pub fn handle_ability_cooldown_update(&mut self, id: u8, turns: u8) {
+        let mut cooldowns = self.cooldowns.unwrap_or([0; 5]);
+        cooldowns[id as usize] = turns;
+        self.cooldowns = Some(cooldowns);
+    }
+
+    pub fn handle_charges_update(&mut self, radar: u8, torpedo: u8) {
+        self.charges = Charges { radar: radar, torpedo: torpedo };
+    }
+
     pub fn handle_your_turn_update(&mut self) {
         self.handle_response(
             Status::OpponentPlacing,
             Status::Planning,
-            |state| { state.my_turn = true; },
+            |state| { state.begin_my_turn(); },
             "YourTurnUpdate"
         );
     }
@@ -392,7 +754,7 @@ impl State {
         self.handle_response(
             Status::OpponentPlacing,
             Status::OpponentPlanning,
-            |state| { state.my_turn = false; },
+            |state| { state.end_my_turn(); },
             "EnemyTurnUpdate"
         );
     }
@@ -409,12 +771,19 @@ impl State {
         }
     }
 
+    /* A damaged segment of a moving ship has been sighted. */
+    pub fn handle_enemy_damaged_update(&mut self, x: u8, y: u8) {
+        if let Some(ref mut board) = self.their_board {
+            board.set_damaged(x, y);
+        }
+    }
+
     pub fn handle_afk_warning_update(&mut self, strikes: u8) {
         self.handle_response(
             Status::Planning,
             Status::OpponentPlanning,
             |state| {
-                state.my_turn = false;
+                state.end_my_turn();
                 state.my_afks -= 1;
                 if state.my_afks != strikes {
                     panic!("Inconsistent strike count for **me**! MINE={}, SERVER={}", state.my_afks, strikes);
@@ -425,11 +794,15 @@ impl State {
     }
 
     pub fn handle_enemy_afk_update(&mut self, strikes: u8) {
+        if self.multiplayer {
+            // strikes are counted per player, the next turn is announced by a PlayerTurnUpdate
+            return;
+        }
         self.handle_response(
             Status::OpponentPlanning,
             Status::Planning,
             |state| {
-                state.my_turn = true;
+                state.begin_my_turn();
                 state.their_afks -= 1;
                 if state.their_afks != strikes {
                     panic!("Inconsistent strike count for **the enemy**! MINE={}, SERVER={}", state.their_afks, strikes);
@@ -439,17 +812,20 @@ impl State {
         );
     }
 
-    pub fn handle_game_over_update(&mut self, victory: bool, reason: Reason) {
+    pub fn handle_game_over_update(&mut self, outcome: Outcome, reason: Reason) {
         if self.status == Status::OpponentPlanning || self.status == Status::Planning ||
                 self.status == Status::OpponentPlacing || self.status == Status::PlacingShips ||
                 self.status == Status::Available || self.status == Status::Surrendered {
             println!("The game is over.");
-            if victory {
-                println!("Congratulations, captain! You've won!");
-            } else {
-                println!("You've lost.", );
+            match outcome {
+                Outcome::Victory => println!("Congratulations, captain! You've won!"),
+                Outcome::Draw => println!("It's a draw."),
+                Outcome::Defeat => println!("You've lost."),
+            }
+            match reason {
+                Reason::EndedByServer => println!("The game was ended by the server's administrator."),
+                _ => println!("Reason: {:?}", reason),
             }
-            println!("Reason: {:?}", reason);
             self.status = Status::Available;
 
             // reset game state
@@ -460,8 +836,19 @@ impl State {
             self.their_afks = 3;
             self.hits = 0;
             self.destroyed = 0;
+            self.charges = Charges { radar: 0, torpedo: 0 };
+            self.pending_torpedo = None;
+            self.cooldowns = None;
+            self.multiplayer = false;
+            self.ally = None;
+            self.opponent_boards = HashMap::new();
+            self.pending_target = None;
 
+            let player_name = self.lobby.player_name.clone();
+            let feature_list = self.lobby.feature_list.clone();
             self.lobby = ClientLobby::new();
+            self.lobby.set_player_name(&player_name);
+            self.lobby.set_feature_list(feature_list);
             self.send_updated_boards(); // send new, empty board to UI thread
         } else {
             let error_message: String = format!("ERROR: I did not expect a GAME_OVER_UPDATE! CUR_STATE={:?}", self.status);
@@ -494,9 +881,10 @@ impl State {
         };
         let theirboard = match self.their_board {
             Some(ref board) => board.clone(),
-            None => DumbBoard::new(),
+            None => OpponentBoard::new(),
         };
-        let boards = (myboard, theirboard, self.hits, self.destroyed);
+        let boards = (myboard, theirboard, self.hits, self.destroyed, self.charges, self.cooldowns,
+                      self.opponent_boards.clone());
         self.board_update_sender.send(boards).unwrap();
     }
 
@@ -507,6 +895,7 @@ impl State {
             Message::PlayerReadyUpdate {nickname: nn} => println!("Captain {:?} is now ready to be challenged.", nn),
             Message::PlayerNotReadyUpdate {nickname : nn} => println!("Captain {:?} is not ready.", nn),
             Message::GameStartUpdate {nickname: nn} => println!("Received a challenge by captain {:?}", nn),
+            Message::ServerMessageUpdate {message: msg} => println!("Message from the server: {}", msg),
             Message::ServerGoingDownUpdate {errormessage: err}=> {
                 println!("The server is going down!");
                 println!("REASON:{:?}",err);
@@ -518,17 +907,31 @@ impl State {
             Message::EnemyMissUpdate {x, y} => println!("They missed! ({}, {})", x, y),
             Message::EnemyVisibleUpdate {x, y} => println!("The enemy has been sighted! ({}, {})", x, y),
             Message::EnemyInvisibleUpdate {x, y} => println!("We lost track of the enemy! ({}, {})", x, y),
+            Message::EnemyDamagedUpdate {x, y} => println!("A damaged enemy ship has been sighted! ({}, {})", x, y),
             Message::EnemyAfkUpdate {strikes} => println!("The enemy is sleeping! ({})", strikes),
+            Message::EnemyRadarUpdate {x, y} => println!("The enemy swept the area around ({}, {}) with radar!", x, y),
+            Message::EnemyTorpedoUpdate {x, y, direction} => println!("Torpedo incoming from ({}, {}) heading {:?}!", x, y, direction),
+            Message::ChargesUpdate {radar, torpedo} => println!("Special weapons ready: {} radar sweeps, {} torpedoes.", radar, torpedo),
             // RESPONSES
             Message::InvalidRequestResponse => println!("Received an INVALID_REQUEST_RESPONSE in state {:?}.", self.status),
             Message::FeaturesResponse {..} => println!("Received features list!"),
             Message::NameTakenResponse {nickname: nn} => println!("There is already a captain {:?} registered. Choose a different name.", nn),
+            Message::AccountRequiredResponse => println!("This server only admits captains with an account."),
             Message::NoSuchPlayerResponse {nickname: nn} => println!("There is no captain {:?} registered.", nn),
             Message::NotWaitingResponse {nickname: nn} => println!("Captain {:?} is not waiting to be challenged.", nn),
             Message::GameAlreadyStartedResponse => println!("The game has already started."),
             Message::HitResponse {x, y} => println!("You have hit a ship! ({}, {})", x, y),
             Message::MissResponse {x, y} => println!("You have missed.({}, {})", x, y),
             Message::DestroyedResponse {..} => println!("Congratulations! You destroyed an enemy ship!"),
+            Message::ReconResponse {x, y} => println!("Reconnaissance report for the area around ({}, {}) received.", x, y),
+            Message::EnemyReconUpdate {x, y} => println!("Enemy aircraft spotted over ({}, {})!", x, y),
+            Message::AbilityCooldownUpdate {id, turns} => println!("Ship {} can use its ability again in {} turns.", id, turns),
+            Message::RadarResponse {x, y, ship_detected} => println!("Radar sweep around ({}, {}) detected a ship: {}", x, y, ship_detected),
+            Message::FreeForAllStartUpdate {nicknames} => println!("Received a free-for-all challenge against captains {:?}", nicknames),
+            Message::TeamGameStartUpdate {ally, opponents} => println!("Teaming up with captain {:?} against captains {:?}", ally, opponents),
+            Message::PlayerTurnUpdate {nickname: nn} => println!("It's captain {:?}'s turn!", nn),
+            Message::PlayerShotUpdate {shooter, target, x, y, hit} => println!("Captain {:?} fired at captain {:?} ({}, {}): {}", shooter, target, x, y, if hit { "hit" } else { "miss" }),
+            Message::PlayerEliminatedUpdate {nickname: nn} => println!("Captain {:?} has been eliminated!", nn),
             _ => println!(">>>RECEIVED: {:?}", msg),
         }
     }
@@ -541,7 +944,7 @@ impl State {
             Message::PlayerReadyUpdate {nickname: nn} => self.lobby.ready_player(&nn.clone()),
             Message::PlayerNotReadyUpdate {nickname : nn} => self.lobby.unready_player(&nn.clone()),
             Message::GameStartUpdate {nickname: nn} => self.handle_game_start_update(&nn.clone()),
-            Message::GameOverUpdate {victorious, reason} => self.handle_game_over_update(victorious, reason),
+            Message::GameOverUpdate {outcome, reason} => self.handle_game_over_update(outcome, reason),
             Message::YourTurnUpdate => self.handle_your_turn_update(),
             Message::EnemyTurnUpdate => self.handle_enemy_turn_update(),
             Message::AfkWarningUpdate {strikes} => self.handle_afk_warning_update(strikes),
@@ -549,16 +952,34 @@ impl State {
             Message::EnemyMissUpdate {x, y} => self.handle_enemy_miss_update(x, y),
             Message::EnemyVisibleUpdate {x, y} => self.handle_enemy_visible_update(x, y),
             Message::EnemyInvisibleUpdate {x, y} => self.handle_enemy_invisible_update(x, y),
+            Message::EnemyDamagedUpdate {x, y} => self.handle_enemy_damaged_update(x, y),
             Message::EnemyAfkUpdate {strikes} => self.handle_enemy_afk_update(strikes),
+            Message::EnemyRadarUpdate {..} => self.handle_enemy_radar_update(),
+            Message::EnemyTorpedoUpdate {x, y, direction} => self.handle_enemy_torpedo_update(x, y, direction),
+            Message::ChargesUpdate {radar, torpedo} => self.handle_charges_update(radar, torpedo),
+            Message::EnemyReconUpdate {x, y} => self.handle_enemy_recon_update(x, y),
+            Message::AbilityCooldownUpdate {id, turns} => self.handle_ability_cooldown_update(id, turns),
+            Message::ReconResponse {x, y} => self.handle_recon_response(x, y),
+            Message::FreeForAllStartUpdate {nicknames} => self.handle_free_for_all_start_update(nicknames),
+            Message::TeamGameStartUpdate {ally, opponents} => self.handle_team_game_start_update(&ally, opponents),
+            Message::PlayerTurnUpdate {nickname: nn} => self.handle_player_turn_update(&nn),
+            Message::PlayerShotUpdate {target, x, y, hit, ..} => self.handle_player_shot_update(&target, x, y, hit),
+            Message::BoardVisibleUpdate {nickname: nn, x, y} => self.handle_board_visible_update(&nn, x, y),
+            Message::BoardInvisibleUpdate {nickname: nn, x, y} => self.handle_board_invisible_update(&nn, x, y),
+            Message::BoardDamagedUpdate {nickname: nn, x, y} => self.handle_board_damaged_update(&nn, x, y),
+            Message::PlayerEliminatedUpdate {nickname: nn} => self.handle_player_eliminated_update(&nn),
             // RESPONSES
             Message::OkResponse => self.handle_ok_response(msg.clone()).unwrap(),
             Message::FeaturesResponse {features: fts} => self.handle_get_features_response(fts),
             Message::NameTakenResponse {nickname: nn} => self.handle_name_taken_response(&nn),
+            Message::AccountRequiredResponse => self.handle_account_required_response(),
             Message::NoSuchPlayerResponse {nickname: nn} => self.handle_no_such_player_response(&nn),
             Message::NotWaitingResponse {nickname: nn} => self.handle_not_waiting_response(&nn),
             Message::HitResponse {x, y} => self.handle_hit_response(x, y),
             Message::MissResponse {x, y} => self.handle_miss_response(x, y),
             Message::DestroyedResponse {x, y} => self.handle_destroyed_response(x, y),
+            Message::RadarResponse {x, y, ship_detected} => self.handle_radar_response(x, y, ship_detected),
+            Message::InvalidPlacementResponse {errors} => self.handle_invalid_placement_response(errors),
             _ => {},
         }
     }
@@ -588,6 +1009,20 @@ impl State {
                         Message::EnemyMissUpdate {..} |
                         Message::EnemyVisibleUpdate {..} | 
                         Message::EnemyInvisibleUpdate {..} |
+                        Message::EnemyDamagedUpdate {..} |
+                        Message::EnemyRadarUpdate {..} |
+                        Message::EnemyTorpedoUpdate {..} |
+                        Message::ChargesUpdate {..} |
+                        Message::EnemyReconUpdate {..} |
+                        Message::AbilityCooldownUpdate {..} |
+                        Message::PlayerTurnUpdate {..} |
+                        Message::PlayerShotUpdate {..} |
+                        Message::BoardVisibleUpdate {..} |
+                        Message::BoardInvisibleUpdate {..} |
+                        Message::BoardDamagedUpdate {..} |
+                        Message::PlayerEliminatedUpdate {..} |
+                        Message::ReconResponse {..} |
+                        Message::RadarResponse {..} |
                         Message::HitResponse {..} |
                         Message::MissResponse {..} |
                         Message::DestroyedResponse {..} => self.send_updated_boards(),
@@ -611,10 +1046,17 @@ impl State {
                     Message::ReadyRequest => { self.ready(); },
                     Message::NotReadyRequest => { self.unready(); },
                     Message::ChallengePlayerRequest { username } => { self.challenge(&username); },
+                    Message::ChallengePlayersRequest { usernames } => { self.challenge_players(usernames); },
+                    Message::ChallengeTeamRequest { ally, opponents } => { self.challenge_team(ally, opponents); },
                     Message::PlaceShipsRequest { placement } => { self.place_ships( placement ); },
                     Message::ShootRequest { x, y } => { self.shoot( x, y ); },
                     Message::MoveAndShootRequest { id, direction, x, y } => { self.move_and_shoot( x, y, id, direction ); },
+                    Message::ShootAtRequest { nickname, x, y } => { self.shoot_at(nickname, x, y); },
+                    Message::MoveAndShootAtRequest { nickname, id, direction, x, y } => { self.move_and_shoot_at(nickname, x, y, id, direction); },
                     Message::SurrenderRequest => { self.surrender(); },
+                    Message::RadarRequest { x, y } => { self.radar(x, y); },
+                    Message::TorpedoRequest { x, y, direction } => { self.torpedo(x, y, direction); },
+                    Message::AbilityRequest { id, direction, x, y } => { self.use_ability(x, y, id, direction); },
                     m => panic!("Received illegal request from client: {:?}", m),
                 }
             } 
@@ -1,36 +1,30 @@
-use std::cell::RefCell;
 use std::net::UdpSocket;
-use std::collections::HashMap;
-use std::io::{BufReader, BufWriter, Write};
-use std::net::{Ipv4Addr, TcpListener, TcpStream};
-use std::option::Option::None;
-use std::rc::Rc;
-use std::sync::mpsc;
+use std::io::Write;
+use std::net::{Ipv4Addr, TcpListener};
 use std::thread;
 
-extern crate net2;
-use net2::UdpSocketExt;
+extern crate mio;
+
+extern crate signal_hook;
+
+extern crate toml;
 
 extern crate byteorder;
 use byteorder::{BigEndian, WriteBytesExt};
 
 extern crate ansi_term;
-use ansi_term::Colour::{Green, Yellow, Cyan};
 
 extern crate time;
 
 extern crate argparse;
-use argparse::{ArgumentParser, Print, Store};
+use argparse::{ArgumentParser, Collect, Print, StoreOption, StoreTrue};
 
 extern crate rusty_battleships;
-use rusty_battleships::message::{serialize_message, deserialize_message, Message};
-use rusty_battleships::board;
-use rusty_battleships::board::{ToMainThreadCommand, ToChildCommand};
-use rusty_battleships::game::Game;
-use rusty_battleships::timer::timer_periodic;
+use rusty_battleships::accounts::Accounts;
 
 mod server_;
-use server_::state;
+use server_::config::{Config, ConfigSource, Overrides};
+use server_::reactor::Server;
 
 // http://stackoverflow.com/questions/35157399/how-to-concatenate-static-strings-in-rust/35159310
 macro_rules! description {
@@ -43,23 +37,23 @@ macro_rules! version_string {
     () => ( concat!(description!(), " v", version!()) )
 }
 
-const TICK_DURATION_MS: u64 = 100;
-
-fn start_udp_discovery(tcp_port: u16) {
-    let socket = match UdpSocket::bind("0.0.0.0:49001") {
+fn start_udp_discovery(config: &Config) {
+    let tcp_port = config.port;
+    let socket = match UdpSocket::bind((Ipv4Addr::new(0, 0, 0, 0), config.discovery_port)) {
         Ok(s) => s,
         Err(e) => {
             println!("Couldn't bind socket: {}", e);
             return;
         }
     };
-    match socket.join_multicast_v4(&Ipv4Addr::new(224, 0, 0, 250), &Ipv4Addr::new(0, 0, 0, 0)) {
+    match socket.join_multicast_v4(&config.discovery_group, &Ipv4Addr::new(0, 0, 0, 0)) {
         Err(why) => {
             println!("Couldn't join multicast group: {:?}", why);
             return;
         },
         Ok(_) => {
-            println!("Joined UDP multicast group 224.0.0.250, listening on port 49001 for UDP discovery");
+            println!("Joined UDP multicast group {}, listening on port {} for UDP discovery",
+                     config.discovery_group, config.discovery_port);
         },
     };
     socket.set_read_timeout(None).unwrap(); // blocking reads
@@ -92,243 +86,114 @@ fn start_udp_discovery(tcp_port: u16) {
     thread::spawn(udp_discovery_loop);
 }
 
-fn respond(response_msg: Message, buff_writer: &mut BufWriter<TcpStream>) -> Result<(), std::io::Error> {
-    let serialized_msg = serialize_message(response_msg);
-    try!(buff_writer.write(&serialized_msg[..])); //.expect("Could not write to TCP steam, exiting.");
-    try!(buff_writer.flush()); //.expect("Could not write to TCP steam, exiting.");
-    Ok(())
-}
-
-fn handle_client(stream: TcpStream, tx: mpsc::SyncSender<ToMainThreadCommand>, rx: mpsc::Receiver<ToChildCommand>) {
-    println!("New incoming TCP stream");
-
-    let response_stream = stream.try_clone().unwrap();
-    let mut buff_reader = BufReader::new(stream);
-    let tick = timer_periodic(TICK_DURATION_MS);
-
-    // launch thread receiving messages from child TCP endpoint
-    thread::spawn(move || {
-        loop {
-            let request = deserialize_message(&mut buff_reader);
-            match request {
-                Ok(msg) => tx.send(ToMainThreadCommand::Message(msg)).unwrap(),
-                Err(e) => {
-                    tx.send(ToMainThreadCommand::Error(e)).unwrap();
-                    return;
-                },
-            }
-            tick.recv().expect("Timer thread died unexpectedly."); // wait for next tick
-        }
-    });
-
-    let mut buff_writer = BufWriter::new(response_stream);
-    loop {
-        let response = rx.recv().unwrap();
-        match response {
-            ToChildCommand::TerminateConnection => return,
-            ToChildCommand::Message(Message::InvalidRequestResponse) => {
-                respond(Message::InvalidRequestResponse, &mut buff_writer).is_err();
-                return;
-            },
-            ToChildCommand::Message(response_msg) => if respond(response_msg, &mut buff_writer).is_err() { return },
-        }
-    }
-}
-
-fn handle_main(msg: Message, player: &mut board::PlayerHandle, lobby: &mut HashMap<String, board::Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> state::Result {
-    // These requests can be handled without any restrictions
-    match msg {
-        Message::GetFeaturesRequest => return state::handle_get_features_request(),
-        Message::ReportErrorRequest { errormessage } => return state::handle_report_error_request(errormessage, player, lobby, games),
-        _ => {},
-    }
-
-    // Login requests on the other hand are only valid if the client is not already logged in, i.e.
-    // their nickname must be None
-    if player.nickname.is_none() {
-        if let Message::LoginRequest { username } = msg {
-            return state::handle_login_request(username, player, lobby);
-        }
-    } else {
-        // All other requests are only valid after logging in, i.e. with a user name
-        assert!(lobby.contains_key(player.nickname.as_ref().unwrap()), "Invalid state: nickname not in lobby.");
-
-        let nickname = player.nickname.as_ref().unwrap();
-
-        match msg {
-            Message::ReadyRequest => return state::handle_ready_request(nickname, lobby),
-            Message::NotReadyRequest => return state::handle_not_ready_request(nickname, lobby),
-            Message::ChallengePlayerRequest { username } => return state::handle_challenge_player_request(username, nickname, lobby, games),
-            Message::SurrenderRequest => return state::handle_surrender_request(nickname, lobby, games),
-            Message::PlaceShipsRequest { placement } => return state::handle_place_ships_request(placement, nickname, lobby),
-            Message::ShootRequest { x, y } => return state::handle_move_shoot_request((x, y), None, nickname, lobby, games),
-            Message::MoveAndShootRequest { id, direction, x, y } => return state::handle_move_shoot_request((x, y), Some((id as usize, direction)), nickname, lobby, games),
-            _ => {},
-        };
-    }
-    return state::Result::respond(Message::InvalidRequestResponse, false);
-}
-
-fn log_msg(index: usize, player_handle: &board::PlayerHandle, color: ansi_term::Colour, msg: &Message) {
-    println!(
-        "#{} ({}): {}", 
-        index, 
-        player_handle.nickname.clone().unwrap_or("".to_owned()), 
-        color.paint(format!("{:?}", msg)));
-}
-
-/**
- * @return.0 Whether the player was terminated
- */
-fn handle_msg(i: usize, player_handle: &mut board::PlayerHandle, msg: Message, mut lobby: &mut HashMap<String, board::Player>, mut games: &mut Vec<Rc<RefCell<Game>>>) -> (bool, state::Result) {
-    log_msg(i, &player_handle, Green, &msg);
-    // Handle Message received from child
-    let result = handle_main(msg, player_handle, &mut lobby, &mut games);
-    if let Some(ref response) = result.response {
-        // handle_main generated a response -> send response Message back to child
-        log_msg(i, &player_handle, Cyan, &response);
-        player_handle.to_child_endpoint.send(ToChildCommand::Message((*response).clone())).unwrap();
-    }
-    if result.terminate_connection {
-        println!("-- Closing connection to child {}", i);
-        player_handle.to_child_endpoint.send(ToChildCommand::TerminateConnection).unwrap();
-        return (false, result);
-    }
-    (true, result)
+/// Binds a port that only serves the machine the server runs on.
+fn listen_locally(port: u16, purpose: &str) -> mio::net::TcpListener {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+            .expect(&format!("Could not bind to port {} for {}", port, purpose));
+    println!("Accepting {} on 127.0.0.1:{}.", purpose, port);
+    mio::net::TcpListener::from_std(listener)
+            .expect("Could not watch the listening socket.")
 }
 
 fn main() {
-    let mut port:u16 = 5000;
-    let mut ip = Ipv4Addr::new(0,0,0,0);
+    let mut source = ConfigSource { path: None, overrides: Overrides::default() };
 
     {  // this block limits scope of borrows by ap.refer() method
+        let overrides = &mut source.overrides;
         let mut ap = ArgumentParser::new();
         ap.set_description(description!());
-        ap.refer(&mut ip).add_argument("IP", Store, "IPv4 address to listen to");
-        ap.refer(&mut port).add_option(&["-p", "--port"], Store, "port to listen on");
+        ap.refer(&mut source.path).add_option(&["-c", "--config"], StoreOption,
+            "configuration file, reloaded on SIGHUP; the options below take precedence");
+        ap.refer(&mut overrides.ip).add_argument("IP", StoreOption, "IPv4 address to listen to (default 0.0.0.0)");
+        ap.refer(&mut overrides.port).add_option(&["-p", "--port"], StoreOption, "port to listen on (default 5000)");
+        ap.refer(&mut overrides.radar_charges).add_option(&["--radar-charges"], StoreOption,
+            "number of radar sweeps per player and game (default 0)");
+        ap.refer(&mut overrides.torpedo_charges).add_option(&["--torpedo-charges"], StoreOption,
+            "number of torpedoes per player and game (default 0)");
+        ap.refer(&mut overrides.ship_abilities).add_option(&["--ship-abilities"], StoreTrue,
+            "enable ship class abilities (reconnaissance, submerge, double move)");
+        ap.refer(&mut overrides.ability_cooldown).add_option(&["--ability-cooldown"], StoreOption,
+            "number of own turns until a ship ability can be used again (default 3)");
+        ap.refer(&mut overrides.max_turns).add_option(&["--max-turns"], StoreOption,
+            "number of turns after which the game is decided by remaining hit points");
+        ap.refer(&mut overrides.no_touching).add_option(&["--no-touching"], StoreTrue,
+            "do not allow ships to be placed next to each other");
+        ap.refer(&mut overrides.damaged_segments_miss).add_option(&["--damaged-segments-miss"], StoreTrue,
+            "count shots at already damaged ship segments as misses");
+        ap.refer(&mut overrides.turn_seconds).add_option(&["--turn-seconds"], StoreOption,
+            "number of seconds a player has for each turn (default 60)");
+        ap.refer(&mut overrides.afk_strikes).add_option(&["--afk-strikes"], StoreOption,
+            "number of skipped turns after which a player loses (default 3)");
+        ap.refer(&mut overrides.accounts_file).add_option(&["--accounts"], StoreOption,
+            "let players register, keeping their accounts in the given file");
+        ap.refer(&mut overrides.no_guests).add_option(&["--no-guests"], StoreTrue,
+            "only admit players with an account");
+        ap.refer(&mut overrides.bots).add_option(&["--bot"], Collect,
+            "host a computer player of the given difficulty (easy, medium or hard), may be repeated");
+        ap.refer(&mut overrides.grace_period).add_option(&["--grace-period"], StoreOption,
+            "seconds running games may take to finish when the server is shut down (default 0)");
+        ap.refer(&mut overrides.tick_ms).add_option(&["--tick-ms"], StoreOption,
+            "longest time in milliseconds between checks for turns that took too long (default 100)");
+        ap.refer(&mut overrides.no_discovery).add_option(&["--no-discovery"], StoreTrue,
+            "do not answer UDP discovery requests");
+        ap.refer(&mut overrides.discovery_group).add_option(&["--discovery-group"], StoreOption,
+            "multicast group to listen to for discovery requests (default 224.0.0.250)");
+        ap.refer(&mut overrides.discovery_port).add_option(&["--discovery-port"], StoreOption,
+            "port to listen on for discovery requests (default 49001)");
+        ap.refer(&mut overrides.admin_port).add_option(&["--admin-port"], StoreOption,
+            "accept administration commands on this port of 127.0.0.1 (disabled by default)");
+        ap.refer(&mut overrides.metrics_port).add_option(&["--metrics-port"], StoreOption,
+            "serve metrics at /metrics and a health check at /health over HTTP on this port of 127.0.0.1 (disabled by default)");
         ap.add_option(&["-v", "--version"], Print(version_string!().to_owned()),
         "show version number");
         ap.parse_args_or_exit();
     }
 
-    println!("Operating as server on port {}.", port);
-
-    let listener = TcpListener::bind((ip, port))
-            .expect(&format!("Could not bind to port {}", port));
-    let address = listener.local_addr()
-            .expect("Could not get local address.");
-    println!("Started listening on port {} at address {}.", port, address);
-    let mut player_handles = Vec::new();
-    let mut lobby = HashMap::new();
-
-    // channel for letting tcp_loop tell main loop about new players
-    let (tx_tcp_players, rx_main_players) : (mpsc::Sender<board::PlayerHandle>, mpsc::Receiver<board::PlayerHandle>) = mpsc::channel();
-
-    let tcp_loop = move || {
-        for stream in listener.incoming() {
-            // channel child --> main
-            let (tx_child, rx_main) = mpsc::sync_channel(0);
-            // channel main --> child
-            let (tx_main, rx_child) = mpsc::channel();
-
-            thread::spawn(move || {
-                handle_client(stream.unwrap(), tx_child, rx_child);
-            });
-            tx_tcp_players.send(
-                board::PlayerHandle {
-                    nickname: None,
-                    from_child_endpoint: rx_main,
-                    to_child_endpoint: tx_main,
-                }
-            ).expect("Main thread died, exiting.");
-        }
+    let config = match source.load() {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        },
     };
 
-    thread::spawn(tcp_loop);
-    start_udp_discovery(port);
+    println!("Operating as server on port {}.", config.port);
 
-    let mut message_store: HashMap<String, Vec<Message>> = HashMap::new();
-    let mut games: Vec<Rc<RefCell<Game>>> = vec![];
-    // stores player name -> game
-
-    let tick = timer_periodic(TICK_DURATION_MS);
-    // Main loop
-    loop {
-        // Receive new players from tcp_loop
-        if let Ok(player_handle) = rx_main_players.try_recv() {
-            player_handles.push(player_handle);
-        }
-        // Receive Messages from child threads
-	    let mut valid = vec![true; player_handles.len()];
-        for (i, player_handle) in player_handles.iter_mut().enumerate() {
-            match player_handle.from_child_endpoint.try_recv() {
-                Ok(ToMainThreadCommand::Message(msg)) => {
-                    let (player_terminated, result) = handle_msg(i, player_handle, msg, &mut lobby, &mut games);
-                    if player_terminated {
-                        valid[i] = false;
-                    }
-                    if !result.updates.is_empty() {
-                        message_store = result.updates;
-                        break;
-                    }
-                },
-                Ok(ToMainThreadCommand::Error(ref e)) => {
-                    if let Some(ref name) = player_handle.nickname {
-                        message_store = state::terminate_player(name, &mut lobby, &mut games);
-                    }
-                    valid[i] = false;
-                    match e.kind() {
-                        std::io::ErrorKind::UnexpectedEof => {
-                            println!("Client terminated connection");
-                            player_handle.to_child_endpoint.send(ToChildCommand::TerminateConnection).unwrap();
-                        },
-                        _ => {
-                            println!("Got error: {:?}", e);
-                            // Ignoring return value of respond() since we are exiting anyway
-                            player_handle.to_child_endpoint.send(ToChildCommand::Message(Message::InvalidRequestResponse)).unwrap();
-                        }
-                    }
-                },
-                _ => {},
-            }
-        }
-
-	    // delete old handles
-	    for (i, is_valid) in valid.iter().enumerate() {
-		    if !is_valid {
-			    player_handles.remove(i);
-		    }
-	    }
-
-        // Send all updates from result
-        send_updates(&mut player_handles, &mut message_store);
+    let listener = TcpListener::bind((config.ip, config.port))
+            .expect(&format!("Could not bind to port {}", config.port));
+    let address = listener.local_addr()
+            .expect("Could not get local address.");
+    println!("Started listening on port {} at address {}.", config.port, address);
+    let listener = mio::net::TcpListener::from_std(listener)
+            .expect("Could not watch the listening socket.");
 
-        // Send updates issued by handle_afk() for all games with exceeded turn times
-        let mut afk_games: Vec<Rc<RefCell<Game>>> = vec![];
-        for game in &games {
-            if (*game).borrow_mut().turn_time_exceeded() {
-                afk_games.push(game.clone());
-            }
-        }
-        for game in &afk_games {
-            let mut result = state::handle_afk(game.clone(), &mut lobby, &mut games);
-            send_updates(&mut player_handles, &mut result);
-        }
+    let admin_listener = config.admin_port.map(|port| listen_locally(port, "administration commands"));
+    let metrics_listener = config.metrics_port.map(|port| listen_locally(port, "metrics requests"));
 
-        tick.recv().expect("Timer thread died unexpectedly."); // wait for next tick
+    if config.discovery {
+        start_udp_discovery(&config);
+    }
+    let accounts = config.accounts_file.as_ref().map(|path| match Accounts::open(path) {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        },
+    });
+    let bots = config.bots.clone();
+    let mut server = Server::new(listener, admin_listener, metrics_listener, config, source)
+            .expect("Could not set up event loop.");
+    if let Some(accounts) = accounts {
+        server.use_accounts(accounts);
+    }
+    for difficulty in bots {
+        println!("Hosting a computer player ({:?}).", difficulty);
+        server.host_bot(difficulty);
     }
-}
 
-fn send_updates(player_handles: &mut Vec<board::PlayerHandle>, message_store: &mut HashMap<String, Vec<Message>>) {
-    for (i, player_handle) in player_handles.iter_mut().enumerate() {
-        if let Some(ref name) = player_handle.nickname {
-            if message_store.contains_key(name) {
-                for message in message_store.remove(name).unwrap() {
-                    println!("#{} ({}): {}", i, name, Yellow.paint(format!("{:?}", message)));
-                    player_handle.to_child_endpoint.send(ToChildCommand::Message(message)).unwrap();
-                }
-            }
-        }
+    if let Err(e) = server.run() {
+        println!("Server stopped: {}", e);
+        std::process::exit(1);
     }
-    message_store.clear();
+    println!("Server stopped.");
 }
@@ -0,0 +1,89 @@
+use std::net::IpAddr;
+
+/// Lines describing the commands of the admin port
+pub const HELP: [&'static str; 10] = [
+    "players                 list connected clients and their state",
+    "games                   list running games with turn count and scores",
+    "kick NICKNAME           disconnect a player",
+    "ban NICKNAME            disconnect a player and refuse connections from their address",
+    "unban ADDRESS           accept connections from the address again",
+    "bans                    list banned addresses",
+    "end NICKNAME            end the player's game as a draw",
+    "broadcast TEXT          send a message to everybody connected",
+    "set SETTING VALUE       change a setting as in the configuration file, e.g. set rules.turn_seconds 30",
+    "help                    show this list",
+];
+
+/**
+ * Command sent to the admin port, one per line. The server answers with any number of lines,
+ * followed by `ok` or `error` and a description of what went wrong.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Players,
+    Games,
+    Kick(String),
+    Ban(String),
+    Unban(IpAddr),
+    Bans,
+    End(String),
+    Broadcast(String),
+    Set(String, String),
+    Help,
+}
+
+/// Splits off the first word of a line.
+fn split_word(line: &str) -> (&str, &str) {
+    let line = line.trim();
+    match line.find(char::is_whitespace) {
+        Some(end) => (&line[..end], line[end..].trim()),
+        None => (line, ""),
+    }
+}
+
+fn nickname(command: &str, rest: &str) -> Result<String, String> {
+    if rest.is_empty() || rest.contains(char::is_whitespace) {
+        return Err(format!("{} needs a nickname", command));
+    }
+    Ok(rest.to_owned())
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let (command, rest) = split_word(line);
+        let without_arguments = |parsed: Command| if rest.is_empty() {
+            Ok(parsed)
+        } else {
+            Err(format!("{} takes no arguments", command))
+        };
+        match command {
+            "players" => without_arguments(Command::Players),
+            "games" => without_arguments(Command::Games),
+            "bans" => without_arguments(Command::Bans),
+            "help" => without_arguments(Command::Help),
+            "kick" => Ok(Command::Kick(try!(nickname(command, rest)))),
+            "ban" => Ok(Command::Ban(try!(nickname(command, rest)))),
+            "end" => Ok(Command::End(try!(nickname(command, rest)))),
+            "unban" => match rest.parse() {
+                Ok(address) => Ok(Command::Unban(address)),
+                Err(_) => Err("unban needs an IP address".to_owned()),
+            },
+            "broadcast" => {
+                // the protocol only carries short strings of printable ASCII characters
+                if rest.is_empty() || rest.len() > 255 || rest.chars().any(|c| c < ' ' || c > '~') {
+                    return Err("broadcast needs a text of at most 255 printable ASCII characters".to_owned());
+                }
+                Ok(Command::Broadcast(rest.to_owned()))
+            },
+            "set" => {
+                let (setting, value) = split_word(rest);
+                if setting.is_empty() || value.is_empty() {
+                    return Err("set needs a setting and a value".to_owned());
+                }
+                Ok(Command::Set(setting.to_owned(), value.to_owned()))
+            },
+            "" => Err("empty command, try help".to_owned()),
+            _ => Err(format!("unknown command {}, try help", command)),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::io::Result;
+
+use rusty_battleships::bot::Bot;
+use rusty_battleships::message::Message;
+use rusty_battleships::strategy::{Strategy, RandomStrategy, HuntTargetStrategy, ProbabilityStrategy};
+
+/// Strength of a computer player hosted by the server
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        match &name.to_lowercase()[..] {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            _ => None,
+        }
+    }
+
+    /// Nickname the bot logs in with, a number is appended if several bots share a difficulty
+    pub fn nickname(&self) -> String {
+        format!("Bot-{:?}", self)
+    }
+
+    fn strategy(&self) -> Box<Strategy> {
+        match *self {
+            Difficulty::Easy => Box::new(RandomStrategy::new(None)),
+            Difficulty::Medium => Box::new(HuntTargetStrategy::new(None)),
+            Difficulty::Hard => Box::new(ProbabilityStrategy::new(None)),
+        }
+    }
+}
+
+/**
+ * A computer player hosted in the server process. It is treated like a client connection, so its
+ * requests go through the same handlers, but it answers every message right away instead of
+ * sending its requests over the network.
+ */
+pub struct HostedBot {
+    bot: Bot,
+    difficulty: Difficulty,
+}
+
+impl HostedBot {
+    /// The bot along with the first request it sends
+    pub fn new(difficulty: Difficulty) -> (HostedBot, Message) {
+        let mut bot = Bot::new(difficulty.nickname(), difficulty.strategy(), None);
+        let request = bot.connect();
+        return (HostedBot { bot: bot, difficulty: difficulty }, request);
+    }
+
+    /**
+     * Passes a message on to the bot. Fails if the bot gives up, after which it is to be treated
+     * like a client whose connection broke.
+     * @return the requests the bot sends in reply
+     */
+    pub fn receive(&mut self, msg: Message) -> Result<Vec<Message>> {
+        let mut requests = try!(self.bot.handle_message(msg));
+        // back to the lobby after every game
+        if self.bot.get_outcome().is_some() {
+            requests.push(self.bot.play_again(self.difficulty.strategy()));
+        }
+        return Ok(requests);
+    }
+}
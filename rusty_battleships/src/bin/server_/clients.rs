@@ -0,0 +1,160 @@
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+use std::net::IpAddr;
+
+use mio::net::TcpStream;
+
+use rusty_battleships::message::{serialize_message, deserialize_message, Message};
+
+use server_::bots::HostedBot;
+
+/// Number of bytes read from a socket at once
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Longest line accepted on the admin and metrics ports, the connection is dropped when one is longer
+pub const MAX_LINE_BYTES: usize = 8192;
+
+/// Most bytes queued for a client that does not read them, it is disconnected when there would be more
+const MAX_OUTGOING_BYTES: usize = 1 << 20;
+
+/**
+ * A client connected over TCP. Reading and writing never block: incoming bytes are buffered until
+ * a message (or a line, on the admin and metrics ports) is complete, outgoing ones until the socket accepts them.
+ */
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> Connection {
+        Connection { stream: stream, incoming: vec![], outgoing: vec![] }
+    }
+
+    pub fn get_stream(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    pub fn peer_address(&self) -> Option<IpAddr> {
+        self.stream.peer_addr().ok().map(|address| address.ip())
+    }
+
+    /**
+     * Reads everything that has arrived, failing once more than `limit` bytes are waiting to be
+     * handled. @return Whether the client closed the connection
+     */
+    fn read_available(&mut self, limit: usize) -> Result<bool> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(true),
+                Ok(count) => {
+                    self.incoming.extend_from_slice(&chunk[..count]);
+                    // a line has to end within the limit, but several complete ones may be waiting
+                    let unfinished = self.incoming.iter().rev().position(|&byte| byte == b'\n').unwrap_or(self.incoming.len());
+                    if unfinished > limit {
+                        return Err(Error::new(ErrorKind::InvalidData, "Line too long."));
+                    }
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn closed_error() -> Error {
+        Error::new(ErrorKind::UnexpectedEof, "Client closed the connection.")
+    }
+
+    /**
+     * Reads everything that has arrived and appends the complete messages to `messages`. Fails
+     * once the client closed the connection, with `UnexpectedEof`, or sent something that is not
+     * a message.
+     */
+    pub fn receive(&mut self, messages: &mut Vec<Message>) -> Result<()> {
+        // messages are short, so anything that is not one is noticed long before the limit
+        let closed = try!(self.read_available(::std::usize::MAX));
+
+        loop {
+            let (result, length) = {
+                let mut cursor = Cursor::new(&self.incoming[..]);
+                let result = deserialize_message(&mut cursor);
+                (result, cursor.position() as usize)
+            };
+            match result {
+                Ok(msg) => {
+                    messages.push(msg);
+                    self.incoming.drain(..length);
+                },
+                // the rest of the message has not arrived yet
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if closed {
+            return Err(Connection::closed_error());
+        }
+        Ok(())
+    }
+
+    /// Like `receive`, but for lines of text of at most `MAX_LINE_BYTES`
+    pub fn receive_lines(&mut self, lines: &mut Vec<String>) -> Result<()> {
+        let closed = try!(self.read_available(MAX_LINE_BYTES));
+        while let Some(end) = self.incoming.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.incoming.drain(..(end + 1)).collect();
+            lines.push(String::from_utf8_lossy(&line).trim_end().to_owned());
+        }
+        if closed {
+            return Err(Connection::closed_error());
+        }
+        Ok(())
+    }
+
+    /// Queues a message and sends as much as the socket accepts right away
+    pub fn send(&mut self, msg: Message) -> Result<()> {
+        self.send_bytes(&serialize_message(msg))
+    }
+
+    pub fn send_line(&mut self, line: &str) -> Result<()> {
+        let mut bytes = line.as_bytes().to_vec();
+        bytes.push(b'\n');
+        self.send_bytes(&bytes)
+    }
+
+    /// Fails if the client has left too much of what was sent to it unread
+    pub fn send_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.outgoing.len() + bytes.len() > MAX_OUTGOING_BYTES {
+            return Err(Error::new(ErrorKind::Other, "Client does not read what is sent to it."));
+        }
+        self.outgoing.extend_from_slice(bytes);
+        self.flush()
+    }
+
+    /// Sends as much of the queued messages as the socket accepts
+    pub fn flush(&mut self) -> Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing[..]) {
+                Ok(0) => return Err(Error::new(ErrorKind::WriteZero, "Client does not accept any data.")),
+                Ok(count) => {
+                    self.outgoing.drain(..count);
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {},
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_flushed(&self) -> bool {
+        self.outgoing.is_empty()
+    }
+}
+
+/// Where the messages for a client go
+pub enum Endpoint {
+    Remote(Connection),
+    Hosted(HostedBot),
+}
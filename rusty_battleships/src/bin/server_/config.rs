@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::net::Ipv4Addr;
+use std::result;
+
+use toml::{Parser, Value};
+
+use rusty_battleships::rules::Rules;
+
+use server_::bots::Difficulty;
+
+type Result<T> = result::Result<T, String>;
+
+/**
+ * Server settings. They are read from a configuration file, if any, and the command line takes
+ * precedence. The listening addresses, hosted bots and discovery are set up once at startup; all
+ * other settings can be reloaded while the server is running.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+    /// Longest time in milliseconds between two checks for turns that took too long
+    pub tick_ms: u64,
+    /// Number of seconds running games may take to finish once the server is shut down
+    pub grace_period: u64,
+    pub bots: Vec<Difficulty>,
+    /// Whether clients can find the server by multicast
+    pub discovery: bool,
+    pub discovery_group: Ipv4Addr,
+    pub discovery_port: u16,
+    /// Port on the loopback interface for administration, if any
+    pub admin_port: Option<u16>,
+    /// Port on the loopback interface for Prometheus metrics and health checks over HTTP, if any
+    pub metrics_port: Option<u16>,
+    /// File with the registered players, if players can register
+    pub accounts_file: Option<String>,
+    /// Whether players without an account can log in, when players can register
+    pub guests: bool,
+    /// Rules of new games, which also decide the features the server advertises
+    pub rules: Rules,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            ip: Ipv4Addr::new(0, 0, 0, 0),
+            port: 5000,
+            tick_ms: 100,
+            grace_period: 0,
+            bots: vec![],
+            discovery: true,
+            discovery_group: Ipv4Addr::new(224, 0, 0, 250),
+            discovery_port: 49001,
+            admin_port: None,
+            metrics_port: None,
+            accounts_file: None,
+            guests: true,
+            rules: Rules::default(),
+        }
+    }
+}
+
+/// Settings given on the command line
+#[derive(Default)]
+pub struct Overrides {
+    pub ip: Option<Ipv4Addr>,
+    pub port: Option<u16>,
+    pub tick_ms: Option<u64>,
+    pub grace_period: Option<u64>,
+    /// Replace the bots of the configuration file, unless empty
+    pub bots: Vec<String>,
+    pub no_discovery: bool,
+    pub discovery_group: Option<Ipv4Addr>,
+    pub discovery_port: Option<u16>,
+    pub admin_port: Option<u16>,
+    pub metrics_port: Option<u16>,
+    pub accounts_file: Option<String>,
+    pub no_guests: bool,
+    pub turn_seconds: Option<u16>,
+    pub afk_strikes: Option<u8>,
+    pub radar_charges: Option<u8>,
+    pub torpedo_charges: Option<u8>,
+    pub ship_abilities: bool,
+    pub ability_cooldown: Option<u8>,
+    pub max_turns: Option<u16>,
+    pub no_touching: bool,
+    pub damaged_segments_miss: bool,
+}
+
+/// Where the configuration comes from, so it can be read again when it is reloaded
+pub struct ConfigSource {
+    pub path: Option<String>,
+    pub overrides: Overrides,
+}
+
+impl ConfigSource {
+    /// Reads the configuration file and applies the command line on top
+    pub fn load(&self) -> Result<Config> {
+        let mut config = Config::default();
+        if let Some(ref path) = self.path {
+            let table = try!(read_file(path));
+            try!(config.apply_file(&table).map_err(|e| format!("{}: {}", path, e)));
+        }
+        try!(self.overrides.apply(&mut config));
+        try!(config.validate());
+        Ok(config)
+    }
+}
+
+fn read_file(path: &str) -> Result<BTreeMap<String, Value>> {
+    let mut text = String::new();
+    try!(File::open(path)
+         .and_then(|mut file| file.read_to_string(&mut text))
+         .map_err(|e| format!("Could not read {}: {}", path, e)));
+    parse(&text).map_err(|e| format!("{}:{}", path, e))
+}
+
+fn parse(text: &str) -> Result<BTreeMap<String, Value>> {
+    let mut parser = Parser::new(text);
+    match parser.parse() {
+        Some(table) => Ok(table),
+        None => {
+            let error = &parser.errors[0];
+            let (line, column) = parser.to_linecol(error.lo);
+            Err(format!("{}:{}: {}", line + 1, column + 1, error.desc))
+        },
+    }
+}
+
+fn integer(value: &Value, key: &str, min: i64, max: i64) -> Result<i64> {
+    match value.as_integer() {
+        Some(number) if min <= number && number <= max => Ok(number),
+        _ => Err(format!("{} has to be an integer from {} to {}", key, min, max)),
+    }
+}
+
+fn boolean(value: &Value, key: &str) -> Result<bool> {
+    value.as_bool().ok_or(format!("{} has to be true or false", key))
+}
+
+fn address(value: &Value, key: &str) -> Result<Ipv4Addr> {
+    match value.as_str().map(|text| text.parse()) {
+        Some(Ok(address)) => Ok(address),
+        _ => Err(format!("{} has to be an IPv4 address like \"127.0.0.1\"", key)),
+    }
+}
+
+fn table<'a>(value: &'a Value, key: &str) -> Result<&'a BTreeMap<String, Value>> {
+    value.as_table().ok_or(format!("{} has to be a table", key))
+}
+
+fn difficulty(name: &str) -> Result<Difficulty> {
+    Difficulty::from_name(name).ok_or(format!("Unknown bot difficulty {}, choose one of: easy, medium, hard", name))
+}
+
+impl Config {
+    fn apply_file(&mut self, settings: &BTreeMap<String, Value>) -> Result<()> {
+        for (key, value) in settings {
+            match &key[..] {
+                "ip" => self.ip = try!(address(value, key)),
+                "port" => self.port = try!(integer(value, key, 0, 65535)) as u16,
+                "tick_ms" => self.tick_ms = try!(integer(value, key, 1, 60000)) as u64,
+                "grace_period" => self.grace_period = try!(integer(value, key, 0, 86400)) as u64,
+                "bots" => {
+                    let names = try!(value.as_slice().ok_or(format!("{} has to be a list of difficulties", key)));
+                    self.bots.clear();
+                    for name in names {
+                        let name = try!(name.as_str().ok_or(format!("{} has to be a list of difficulties", key)));
+                        self.bots.push(try!(difficulty(name)));
+                    }
+                },
+                "admin_port" => self.admin_port = Some(try!(integer(value, key, 1, 65535)) as u16),
+                "metrics_port" => self.metrics_port = Some(try!(integer(value, key, 1, 65535)) as u16),
+                "accounts" => try!(self.apply_accounts(try!(table(value, key)))),
+                "discovery" => try!(self.apply_discovery(try!(table(value, key)))),
+                "rules" => try!(self.apply_rules(try!(table(value, key)))),
+                _ => return Err(format!("Unknown setting {}", key)),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_accounts(&mut self, settings: &BTreeMap<String, Value>) -> Result<()> {
+        for (key, value) in settings {
+            let name = format!("accounts.{}", key);
+            match &key[..] {
+                "file" => self.accounts_file = Some(try!(value.as_str().ok_or(format!("{} has to be a path", name))).to_owned()),
+                "guests" => self.guests = try!(boolean(value, &name)),
+                _ => return Err(format!("Unknown setting {}", name)),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_discovery(&mut self, settings: &BTreeMap<String, Value>) -> Result<()> {
+        for (key, value) in settings {
+            let name = format!("discovery.{}", key);
+            match &key[..] {
+                "enabled" => self.discovery = try!(boolean(value, &name)),
+                "group" => self.discovery_group = try!(address(value, &name)),
+                "port" => self.discovery_port = try!(integer(value, &name, 1, 65535)) as u16,
+                _ => return Err(format!("Unknown setting {}", name)),
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_rules(&mut self, settings: &BTreeMap<String, Value>) -> Result<()> {
+        let rules = &mut self.rules;
+        for (key, value) in settings {
+            let name = format!("rules.{}", key);
+            match &key[..] {
+                "turn_seconds" => rules.turn_seconds = try!(integer(value, &name, 1, 65535)) as u16,
+                "afk_strikes" => rules.afk_strikes = try!(integer(value, &name, 1, 255)) as u8,
+                "radar_charges" => rules.radar_charges = try!(integer(value, &name, 0, 255)) as u8,
+                "torpedo_charges" => rules.torpedo_charges = try!(integer(value, &name, 0, 255)) as u8,
+                "ship_abilities" => rules.ship_abilities = try!(boolean(value, &name)),
+                "ability_cooldown" => rules.ability_cooldown = try!(integer(value, &name, 0, 255)) as u8,
+                "max_turns" => rules.max_turns = Some(try!(integer(value, &name, 1, 65535)) as u16),
+                "no_touching" => rules.no_touching = try!(boolean(value, &name)),
+                "damaged_segments_miss" => rules.damaged_segments_miss = try!(boolean(value, &name)),
+                _ => return Err(format!("Unknown setting {}", name)),
+            }
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.tick_ms == 0 {
+            return Err("The tick duration has to be at least 1 ms.".to_owned());
+        }
+        if self.rules.turn_seconds == 0 {
+            return Err("Players need at least 1 second per turn.".to_owned());
+        }
+        if self.rules.afk_strikes == 0 {
+            return Err("Players need at least 1 AFK strike.".to_owned());
+        }
+        if self.rules.max_turns == Some(0) {
+            return Err("The turn limit has to be at least 1.".to_owned());
+        }
+        if !self.guests && self.accounts_file.is_none() {
+            return Err("Guests can only be turned away when players can register, which needs an accounts file.".to_owned());
+        }
+        if self.discovery && !self.discovery_group.is_multicast() {
+            return Err(format!("{} is not a multicast address.", self.discovery_group));
+        }
+        Ok(())
+    }
+
+    /**
+     * Takes over the settings that only apply at startup from the running configuration.
+     * @return The names of those settings that differed
+     */
+    pub fn keep_startup_settings(&mut self, running: &Config) -> Vec<&'static str> {
+        let mut changed = vec![];
+        if (self.ip, self.port) != (running.ip, running.port) {
+            changed.push("listening address");
+        }
+        if self.admin_port != running.admin_port {
+            changed.push("admin port");
+        }
+        if self.metrics_port != running.metrics_port {
+            changed.push("metrics port");
+        }
+        if self.accounts_file != running.accounts_file {
+            changed.push("accounts file");
+        }
+        if self.bots != running.bots {
+            changed.push("bots");
+        }
+        if (self.discovery, self.discovery_group, self.discovery_port) != (running.discovery, running.discovery_group, running.discovery_port) {
+            changed.push("discovery");
+        }
+        self.ip = running.ip;
+        self.port = running.port;
+        self.admin_port = running.admin_port;
+        self.metrics_port = running.metrics_port;
+        self.accounts_file = running.accounts_file.clone();
+        self.bots = running.bots.clone();
+        self.discovery = running.discovery;
+        self.discovery_group = running.discovery_group;
+        self.discovery_port = running.discovery_port;
+        return changed;
+    }
+
+    /**
+     * Changes a single setting that can be reloaded, written as in the configuration file,
+     * e.g. `rules.no_touching` and `true`.
+     */
+    pub fn set(&mut self, setting: &str, value: &str) -> Result<()> {
+        let text = match setting.find('.') {
+            Some(dot) => format!("[{}]\n{} = {}\n", &setting[..dot], &setting[(dot + 1)..], value),
+            None => format!("{} = {}\n", setting, value),
+        };
+        let mut changed = self.clone();
+        try!(changed.apply_file(&try!(parse(&text))));
+        try!(changed.validate());
+        if !changed.keep_startup_settings(self).is_empty() {
+            return Err(format!("{} can only be changed with a restart", setting));
+        }
+        *self = changed;
+        Ok(())
+    }
+}
+
+impl Overrides {
+    fn apply(&self, config: &mut Config) -> Result<()> {
+        if let Some(ip) = self.ip { config.ip = ip; }
+        if let Some(port) = self.port { config.port = port; }
+        if let Some(tick_ms) = self.tick_ms { config.tick_ms = tick_ms; }
+        if let Some(grace_period) = self.grace_period { config.grace_period = grace_period; }
+        if !self.bots.is_empty() {
+            config.bots.clear();
+            for name in &self.bots {
+                config.bots.push(try!(difficulty(name)));
+            }
+        }
+        if self.no_discovery { config.discovery = false; }
+        if let Some(group) = self.discovery_group { config.discovery_group = group; }
+        if let Some(port) = self.discovery_port { config.discovery_port = port; }
+        if self.admin_port.is_some() { config.admin_port = self.admin_port; }
+        if self.metrics_port.is_some() { config.metrics_port = self.metrics_port; }
+        if self.accounts_file.is_some() { config.accounts_file = self.accounts_file.clone(); }
+        if self.no_guests { config.guests = false; }
+
+        let rules = &mut config.rules;
+        if let Some(turn_seconds) = self.turn_seconds { rules.turn_seconds = turn_seconds; }
+        if let Some(afk_strikes) = self.afk_strikes { rules.afk_strikes = afk_strikes; }
+        if let Some(radar_charges) = self.radar_charges { rules.radar_charges = radar_charges; }
+        if let Some(torpedo_charges) = self.torpedo_charges { rules.torpedo_charges = torpedo_charges; }
+        if let Some(ability_cooldown) = self.ability_cooldown { rules.ability_cooldown = ability_cooldown; }
+        if self.max_turns.is_some() { rules.max_turns = self.max_turns; }
+        // flags can only switch rules on
+        rules.ship_abilities |= self.ship_abilities;
+        rules.no_touching |= self.no_touching;
+        rules.damaged_segments_miss |= self.damaged_segments_miss;
+        Ok(())
+    }
+}
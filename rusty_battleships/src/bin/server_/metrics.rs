@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use time;
+
+use rusty_battleships::message::{serialize_message, Message};
+
+/// Longest HTTP request head accepted, the connection is dropped when it is longer
+pub const MAX_REQUEST_HEAD_BYTES: usize = 16384;
+
+/// Values that are looked up in the server's state whenever the metrics are requested
+pub struct Gauges {
+    pub connections: usize,
+    pub players: usize,
+    pub placing_games: usize,
+    pub running_games: usize,
+}
+
+/**
+ * Counters of everything that happened since the server was started, served in the Prometheus
+ * text format together with the gauges.
+ */
+#[derive(Default)]
+pub struct Metrics {
+    accepted_connections: u64,
+    received: BTreeMap<u8, u64>,
+    sent: BTreeMap<u8, u64>,
+    invalid_requests: u64,
+    afk_terminations: u64,
+    turn_seconds_sum: f64,
+    turn_count: u64,
+}
+
+/// Opcode the message is sent with. Messages are small, so serializing one again is cheap.
+fn opcode(msg: &Message) -> u8 {
+    serialize_message(msg.clone())[0]
+}
+
+fn write_header(text: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(text, "# HELP {} {}", name, help).unwrap();
+    writeln!(text, "# TYPE {} {}", name, kind).unwrap();
+}
+
+fn write_by_opcode(text: &mut String, name: &str, help: &str, counts: &BTreeMap<u8, u64>) {
+    write_header(text, name, "counter", help);
+    for (opcode, count) in counts {
+        writeln!(text, "{}{{opcode=\"{}\"}} {}", name, opcode, count).unwrap();
+    }
+}
+
+impl Metrics {
+    pub fn count_connection(&mut self) {
+        self.accepted_connections += 1;
+    }
+
+    pub fn count_received(&mut self, msg: &Message) {
+        *self.received.entry(opcode(msg)).or_insert(0) += 1;
+    }
+
+    pub fn count_sent(&mut self, msg: &Message) {
+        *self.sent.entry(opcode(msg)).or_insert(0) += 1;
+    }
+
+    /// A request that was not allowed in the client's state, or data that is not a message at all
+    pub fn count_invalid_request(&mut self) {
+        self.invalid_requests += 1;
+    }
+
+    /// A player lost their game for skipping too many turns
+    pub fn count_afk_termination(&mut self) {
+        self.afk_terminations += 1;
+    }
+
+    pub fn count_turn(&mut self, duration: time::Duration) {
+        self.turn_seconds_sum += duration.num_microseconds().unwrap_or(0) as f64 / 1e6;
+        self.turn_count += 1;
+    }
+
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut text = String::new();
+        write_header(&mut text, "battleships_connections", "gauge", "Clients connected over the network.");
+        writeln!(text, "battleships_connections {}", gauges.connections).unwrap();
+        write_header(&mut text, "battleships_connections_accepted_total", "counter", "Connections accepted since the server was started.");
+        writeln!(text, "battleships_connections_accepted_total {}", self.accepted_connections).unwrap();
+        write_header(&mut text, "battleships_players", "gauge", "Players logged in, including hosted bots.");
+        writeln!(text, "battleships_players {}", gauges.players).unwrap();
+        write_header(&mut text, "battleships_games", "gauge", "Games by state.");
+        writeln!(text, "battleships_games{{state=\"placing\"}} {}", gauges.placing_games).unwrap();
+        writeln!(text, "battleships_games{{state=\"running\"}} {}", gauges.running_games).unwrap();
+        write_by_opcode(&mut text, "battleships_messages_received_total", "Requests received, by opcode.", &self.received);
+        write_by_opcode(&mut text, "battleships_messages_sent_total", "Responses and updates sent, by opcode.", &self.sent);
+        write_header(&mut text, "battleships_invalid_requests_total", "counter", "Requests answered with InvalidRequestResponse.");
+        writeln!(text, "battleships_invalid_requests_total {}", self.invalid_requests).unwrap();
+        write_header(&mut text, "battleships_afk_terminations_total", "counter", "Players who lost for skipping too many turns.");
+        writeln!(text, "battleships_afk_terminations_total {}", self.afk_terminations).unwrap();
+        write_header(&mut text, "battleships_turn_duration_seconds", "summary", "Time players took for their turns.");
+        writeln!(text, "battleships_turn_duration_seconds_sum {}", self.turn_seconds_sum).unwrap();
+        writeln!(text, "battleships_turn_duration_seconds_count {}", self.turn_count).unwrap();
+        return text;
+    }
+}
+
+/**
+ * Builds the answer to an HTTP request for the path in `request_line`, e.g. `GET /metrics HTTP/1.1`.
+ * The connection is closed after every answer.
+ */
+pub fn http_response<F>(request_line: &str, healthy: bool, metrics: F) -> Vec<u8> where F: FnOnce() -> String {
+    let mut words = request_line.split_whitespace();
+    let (status, content_type, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", "text/plain; version=0.0.4", metrics()),
+        (Some("GET"), Some("/health")) if healthy => ("200 OK", "text/plain", "ok\n".to_owned()),
+        (Some("GET"), Some("/health")) => ("503 Service Unavailable", "text/plain", "shutting down\n".to_owned()),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+        _ => ("405 Method Not Allowed", "text/plain", "only GET is supported\n".to_owned()),
+    };
+    format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status, content_type, body.len(), body).into_bytes()
+}
@@ -1 +1,12 @@
-pub mod state;
\ No newline at end of file
+pub mod admin;
+pub mod bots;
+pub mod clients;
+pub mod config;
+pub mod metrics;
+pub mod reactor;
+pub mod registry;
+pub mod state;
+pub mod throttle;
+
+#[cfg(test)]
+mod tests;
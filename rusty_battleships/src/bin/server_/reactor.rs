@@ -0,0 +1,829 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Error, ErrorKind, Result};
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::result;
+use std::time::{Duration, Instant};
+
+use ansi_term;
+use ansi_term::Colour::{Green, Yellow, Cyan};
+
+use mio::{Events, Poll, PollOpt, Ready, Token};
+use mio::net::TcpListener;
+
+use rusty_battleships::accounts::Accounts;
+use rusty_battleships::board::Player;
+use rusty_battleships::game::Game;
+use rusty_battleships::message::{redacted, Message};
+use signal_hook::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use server_::admin::{Command, HELP};
+use server_::bots::{Difficulty, HostedBot};
+use server_::clients::{Connection, Endpoint};
+use server_::config::{Config, ConfigSource};
+use server_::metrics::{http_response, Gauges, Metrics, MAX_REQUEST_HEAD_BYTES};
+use server_::registry::{Client, ConnectionId, Registry};
+use server_::state;
+use server_::throttle::{Attempt, Throttle};
+
+const LISTENER: Token = Token(0);
+const SIGNALS: Token = Token(1);
+const ADMIN: Token = Token(2);
+const METRICS: Token = Token(3);
+
+/// Longest time spent sending the last messages to a client that is disconnected, or to all
+/// clients after shutting down
+const CLOSE_TIMEOUT_MS: u64 = 1000;
+
+/// Number of readiness events handled per wakeup
+const EVENT_CAPACITY: usize = 1024;
+
+/// Something that happened to a client and is still to be handled
+enum ClientEvent {
+    Request(ConnectionId, Message),
+    /// The client sent something the server does not understand
+    Invalid(ConnectionId, Error),
+    /// The connection broke or the client left it
+    Disconnected(ConnectionId, Error),
+}
+
+/// An HTTP request on the metrics port, answered once its head is complete
+struct Scrape {
+    connection: Connection,
+    request_line: Option<String>,
+    // bytes of the request head received so far
+    head_bytes: usize,
+    answered: bool,
+}
+
+/**
+ * Event-driven core of the server: a single thread waits for any of the connections to become
+ * readable or writable and handles every request as soon as it is complete. Clients only cost the
+ * memory for their buffers, and computer players hosted by the server are handled the same way.
+ */
+pub struct Server {
+    poll: Poll,
+    // None once the server is shutting down
+    listener: Option<TcpListener>,
+    admin_listener: Option<TcpListener>,
+    // connections to the admin port, they share the IDs of clients for their tokens
+    admins: HashMap<ConnectionId, Connection>,
+    bans: HashSet<IpAddr>,
+    metrics_listener: Option<TcpListener>,
+    scrapes: HashMap<ConnectionId, Scrape>,
+    metrics: Metrics,
+    // None unless players can register
+    accounts: Option<Accounts>,
+    throttle: Throttle,
+    signals: Signals,
+    config: Config,
+    config_source: ConfigSource,
+    /// When shutting down, the time after which running games are not waited for anymore
+    shutdown_deadline: Option<Instant>,
+    clients: Registry,
+    // requests and disconnections in the order they happened
+    pending: VecDeque<ClientEvent>,
+    lobby: HashMap<String, Player>,
+    games: Vec<Rc<RefCell<Game>>>,
+    // games whose turns are counted in the metrics, kept after they ended until that happened
+    timed_games: Vec<Rc<RefCell<Game>>>,
+}
+
+fn handle_main(msg: Message, nickname: &mut Option<String>, lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>,
+               config: &Config, accounts: &mut Option<Accounts>, guests: bool) -> state::Result {
+    let rules = &config.rules;
+    // These requests can be handled without any restrictions
+    match msg {
+        Message::GetFeaturesRequest => return state::handle_get_features_request(config),
+        Message::ReportErrorRequest { errormessage } => return state::handle_report_error_request(errormessage),
+        _ => {},
+    }
+
+    // Login requests on the other hand are only valid if the client is not already logged in, i.e.
+    // their nickname must be None
+    if nickname.is_none() {
+        match (msg, accounts.as_mut()) {
+            (Message::LoginRequest { username }, accounts) =>
+                return state::handle_guest_login_request(username, nickname, lobby, accounts.map(|accounts| &*accounts), guests),
+            (Message::RegisterRequest { username, password }, Some(accounts)) =>
+                return state::handle_register_request(username, password, nickname, lobby, accounts),
+            (Message::PasswordLoginRequest { username, password }, Some(accounts)) =>
+                return state::handle_password_login_request(username, password, nickname, lobby, accounts),
+            (Message::TokenLoginRequest { username, token }, Some(accounts)) =>
+                return state::handle_token_login_request(username, token, nickname, lobby, accounts),
+            _ => {},
+        }
+    } else {
+        // All other requests are only valid after logging in, i.e. with a user name
+        assert!(lobby.contains_key(nickname.as_ref().unwrap()), "Invalid state: nickname not in lobby.");
+
+        let nickname = nickname.as_ref().unwrap();
+
+        match msg {
+            Message::ReadyRequest => return state::handle_ready_request(nickname, lobby),
+            Message::NotReadyRequest => return state::handle_not_ready_request(nickname, lobby),
+            Message::ChallengePlayerRequest { username } => return state::handle_challenge_player_request(username, nickname, lobby, games, rules),
+            Message::ChallengePlayersRequest { usernames } => return state::handle_challenge_players_request(usernames, nickname, lobby, games, rules),
+            Message::ChallengeTeamRequest { ally, opponents } => return state::handle_challenge_team_request(ally, opponents, nickname, lobby, games, rules),
+            Message::SurrenderRequest => return state::handle_surrender_request(nickname, lobby, games),
+            Message::PlaceShipsRequest { placement } => return state::handle_place_ships_request(placement, nickname, lobby),
+            Message::ShootRequest { x, y } => return state::handle_move_shoot_request(None, (x, y), None, nickname, lobby, games),
+            Message::MoveAndShootRequest { id, direction, x, y } => return state::handle_move_shoot_request(None, (x, y), Some((id as usize, direction)), nickname, lobby, games),
+            Message::ShootAtRequest { nickname: target, x, y } => return state::handle_move_shoot_request(Some(target), (x, y), None, nickname, lobby, games),
+            Message::MoveAndShootAtRequest { nickname: target, id, direction, x, y } => return state::handle_move_shoot_request(Some(target), (x, y), Some((id as usize, direction)), nickname, lobby, games),
+            Message::RadarRequest { x, y } => return state::handle_radar_request(x, y, nickname, lobby, games),
+            Message::TorpedoRequest { x, y, direction } => return state::handle_torpedo_request(x, y, direction, nickname, lobby, games),
+            Message::AbilityRequest { id, direction, x, y } => return state::handle_ability_request(id, direction, x, y, nickname, lobby, games),
+            _ => {},
+        };
+    }
+    return state::Result::respond(Message::InvalidRequestResponse, false);
+}
+
+fn log_msg(id: ConnectionId, nickname: Option<&String>, color: ansi_term::Colour, msg: &Message) {
+    println!(
+        "#{} ({}): {}",
+        id,
+        nickname.cloned().unwrap_or("".to_owned()),
+        color.paint(format!("{:?}", redacted(msg))));
+}
+
+impl Server {
+    /**
+     * SIGINT and SIGTERM shut the server down, asking again during the grace period shuts it down
+     * right away. SIGHUP reloads the configuration from `config_source`. Commands for the
+     * administrator are accepted on `admin_listener`, requests for metrics on `metrics_listener`.
+     */
+    pub fn new(listener: TcpListener, admin_listener: Option<TcpListener>, metrics_listener: Option<TcpListener>,
+               config: Config, config_source: ConfigSource) -> Result<Server> {
+        let poll = try!(Poll::new());
+        try!(poll.register(&listener, LISTENER, Ready::readable(), PollOpt::edge()));
+        if let Some(ref admin_listener) = admin_listener {
+            try!(poll.register(admin_listener, ADMIN, Ready::readable(), PollOpt::edge()));
+        }
+        if let Some(ref metrics_listener) = metrics_listener {
+            try!(poll.register(metrics_listener, METRICS, Ready::readable(), PollOpt::edge()));
+        }
+        let signals = try!(Signals::new(&[SIGINT, SIGTERM, SIGHUP]));
+        try!(poll.register(&signals, SIGNALS, Ready::readable(), PollOpt::edge()));
+        Ok(Server {
+            poll: poll,
+            listener: Some(listener),
+            admin_listener: admin_listener,
+            admins: HashMap::new(),
+            bans: HashSet::new(),
+            metrics_listener: metrics_listener,
+            scrapes: HashMap::new(),
+            metrics: Metrics::default(),
+            accounts: None,
+            throttle: Throttle::default(),
+            signals: signals,
+            config: config,
+            config_source: config_source,
+            shutdown_deadline: None,
+            clients: Registry::new(METRICS.0 + 1),
+            pending: VecDeque::new(),
+            lobby: HashMap::new(),
+            games: vec![],
+            timed_games: vec![],
+        })
+    }
+
+    /// Lets players register, reserving their nicknames
+    pub fn use_accounts(&mut self, accounts: Accounts) {
+        self.accounts = Some(accounts);
+    }
+
+    /// Computer players join the lobby like any client
+    pub fn host_bot(&mut self, difficulty: Difficulty) {
+        let (bot, request) = HostedBot::new(difficulty);
+        let id = self.clients.add(Endpoint::Hosted(bot));
+        self.pending.push_back(ClientEvent::Request(id, request));
+    }
+
+    /// Serves clients until the server has been shut down
+    pub fn run(&mut self) -> Result<()> {
+        let mut events = Events::with_capacity(EVENT_CAPACITY);
+        loop {
+            try!(self.poll.poll(&mut events, Some(Duration::from_millis(self.config.tick_ms))));
+            for event in events.iter() {
+                match event.token() {
+                    LISTENER => self.accept_connections(),
+                    SIGNALS => try!(self.handle_signals()),
+                    ADMIN => self.accept_admins(),
+                    METRICS => self.accept_scrapes(),
+                    Token(id) if self.admins.contains_key(&id) => self.serve_admin(id),
+                    Token(id) if self.scrapes.contains_key(&id) => self.serve_scrape(id),
+                    Token(id) => {
+                        if event.readiness().is_readable() {
+                            self.read_from(id);
+                        }
+                        if event.readiness().is_writable() {
+                            self.write_to(id);
+                        }
+                    },
+                }
+            }
+            self.handle_pending();
+            self.check_timers();
+            self.handle_pending();
+            if let Some(deadline) = self.shutdown_deadline {
+                self.dismiss_idle(deadline);
+                self.handle_pending();
+            }
+            self.count_turns();
+            for id in self.clients.finished(Duration::from_millis(CLOSE_TIMEOUT_MS)) {
+                self.remove(id);
+            }
+
+            if let Some(deadline) = self.shutdown_deadline {
+                if self.clients.is_empty() || Instant::now() >= deadline + Duration::from_millis(CLOSE_TIMEOUT_MS) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn handle_signals(&mut self) -> Result<()> {
+        let signals: Vec<i32> = self.signals.pending().collect();
+        for signal in signals {
+            if signal == SIGHUP {
+                self.reload_config();
+            } else {
+                try!(self.begin_shutdown());
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies a changed configuration file to new games, settings needed at startup stay as they are.
+    fn reload_config(&mut self) {
+        println!("Reloading the configuration.");
+        let mut config = match self.config_source.load() {
+            Ok(config) => config,
+            Err(e) => {
+                println!("Keeping the previous configuration: {}", e);
+                return;
+            },
+        };
+        for setting in config.keep_startup_settings(&self.config) {
+            println!("Changing the {} only takes effect after a restart.", setting);
+        }
+        self.config = config;
+    }
+
+    fn begin_shutdown(&mut self) -> Result<()> {
+        if self.shutdown_deadline.is_some() {
+            println!("Shutting down right away.");
+            self.shutdown_deadline = Some(Instant::now());
+            return Ok(());
+        }
+
+        println!("Shutting down, running games may take {} more seconds.", self.config.grace_period);
+        self.shutdown_deadline = Some(Instant::now() + Duration::from_secs(self.config.grace_period));
+        // new clients are refused from now on
+        if let Some(listener) = self.listener.take() {
+            try!(self.poll.deregister(&listener));
+        }
+        self.announce_shutdown();
+        Ok(())
+    }
+
+    /// Tells every client that the server is shutting down, players in a game as well.
+    fn announce_shutdown(&mut self) {
+        let in_game = format!("The server is shutting down, running games may take {} more seconds.",
+                              self.config.grace_period);
+        for id in self.clients.open() {
+            let errormessage = if self.is_playing(id) { in_game.clone() } else { "The server is shutting down.".to_owned() };
+            self.deliver(id, Message::ServerGoingDownUpdate { errormessage: errormessage });
+        }
+    }
+
+    /// Whether the client is logged in and in a running game.
+    fn is_playing(&self, id: ConnectionId) -> bool {
+        self.clients.get(id)
+            .and_then(|client| client.nickname())
+            .and_then(|nickname| self.lobby.get(nickname))
+            .map_or(false, |player| player.game.is_some())
+    }
+
+    /**
+     * While shutting down, disconnects clients that are not in a game, as they have been told
+     * already. Once the deadline has passed, players still in a game are disconnected as well.
+     */
+    fn dismiss_idle(&mut self, deadline: Instant) {
+        let expired = Instant::now() >= deadline;
+        for id in self.clients.open() {
+            if expired || !self.is_playing(id) {
+                self.close(id);
+            }
+        }
+    }
+
+    fn accept_connections(&mut self) {
+        loop {
+            let accepted = match self.listener {
+                Some(ref listener) => listener.accept(),
+                None => return,
+            };
+            match accepted {
+                Ok((stream, address)) => {
+                    if self.bans.contains(&address.ip()) {
+                        println!("Refused connection from banned address {}", address);
+                        continue;
+                    }
+                    // responses are small and should not wait for more data
+                    let _ = stream.set_nodelay(true);
+                    self.metrics.count_connection();
+                    let id = self.clients.add(Endpoint::Remote(Connection::new(stream)));
+                    println!("New incoming TCP stream from {} as #{}", address, id);
+                    let registered = match self.clients.get(id) {
+                        Some(&Client { endpoint: Endpoint::Remote(ref connection), .. }) =>
+                            self.poll.register(connection.get_stream(), Token(id), Ready::readable() | Ready::writable(), PollOpt::edge()),
+                        _ => unreachable!(),
+                    };
+                    if let Err(e) = registered {
+                        println!("Could not watch connection #{}: {}", id, e);
+                        self.clients.close(id);
+                        self.clients.remove(id);
+                    }
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    println!("Could not accept connection: {}", e);
+                    return;
+                },
+            }
+        }
+    }
+
+    fn read_from(&mut self, id: ConnectionId) {
+        let mut messages = vec![];
+        let result = match self.clients.get_mut(id) {
+            Some(&mut Client { endpoint: Endpoint::Remote(ref mut connection), ref state }) if !state.is_closing() =>
+                connection.receive(&mut messages),
+            _ => return,
+        };
+        for msg in messages {
+            self.pending.push_back(ClientEvent::Request(id, msg));
+        }
+        match result {
+            Ok(()) => {},
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => self.pending.push_back(ClientEvent::Disconnected(id, Error::new(e.kind(), e.to_string()))),
+            Err(e) => self.pending.push_back(ClientEvent::Invalid(id, e)),
+        }
+    }
+
+    fn write_to(&mut self, id: ConnectionId) {
+        let result = match self.clients.get_mut(id) {
+            Some(&mut Client { endpoint: Endpoint::Remote(ref mut connection), .. }) => connection.flush(),
+            _ => return,
+        };
+        if let Err(e) = result {
+            self.pending.push_back(ClientEvent::Disconnected(id, e));
+        }
+    }
+
+    /// Passes a message on to a client, unless it is being disconnected
+    fn deliver(&mut self, id: ConnectionId, msg: Message) {
+        if !self.clients.is_open(id) {
+            return;
+        }
+        self.metrics.count_sent(&msg);
+        let result = match self.clients.get_mut(id) {
+            Some(&mut Client { ref mut endpoint, .. }) => match *endpoint {
+                Endpoint::Remote(ref mut connection) => connection.send(msg).map(|_| vec![]),
+                Endpoint::Hosted(ref mut bot) => bot.receive(msg),
+            },
+            None => return,
+        };
+        match result {
+            Ok(requests) => {
+                for request in requests {
+                    self.pending.push_back(ClientEvent::Request(id, request));
+                }
+            },
+            Err(e) => self.pending.push_back(ClientEvent::Disconnected(id, e)),
+        }
+    }
+
+    fn send_updates(&mut self, updates: HashMap<String, Vec<Message>>) {
+        for (name, messages) in updates {
+            if let Some(id) = self.clients.find(&name) {
+                for message in messages {
+                    println!("#{} ({}): {}", id, name, Yellow.paint(format!("{:?}", message)));
+                    self.deliver(id, message);
+                }
+            }
+        }
+    }
+
+    /// Handles requests and disconnections, including those caused by handling others
+    fn handle_pending(&mut self) {
+        while let Some(event) = self.pending.pop_front() {
+            match event {
+                ClientEvent::Request(id, msg) => {
+                    // a client being disconnected is not listened to anymore
+                    if !self.clients.is_open(id) {
+                        continue;
+                    }
+                    // no new games are started while shutting down
+                    if self.shutdown_deadline.is_some() && !self.is_playing(id) {
+                        self.close(id);
+                    } else {
+                        self.handle_request(id, msg);
+                    }
+                    // games are only started by requests, so every game is watched before it can end
+                    self.count_turns();
+                },
+                ClientEvent::Invalid(id, e) => {
+                    if self.clients.is_open(id) {
+                        println!("Client #{} sent invalid data: {}", id, e);
+                        self.metrics.count_invalid_request();
+                        self.deliver(id, Message::InvalidRequestResponse);
+                        self.close(id);
+                    }
+                },
+                ClientEvent::Disconnected(id, e) => {
+                    if self.clients.is_open(id) {
+                        println!("Client #{} disconnected: {}", id, e);
+                        self.close(id);
+                    }
+                    // nothing more can be sent over a broken connection
+                    self.remove(id);
+                },
+            }
+        }
+    }
+
+    fn handle_request(&mut self, id: ConnectionId, msg: Message) {
+        let mut nickname = match self.clients.get(id) {
+            Some(client) => client.nickname().cloned(),
+            None => return,
+        };
+        log_msg(id, nickname.as_ref(), Green, &msg);
+        self.metrics.count_received(&msg);
+        let logged_in = nickname.is_some();
+        // the server's own bots are always let in
+        let guests = self.config.guests || match self.clients.get(id) {
+            Some(&Client { endpoint: Endpoint::Hosted(_), .. }) => true,
+            _ => false,
+        };
+        let attempt = Attempt::of(&msg);
+        let address = match self.clients.get(id) {
+            Some(&Client { endpoint: Endpoint::Remote(ref connection), .. }) => connection.peer_address(),
+            _ => None,
+        };
+        let now = Instant::now();
+        if let (Some(attempt), Some(address)) = (attempt, address) {
+            if let Some(wait) = self.throttle.wait(address, attempt, now) {
+                let reason = format!("Too many attempts from your address, try again in {} seconds.", wait.as_secs() + 1);
+                self.kick(id, &reason);
+                return;
+            }
+        }
+        let result = handle_main(msg, &mut nickname, &mut self.lobby, &mut self.games, &self.config, &mut self.accounts, guests);
+        if !logged_in {
+            if let Some(ref name) = nickname {
+                self.clients.log_in(id, name.clone());
+            }
+        }
+
+        if let (Some(attempt), Some(address), Some(response)) = (attempt, address, result.response.as_ref()) {
+            self.throttle.count(address, attempt, response, now);
+        }
+
+        // clients drop the connection after an invalid request, so the server does as well
+        let mut terminate = result.terminate_connection;
+        if let Some(response) = result.response {
+            log_msg(id, nickname.as_ref(), Cyan, &response);
+            if response == Message::InvalidRequestResponse {
+                self.metrics.count_invalid_request();
+                terminate = true;
+            }
+            self.deliver(id, response);
+        }
+        self.send_updates(result.updates);
+        if terminate {
+            println!("-- Closing connection #{}", id);
+            self.close(id);
+        }
+    }
+
+    /// Counts the turns that ended since the last call, including those of games that are over.
+    fn count_turns(&mut self) {
+        for game in &self.games {
+            if !self.timed_games.iter().any(|timed| Rc::ptr_eq(timed, game)) {
+                self.timed_games.push(game.clone());
+            }
+        }
+        for game in &self.timed_games {
+            for duration in game.borrow_mut().pop_turn_durations() {
+                self.metrics.count_turn(duration);
+            }
+        }
+        let games = &self.games;
+        self.timed_games.retain(|timed| games.iter().any(|game| Rc::ptr_eq(game, timed)));
+    }
+
+    /**
+     * Stops handling the client's requests and removes it from the lobby and its game, the
+     * connection is closed once everything was sent.
+     */
+    fn close(&mut self, id: ConnectionId) {
+        if let Some(name) = self.clients.close(id) {
+            let updates = state::terminate_player(&name, &mut self.lobby, &mut self.games);
+            self.send_updates(updates);
+        }
+    }
+
+    /// Forgets a closed client and stops watching its connection.
+    fn remove(&mut self, id: ConnectionId) {
+        if let Some(Client { endpoint: Endpoint::Remote(connection), .. }) = self.clients.remove(id) {
+            let _ = self.poll.deregister(connection.get_stream());
+        }
+    }
+
+    /// Ends turns that took too long
+    fn check_timers(&mut self) {
+        let afk_games: Vec<Rc<RefCell<Game>>> = self.games.iter()
+            .filter(|game| game.borrow().turn_time_exceeded())
+            .cloned()
+            .collect();
+        for game in afk_games {
+            if game.borrow().get_active_player_afk_count() <= 1 {
+                self.metrics.count_afk_termination();
+            }
+            let updates = state::handle_afk(game, &mut self.lobby, &mut self.games);
+            self.send_updates(updates);
+        }
+    }
+
+    fn accept_admins(&mut self) {
+        loop {
+            let accepted = match self.admin_listener {
+                Some(ref listener) => listener.accept(),
+                None => return,
+            };
+            match accepted {
+                Ok((stream, address)) => {
+                    let id = self.clients.reserve_id();
+                    let connection = Connection::new(stream);
+                    match self.poll.register(connection.get_stream(), Token(id), Ready::readable() | Ready::writable(), PollOpt::edge()) {
+                        Ok(()) => {
+                            println!("Administrator connected from {} as #{}", address, id);
+                            self.admins.insert(id, connection);
+                        },
+                        Err(e) => println!("Could not watch admin connection #{}: {}", id, e),
+                    }
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    println!("Could not accept admin connection: {}", e);
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Executes the commands that arrived from an administrator and sends the answers.
+    fn serve_admin(&mut self, id: ConnectionId) {
+        let mut lines = vec![];
+        let mut result = match self.admins.get_mut(&id) {
+            Some(connection) => connection.receive_lines(&mut lines),
+            None => return,
+        };
+        for line in lines {
+            let mut answer = self.execute_admin_line(&line);
+            let connection = self.admins.get_mut(&id).unwrap();
+            for line in answer.drain(..) {
+                result = result.and(connection.send_line(&line));
+            }
+        }
+        if result.is_ok() {
+            result = self.admins.get_mut(&id).unwrap().flush();
+        }
+        if let Err(e) = result {
+            println!("Administrator #{} disconnected: {}", id, e);
+            if let Some(connection) = self.admins.remove(&id) {
+                let _ = self.poll.deregister(connection.get_stream());
+            }
+        }
+    }
+
+    /**
+     * Executes a command of the administrator.
+     * @return The lines to answer with, the last one being `ok` or `error` and the reason
+     */
+    fn execute_admin_line(&mut self, line: &str) -> Vec<String> {
+        println!("Admin: {}", Yellow.paint(line));
+        match Command::parse(line).and_then(|command| self.execute(command)) {
+            Ok(mut output) => {
+                output.push("ok".to_owned());
+                output
+            },
+            Err(e) => vec![format!("error {}", e)],
+        }
+    }
+
+    fn execute(&mut self, command: Command) -> result::Result<Vec<String>, String> {
+        let mut output = vec![];
+        match command {
+            Command::Players => {
+                let mut ids = self.clients.open();
+                ids.sort();
+                for id in ids {
+                    output.push(self.describe_client(id));
+                }
+            },
+            Command::Games => {
+                for game in &self.games {
+                    output.push(describe_game(&game.borrow()));
+                }
+            },
+            Command::Kick(nickname) => {
+                let id = try!(self.find_player(&nickname));
+                self.kick(id, "You have been kicked by the administrator.");
+            },
+            Command::Ban(nickname) => {
+                let id = try!(self.find_player(&nickname));
+                let address = match self.clients.get(id).map(|client| &client.endpoint) {
+                    Some(&Endpoint::Remote(ref connection)) => try!(connection.peer_address()
+                        .ok_or(format!("the address of {} is unknown", nickname))),
+                    _ => return Err(format!("{} is hosted by the server", nickname)),
+                };
+                self.bans.insert(address);
+                // everybody else from the same address is banned as well
+                for other in self.clients.open() {
+                    let same_address = match self.clients.get(other).map(|client| &client.endpoint) {
+                        Some(&Endpoint::Remote(ref connection)) => connection.peer_address() == Some(address),
+                        _ => false,
+                    };
+                    if same_address {
+                        self.kick(other, "You have been banned by the administrator.");
+                    }
+                }
+                output.push(format!("banned {}", address));
+            },
+            Command::Unban(address) => {
+                if !self.bans.remove(&address) {
+                    return Err(format!("{} is not banned", address));
+                }
+            },
+            Command::Bans => {
+                let mut bans: Vec<&IpAddr> = self.bans.iter().collect();
+                bans.sort();
+                for address in bans {
+                    output.push(address.to_string());
+                }
+            },
+            Command::End(nickname) => {
+                let game = try!(self.lobby.get(&nickname)
+                    .and_then(|player| player.game.clone())
+                    .ok_or(format!("{} is not in a game", nickname)));
+                let updates = state::handle_force_end(game, &mut self.lobby, &mut self.games);
+                self.send_updates(updates);
+            },
+            Command::Broadcast(text) => {
+                for id in self.clients.open() {
+                    self.deliver(id, Message::ServerMessageUpdate { message: text.clone() });
+                }
+            },
+            Command::Set(setting, value) => try!(self.config.set(&setting, &value)),
+            Command::Help => output.extend(HELP.iter().map(|line| line.to_string())),
+        }
+        Ok(output)
+    }
+
+    fn find_player(&self, nickname: &String) -> result::Result<ConnectionId, String> {
+        self.clients.find(nickname).ok_or(format!("nobody is logged in as {}", nickname))
+    }
+
+    fn kick(&mut self, id: ConnectionId, reason: &str) {
+        println!("-- Closing connection #{}", id);
+        self.deliver(id, Message::ServerMessageUpdate { message: reason.to_owned() });
+        self.close(id);
+    }
+
+    /// One line for the player list: ID, nickname, state and where the client connected from.
+    fn describe_client(&self, id: ConnectionId) -> String {
+        let client = self.clients.get(id).unwrap();
+        let state = match client.nickname().and_then(|nickname| self.lobby.get(nickname)) {
+            Some(player) => format!("{:?}", player.state),
+            None => "Connected".to_owned(),
+        };
+        let address = match client.endpoint {
+            Endpoint::Remote(ref connection) => connection.peer_address()
+                .map_or("unknown".to_owned(), |address| address.to_string()),
+            Endpoint::Hosted(_) => "hosted".to_owned(),
+        };
+        format!("#{} {} {} {}", id, client.nickname().map_or("-", |nickname| &nickname[..]), state, address)
+    }
+
+    fn accept_scrapes(&mut self) {
+        loop {
+            let accepted = match self.metrics_listener {
+                Some(ref listener) => listener.accept(),
+                None => return,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let id = self.clients.reserve_id();
+                    let connection = Connection::new(stream);
+                    match self.poll.register(connection.get_stream(), Token(id), Ready::readable() | Ready::writable(), PollOpt::edge()) {
+                        Ok(()) => {
+                            self.scrapes.insert(id, Scrape { connection: connection, request_line: None, head_bytes: 0, answered: false });
+                        },
+                        Err(e) => println!("Could not watch metrics connection #{}: {}", id, e),
+                    }
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    println!("Could not accept metrics connection: {}", e);
+                    return;
+                },
+            }
+        }
+    }
+
+    /// Answers an HTTP request once its head has arrived and closes the connection when it was sent.
+    fn serve_scrape(&mut self, id: ConnectionId) {
+        let (mut result, complete) = match self.scrapes.get_mut(&id) {
+            Some(scrape) => {
+                let mut lines = vec![];
+                let mut result = scrape.connection.receive_lines(&mut lines);
+                let mut complete = false;
+                for line in lines {
+                    scrape.head_bytes += line.len() + 1;
+                    if scrape.head_bytes > MAX_REQUEST_HEAD_BYTES && !scrape.answered {
+                        result = Err(Error::new(ErrorKind::InvalidData, "Request head too long."));
+                        break;
+                    }
+                    if scrape.request_line.is_none() {
+                        scrape.request_line = Some(line);
+                    } else if line.is_empty() {
+                        complete = !scrape.answered;
+                    }
+                }
+                (result.and(scrape.connection.flush()), complete)
+            },
+            None => return,
+        };
+
+        if complete {
+            let response = {
+                let request_line = self.scrapes[&id].request_line.as_ref().unwrap();
+                http_response(request_line, self.shutdown_deadline.is_none(), || self.metrics.render(&self.gauges()))
+            };
+            let scrape = self.scrapes.get_mut(&id).unwrap();
+            scrape.answered = true;
+            result = scrape.connection.send_bytes(&response);
+        }
+
+        let done = {
+            let scrape = &self.scrapes[&id];
+            match result {
+                // the client may stop sending once its request is complete
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => !scrape.answered || scrape.connection.is_flushed(),
+                Err(_) => true,
+                Ok(()) => scrape.answered && scrape.connection.is_flushed(),
+            }
+        };
+        if done {
+            if let Some(scrape) = self.scrapes.remove(&id) {
+                let _ = self.poll.deregister(scrape.connection.get_stream());
+            }
+        }
+    }
+
+    fn gauges(&self) -> Gauges {
+        let open = self.clients.open();
+        Gauges {
+            connections: open.iter()
+                .filter(|&&id| match self.clients.get(id) {
+                    Some(&Client { endpoint: Endpoint::Remote(_), .. }) => true,
+                    _ => false,
+                })
+                .count(),
+            players: self.lobby.len(),
+            placing_games: self.games.iter().filter(|game| !game.borrow().is_running()).count(),
+            running_games: self.games.iter().filter(|game| game.borrow().is_running()).count(),
+        }
+    }
+}
+
+/// One line for the game list: the players with their scores, the turn and whose turn it is.
+fn describe_game(game: &Game) -> String {
+    let players: Vec<String> = game.get_player_names().iter()
+        .map(|name| {
+            let (hit_points, ships) = game.get_score(name);
+            format!("{} ({} hit points, {} ships)", name, hit_points, ships)
+        })
+        .collect();
+    if !game.is_running() {
+        return format!("{}: placing ships", players.join(", "));
+    }
+    format!("{}: turn {}, {} to play", players.join(", "), game.get_turn_count() + 1, game.get_active_player())
+}
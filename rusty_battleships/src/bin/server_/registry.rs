@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use server_::clients::Endpoint;
+
+/// Identifies a client for as long as the server runs, IDs are never reused
+pub type ConnectionId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConnectionState {
+    /// Not logged in yet, only features and logging in can be requested
+    Connected,
+    /// In the lobby under the given nickname
+    LoggedIn(String),
+    /// No longer in the lobby since the given time, the connection is closed once the messages
+    /// queued for it have been sent
+    Closing(Instant),
+}
+
+impl ConnectionState {
+    pub fn is_closing(&self) -> bool {
+        match *self {
+            ConnectionState::Closing(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Anybody taking part in the lobby, be it over the network or hosted by the server
+pub struct Client {
+    pub state: ConnectionState,
+    pub endpoint: Endpoint,
+}
+
+impl Client {
+    pub fn nickname(&self) -> Option<&String> {
+        match self.state {
+            ConnectionState::LoggedIn(ref nickname) => Some(nickname),
+            _ => None,
+        }
+    }
+
+    pub fn is_closing(&self) -> bool {
+        self.state.is_closing()
+    }
+}
+
+/**
+ * All clients of the server by connection ID, and the logged in ones by nickname. A client passes
+ * through the states in order, so it leaves the lobby exactly once, when it starts closing.
+ */
+pub struct Registry {
+    clients: HashMap<ConnectionId, Client>,
+    nicknames: HashMap<String, ConnectionId>,
+    next_id: ConnectionId,
+}
+
+impl Registry {
+    pub fn new(first_id: ConnectionId) -> Registry {
+        Registry { clients: HashMap::new(), nicknames: HashMap::new(), next_id: first_id }
+    }
+
+    /// Hands out an ID without adding a client, for connections that are not part of the lobby
+    pub fn reserve_id(&mut self) -> ConnectionId {
+        let id = self.next_id;
+        self.next_id += 1;
+        return id;
+    }
+
+    pub fn add(&mut self, endpoint: Endpoint) -> ConnectionId {
+        let id = self.reserve_id();
+        self.clients.insert(id, Client { state: ConnectionState::Connected, endpoint: endpoint });
+        return id;
+    }
+
+    pub fn get(&self, id: ConnectionId) -> Option<&Client> {
+        self.clients.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: ConnectionId) -> Option<&mut Client> {
+        self.clients.get_mut(&id)
+    }
+
+    /// Whether requests of the client are still handled
+    pub fn is_open(&self, id: ConnectionId) -> bool {
+        self.clients.get(&id).map_or(false, |client| !client.is_closing())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// All clients whose requests are still handled
+    pub fn open(&self) -> Vec<ConnectionId> {
+        self.clients.iter()
+            .filter(|&(_, client)| !client.is_closing())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    pub fn find(&self, nickname: &String) -> Option<ConnectionId> {
+        self.nicknames.get(nickname).cloned()
+    }
+
+    pub fn log_in(&mut self, id: ConnectionId, nickname: String) {
+        if let Some(client) = self.clients.get_mut(&id) {
+            assert_eq!(client.state, ConnectionState::Connected, "Client #{} logged in twice.", id);
+            self.nicknames.insert(nickname.clone(), id);
+            client.state = ConnectionState::LoggedIn(nickname);
+        }
+    }
+
+    /**
+     * Stops handling the client's requests.
+     * @return The nickname the client was logged in with, which has to be removed from the lobby
+     */
+    pub fn close(&mut self, id: ConnectionId) -> Option<String> {
+        let client = match self.clients.get_mut(&id) {
+            Some(client) if !client.is_closing() => client,
+            _ => return None,
+        };
+        let previous = ::std::mem::replace(&mut client.state, ConnectionState::Closing(Instant::now()));
+        match previous {
+            ConnectionState::LoggedIn(nickname) => {
+                self.nicknames.remove(&nickname);
+                Some(nickname)
+            },
+            _ => None,
+        }
+    }
+
+    /// Forgets a client, which has to be closed first
+    pub fn remove(&mut self, id: ConnectionId) -> Option<Client> {
+        assert!(!self.is_open(id), "Client #{} removed without being closed.", id);
+        self.clients.remove(&id)
+    }
+
+    /// Closing clients that have nothing left to send, or did not read it within `timeout`
+    pub fn finished(&self, timeout: Duration) -> Vec<ConnectionId> {
+        self.clients.iter()
+            .filter(|&(_, client)| match (&client.state, &client.endpoint) {
+                (&ConnectionState::Closing(since), &Endpoint::Remote(ref connection)) =>
+                    connection.is_flushed() || since.elapsed() >= timeout,
+                (&ConnectionState::Closing(_), &Endpoint::Hosted(_)) => true,
+                _ => false,
+            })
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
@@ -2,10 +2,15 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use rusty_battleships::message::{ShipPlacement, Direction, Message, Reason};
-use rusty_battleships::board::{Board, PlayerState, Player, PlayerHandle, HitResult};
-use rusty_battleships::ship::Ship;
+use rusty_battleships::accounts::Accounts;
+use rusty_battleships::message::{ShipPlacement, Direction, Message, Outcome, Reason};
+use rusty_battleships::board::{Board, PlayerState, Player, HitResult, W, H};
+use rusty_battleships::ship::Ability;
 use rusty_battleships::game::Game;
+use rusty_battleships::placement;
+use rusty_battleships::rules::Rules;
+
+use server_::config::Config;
 
 // From http://stackoverflow.com/a/28392068
 macro_rules! hashmap {
@@ -70,6 +75,39 @@ fn add_update_lobby_except(lobby: &HashMap<String, Player>, except_player: &Stri
     }
 }
 
+/// Send update to all participants of the game, including eliminated players.
+fn add_update_game(game: &Game, updates: &mut HashMap<String, Vec<Message>>, new_update: Message) {
+    for player in game.get_player_names() {
+        let player_updates = updates.entry(player).or_insert(vec![]);
+        player_updates.push(new_update.clone());
+    }
+}
+
+fn add_update_game_except(game: &Game, except_player: &String,
+        updates: &mut HashMap<String, Vec<Message>>, new_update: Message) {
+    for player in game.get_player_names().into_iter()
+                      .filter(|p| p != except_player) {
+        let player_updates = updates.entry(player).or_insert(vec![]);
+        player_updates.push(new_update.clone());
+    }
+}
+
+/**
+ * Converts the visibility updates of `nickname`'s board into updates naming the board, since
+ * players in a free-for-all game watch several opponents.
+ */
+fn name_board_updates(nickname: &String, board_updates: Vec<Message>) -> Vec<Message> {
+    return board_updates.into_iter().map(|update| match update {
+        Message::EnemyVisibleUpdate { x, y } =>
+            Message::BoardVisibleUpdate { nickname: nickname.clone(), x: x, y: y },
+        Message::EnemyInvisibleUpdate { x, y } =>
+            Message::BoardInvisibleUpdate { nickname: nickname.clone(), x: x, y: y },
+        Message::EnemyDamagedUpdate { x, y } =>
+            Message::BoardDamagedUpdate { nickname: nickname.clone(), x: x, y: y },
+        other => other,
+    }).collect();
+}
+
 fn list_lobby_for(lobby: &HashMap<String, Player>, username: &String) -> Vec<Message> {
     let mut result = vec![];
 
@@ -92,48 +130,152 @@ fn merge_updates(updates: &mut HashMap<String, Vec<Message>>, mut additional_upd
     }
 }
 
-/// Terminates the game and returns the update messages to send
+/**
+ * Switches turns and adds the resulting updates: ability cooldowns and, unless this is a
+ * two-player game, whose turn it is. Once the turn limit is reached, the game ends instead.
+ */
+fn switch_turns(games: &mut Vec<Rc<RefCell<Game>>>, game: Rc<RefCell<Game>>,
+        lobby: &mut HashMap<String, Player>, updates: &mut HashMap<String, Vec<Message>>) {
+    {
+        let mut game_ref = (*game).borrow_mut();
+        game_ref.switch_turns();
+        if !game_ref.turn_limit_reached() {
+            let ability_updates = game_ref.pop_ability_updates();
+            merge_updates(updates, ability_updates);
+            if !game_ref.is_two_player() {
+                let active_player = game_ref.get_active_player();
+                add_update_game(&game_ref, updates, Message::PlayerTurnUpdate { nickname: active_player });
+            }
+            return;
+        }
+    }
+    let turn_limit_updates = handle_turn_limit(game, lobby, games);
+    merge_updates(updates, turn_limit_updates);
+}
+
+/**
+ * Terminates the game and returns the update messages to send. The active player and their
+ * allies are `victorious`, all other participants are not.
+ */
 fn terminate_game(games: &mut Vec<Rc<RefCell<Game>>>, game: Rc<RefCell<Game>>,
         lobby: &mut HashMap<String, Player>, active_player_name: &String, victorious: bool,
         reason: Reason) -> HashMap<String, Vec<Message>> {
+    return end_game(games, game, lobby, reason, |game_ref, player_name| {
+        if player_name == active_player_name && !victorious && reason == Reason::Disconnected {
+            // the player who disconnected cannot be notified anymore
+            return None;
+        }
+        if game_ref.is_ally(player_name, active_player_name) == victorious {
+            return Some(Outcome::Victory);
+        }
+        return Some(Outcome::Defeat);
+    });
+}
+
+/**
+ * Deletes the game, sends all participants back to the lobby and returns the update messages to
+ * send. Players for whom `outcome` returns None are not notified.
+ */
+fn end_game<F>(games: &mut Vec<Rc<RefCell<Game>>>, game: Rc<RefCell<Game>>,
+        lobby: &mut HashMap<String, Player>, reason: Reason, outcome: F) -> HashMap<String, Vec<Message>>
+        where F: Fn(&Game, &String) -> Option<Outcome> {
+    if reason == Reason::Obliterated {
+        game.borrow_mut().end_last_turn();
+    }
     let game_ref = (*game).borrow();
-    let opponent_name = game_ref.get_opponent_name(active_player_name);
     let mut updates = HashMap::new();
 
-    // delete game for active player
+    for player_name in game_ref.get_player_names() {
+        // delete game for player
+        {
+            let player = lobby.get_mut(&player_name).unwrap();
+            player.game = None;
+            player.state = PlayerState::Available;
+        }
+        if let Some(player_outcome) = outcome(&game_ref, &player_name) {
+            let mut player_updates = vec![Message::GameOverUpdate {
+                outcome: player_outcome,
+                reason: reason
+            }];
+            player_updates.extend(list_lobby_for(lobby, &player_name));
+            updates.insert(player_name, player_updates);
+        }
+    }
+
+    // delete game
+    games.retain(|g| (*(*g).borrow()) != (*game_ref));
+
+    return updates;
+}
+
+/**
+ * Removes a player from a free-for-all game. The game goes on as long as at least two players
+ * are left, otherwise the last one standing wins.
+ */
+fn leave_free_for_all(games: &mut Vec<Rc<RefCell<Game>>>, game: Rc<RefCell<Game>>,
+        lobby: &mut HashMap<String, Player>, player_name: &String,
+        reason: Reason) -> HashMap<String, Vec<Message>> {
+    let mut updates = HashMap::new();
+    let living_players;
+
     {
-        let p1 = lobby.get_mut(active_player_name).unwrap();
-        p1.game = None;
-        p1.state = PlayerState::Available;
+        let mut game_ref = (*game).borrow_mut();
+        let was_eliminated = game_ref.is_eliminated(player_name);
+        let was_active = game_ref.my_turn(player_name);
+        game_ref.remove_player(player_name);
+
+        if !was_eliminated {
+            add_update_game(&game_ref, &mut updates,
+                            Message::PlayerEliminatedUpdate { nickname: player_name.clone() });
+        }
+        living_players = game_ref.get_living_players();
+        if living_players.len() > 1 {
+            if game_ref.is_running() && was_active {
+                let ability_updates = game_ref.pop_ability_updates();
+                merge_updates(&mut updates, ability_updates);
+                let active_player = game_ref.get_active_player();
+                add_update_game(&game_ref, &mut updates,
+                                Message::PlayerTurnUpdate { nickname: active_player });
+            } else if !game_ref.is_running() && game_ref.all_boards_placed() {
+                let start_updates = start_game(&mut game_ref);
+                merge_updates(&mut updates, start_updates);
+            }
+        }
+    }
+
+    {
+        let player = lobby.get_mut(player_name).unwrap();
+        player.game = None;
+        player.state = PlayerState::Available;
     }
     if reason != Reason::Disconnected {
-        let mut active_player_updates = vec![Message::GameOverUpdate {
-            reason: reason,
-            victorious: victorious
+        let mut player_updates = vec![Message::GameOverUpdate {
+            outcome: Outcome::Defeat,
+            reason: reason
         }];
-        active_player_updates.extend(list_lobby_for(lobby, active_player_name));
-        updates.insert(active_player_name.clone(), active_player_updates);
+        player_updates.extend(list_lobby_for(lobby, player_name));
+        updates.insert(player_name.clone(), player_updates);
     }
 
-    // delete game for opponent
-    {
-        let p2 = lobby.get_mut(opponent_name).unwrap();
-        p2.game = None;
-        p2.state = PlayerState::Available;
+    if living_players.len() == 1 {
+        let game_over_updates = terminate_game(games, game, lobby, &living_players[0], true, reason);
+        merge_updates(&mut updates, game_over_updates);
     }
-    let mut opponent_updates = vec![Message::GameOverUpdate {
-        reason: reason,
-        victorious: !victorious
-    }];
-    opponent_updates.extend(list_lobby_for(lobby, opponent_name));
-    updates.insert(opponent_name.clone(), opponent_updates);
-
-    // delete game
-    games.retain(|g| (*(*g).borrow()) != (*game_ref));
 
     return updates;
 }
 
+/// Removes a player who surrendered, disconnected or was AFK from their game
+fn leave_game(games: &mut Vec<Rc<RefCell<Game>>>, game: Rc<RefCell<Game>>,
+        lobby: &mut HashMap<String, Player>, player_name: &String,
+        reason: Reason) -> HashMap<String, Vec<Message>> {
+    let free_for_all = (*game).borrow().is_free_for_all();
+    if free_for_all {
+        return leave_free_for_all(games, game, lobby, player_name, reason);
+    }
+    return terminate_game(games, game, lobby, player_name, false, reason);
+}
+
 pub fn terminate_player(name: &String, lobby: &mut HashMap<String, Player>,
         games: &mut Vec<Rc<RefCell<Game>>>) -> HashMap<String, Vec<Message>> {
     assert!(lobby.contains_key(name));
@@ -150,7 +292,7 @@ pub fn terminate_player(name: &String, lobby: &mut HashMap<String, Player>,
     }
 
     if let Some(ref game_ref) = game {
-        result = terminate_game(games, game_ref.clone(), lobby, &name, false, Reason::Disconnected);
+        result = leave_game(games, game_ref.clone(), lobby, &name, Reason::Disconnected);
     } else {
         result = HashMap::new();
     }
@@ -162,13 +304,39 @@ pub fn terminate_player(name: &String, lobby: &mut HashMap<String, Player>,
     return result;
 }
 
-pub fn handle_get_features_request() -> Result {
+pub fn handle_get_features_request(config: &Config) -> Result {
+    let rules = &config.rules;
+    let mut features = vec![];
+    if config.discovery {
+        features.push("UDP Server Discovery".to_owned());
+    }
+    if config.accounts_file.is_some() {
+        features.push("Player Accounts".to_owned());
+        if !config.guests {
+            features.push("Accounts Required".to_owned());
+        }
+    }
+    if rules.special_weapons_enabled() {
+        features.push("Special Weapons".to_owned());
+    }
+    if rules.ship_abilities {
+        features.push("Ship Abilities".to_owned());
+    }
+    if rules.max_turns.is_some() {
+        features.push("Turn Limit".to_owned());
+    }
+    if rules.no_touching {
+        features.push("No Touching Ships".to_owned());
+    }
+    if rules.damaged_segments_miss {
+        features.push("Damaged Segments Miss".to_owned());
+    }
     return Result::respond(Message::FeaturesResponse {
-        features: vec!["UDP Server Discovery".to_owned()]
+        features: features
     }, false);
 }
 
-pub fn handle_login_request(username: String, player: &mut PlayerHandle, lobby: &mut HashMap<String, Player>) -> Result {
+pub fn handle_login_request(username: String, nickname: &mut Option<String>, lobby: &mut HashMap<String, Player>) -> Result {
     if username.len() == 0 {
         return Result::respond(Message::InvalidRequestResponse, false);
     }
@@ -187,18 +355,87 @@ pub fn handle_login_request(username: String, player: &mut PlayerHandle, lobby:
             game: None,
         });
         // Update player struct
-        player.nickname = Some(username.clone());
+        *nickname = Some(username.clone());
 
         return result;
     }
 }
 
 
+/// Logging in without an account, which is only possible with a nickname nobody registered
+pub fn handle_guest_login_request(username: String, nickname: &mut Option<String>, lobby: &mut HashMap<String, Player>,
+        accounts: Option<&Accounts>, guests: bool) -> Result {
+    if let Some(accounts) = accounts {
+        if !guests {
+            return Result::respond(Message::AccountRequiredResponse, false);
+        }
+        if accounts.exists(&username) {
+            return Result::respond(Message::NameTakenResponse { nickname: username }, false);
+        }
+    }
+    return handle_login_request(username, nickname, lobby);
+}
+
+/// Logs in with an account once the credentials have been checked, answering with the token if there is one.
+fn log_in_account(username: String, token: Option<String>, nickname: &mut Option<String>, lobby: &mut HashMap<String, Player>) -> Result {
+    let mut result = handle_login_request(username, nickname, lobby);
+    if let (Some(token), Some(&Message::OkResponse)) = (token, result.response.as_ref()) {
+        result.response = Some(Message::TokenResponse { token: token });
+    }
+    return result;
+}
+
+pub fn handle_register_request(username: String, password: String, nickname: &mut Option<String>,
+        lobby: &mut HashMap<String, Player>, accounts: &mut Accounts) -> Result {
+    if username.len() == 0 || password.len() == 0 {
+        return Result::respond(Message::InvalidRequestResponse, false);
+    }
+    // a guest may be using the nickname right now
+    if lobby.contains_key(&username) {
+        return Result::respond(Message::NameTakenResponse { nickname: username }, false);
+    }
+    match accounts.register(&username, &password) {
+        Ok(Some(token)) => {
+            println!("Registered the account {}", username);
+            return log_in_account(username, Some(token), nickname, lobby);
+        },
+        Ok(None) => return Result::respond(Message::NameTakenResponse { nickname: username }, false),
+        Err(e) => {
+            println!("Could not register {}: {}", username, e);
+            return Result::respond(Message::InvalidRequestResponse, false);
+        },
+    }
+}
+
+pub fn handle_password_login_request(username: String, password: String, nickname: &mut Option<String>,
+        lobby: &mut HashMap<String, Player>, accounts: &mut Accounts) -> Result {
+    // the account is in use, no need to issue another token
+    if lobby.contains_key(&username) {
+        return Result::respond(Message::NameTakenResponse { nickname: username }, false);
+    }
+    match accounts.log_in_with_password(&username, &password) {
+        Ok(Some(token)) => return log_in_account(username, Some(token), nickname, lobby),
+        Ok(None) => return Result::respond(Message::WrongCredentialsResponse, false),
+        Err(e) => {
+            println!("Could not log in {}: {}", username, e);
+            return Result::respond(Message::InvalidRequestResponse, false);
+        },
+    }
+}
+
+pub fn handle_token_login_request(username: String, token: String, nickname: &mut Option<String>,
+        lobby: &mut HashMap<String, Player>, accounts: &Accounts) -> Result {
+    if !accounts.log_in_with_token(&username, &token) {
+        return Result::respond(Message::WrongCredentialsResponse, false);
+    }
+    return log_in_account(username, None, nickname, lobby);
+}
+
 pub fn handle_ready_request(username: &String, lobby: &mut HashMap<String, Player>) -> Result {
     let mut result;
 
     {
-        let mut player = lobby.get_mut(username).unwrap();
+        let player = lobby.get_mut(username).unwrap();
 
         if player.game.is_some() {
             return Result::respond(Message::InvalidRequestResponse, false);
@@ -218,7 +455,7 @@ pub fn handle_not_ready_request(username: &String, lobby: &mut HashMap<String, P
     let mut result;
 
     {
-        let mut player = lobby.get_mut(username).unwrap();
+        let player = lobby.get_mut(username).unwrap();
 
         if player.game.is_some() {
             return Result::respond(Message::GameAlreadyStartedResponse, false);
@@ -234,16 +471,16 @@ pub fn handle_not_ready_request(username: &String, lobby: &mut HashMap<String, P
     return result;
 }
 
-fn initialize_game(player1: &String, player2: &String) -> Rc<RefCell<Game>> {
+fn initialize_game(player1: &String, player2: &String, rules: &Rules) -> Rc<RefCell<Game>> {
     // Unwrapping is safe here since boards with no ships are always valid
     let first_board = Board::try_create(vec![], true).unwrap();
     let second_board = Board::try_create(vec![], true).unwrap();
 
-    return Rc::new(RefCell::new(Game::new(first_board, second_board, (*player1).clone(), (*player2).clone())));
+    return Rc::new(RefCell::new(Game::new(first_board, second_board, (*player1).clone(), (*player2).clone(), *rules)));
 }
 
 pub fn handle_challenge_player_request(challenged_player_name: String, challenger_name: &String,
-        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
+        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>, rules: &Rules) -> Result {
     // Is there a player called challenged_player_name?
     if let Some(ref mut challenged_player) = lobby.get_mut(&challenged_player_name) {
         if challenged_player.game.is_some() || challenged_player.state != PlayerState::Ready {
@@ -259,7 +496,7 @@ pub fn handle_challenge_player_request(challenged_player_name: String, challenge
     }
 
     // Create and save new game
-    let new_game = initialize_game(challenger_name, &challenged_player_name);
+    let new_game = initialize_game(challenger_name, &challenged_player_name, rules);
     lobby.get_mut(challenger_name).unwrap().state = PlayerState::Playing;
     // Set game reference for both players
     lobby.get_mut(challenger_name).unwrap().game = Some(new_game.clone());
@@ -271,6 +508,125 @@ pub fn handle_challenge_player_request(challenged_player_name: String, challenge
     return Result::respond_and_update_single(Message::OkResponse, hashmap![challenged_player_name => vec![update_message]], false);
 }
 
+/// Maximum number of players in a free-for-all game, including the challenger
+const MAX_FREE_FOR_ALL_PLAYERS: usize = 8;
+
+/**
+ * Checks that the challenged players are distinct, not the challenger and waiting to be
+ * challenged. Otherwise the Result to respond with is returned as error.
+ */
+fn check_challenged_players(challenged_player_names: &Vec<String>, challenger_name: &String,
+        lobby: &HashMap<String, Player>) -> ::std::result::Result<(), Result> {
+    for (index, name) in challenged_player_names.iter().enumerate() {
+        if name == challenger_name || challenged_player_names[..index].contains(name) {
+            return Err(Result::respond(Message::InvalidRequestResponse, false));
+        }
+    }
+
+    // Are all challenged players there and waiting?
+    for name in challenged_player_names {
+        match lobby.get(name) {
+            None => return Err(Result::respond(Message::NoSuchPlayerResponse { nickname: name.clone() }, false)),
+            Some(player) => if player.game.is_some() || player.state != PlayerState::Ready {
+                return Err(Result::respond(Message::NotWaitingResponse { nickname: name.clone() }, false));
+            },
+        }
+    }
+
+    return Ok(());
+}
+
+/// Saves the new game and sets the game reference for all its players
+fn register_game(new_game: Game, lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) {
+    let player_names = new_game.get_player_names();
+    let new_game = Rc::new(RefCell::new(new_game));
+    for name in &player_names {
+        let player = lobby.get_mut(name).unwrap();
+        player.state = PlayerState::Playing;
+        player.game = Some(new_game.clone());
+    }
+    games.push(new_game);
+}
+
+/// Board to start with until the player has placed their ships
+fn empty_board() -> Board {
+    // Unwrapping is safe here since boards with no ships are always valid
+    return Board::try_create(vec![], true).unwrap();
+}
+
+/**
+ * Rules for games of more than two players. Special weapons and abilities are aimed at a single
+ * opponent, so they are left out; everything else is played as configured.
+ */
+fn multiplayer_rules(rules: &Rules) -> Rules {
+    Rules { radar_charges: 0, torpedo_charges: 0, ship_abilities: false, ..*rules }
+}
+
+pub fn handle_challenge_players_request(challenged_player_names: Vec<String>, challenger_name: &String,
+        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>, rules: &Rules) -> Result {
+    if challenged_player_names.len() == 1 {
+        return handle_challenge_player_request(challenged_player_names[0].clone(), challenger_name,
+                                               lobby, games, rules);
+    }
+    if challenged_player_names.is_empty() || challenged_player_names.len() + 1 > MAX_FREE_FOR_ALL_PLAYERS {
+        return Result::respond(Message::InvalidRequestResponse, false);
+    }
+    if let Err(result) = check_challenged_players(&challenged_player_names, challenger_name, lobby) {
+        return result;
+    }
+
+    // Create and save new game
+    let mut player_names = vec![challenger_name.clone()];
+    player_names.extend(challenged_player_names);
+    let players = player_names.iter().map(|name| (name.clone(), empty_board())).collect();
+    register_game(Game::new_free_for_all(players, multiplayer_rules(rules)), lobby, games);
+
+    // tell challenged players about the game
+    let mut updates = HashMap::new();
+    for name in &player_names[1..] {
+        let opponents = player_names.iter().filter(|n| *n != name).cloned().collect();
+        updates.insert(name.clone(), vec![Message::FreeForAllStartUpdate { nicknames: opponents }]);
+    }
+    return Result::respond_and_update_single(Message::OkResponse, updates, false);
+}
+
+/// Challenges two opponents to a game against the challenger and their ally
+pub fn handle_challenge_team_request(ally_name: String, opponent_names: Vec<String>,
+        challenger_name: &String, lobby: &mut HashMap<String, Player>,
+        games: &mut Vec<Rc<RefCell<Game>>>, rules: &Rules) -> Result {
+    if opponent_names.len() != 2 {
+        return Result::respond(Message::InvalidRequestResponse, false);
+    }
+    let mut challenged_player_names = vec![ally_name.clone()];
+    challenged_player_names.extend(opponent_names.clone());
+    if let Err(result) = check_challenged_players(&challenged_player_names, challenger_name, lobby) {
+        return result;
+    }
+
+    // Create and save new game
+    let team1 = vec![(challenger_name.clone(), empty_board()), (ally_name.clone(), empty_board())];
+    let team2 = opponent_names.iter().map(|name| (name.clone(), empty_board())).collect();
+    register_game(Game::new_team_game(team1, team2, multiplayer_rules(rules)), lobby, games);
+
+    // tell challenged players about the game
+    let challenger_team = vec![challenger_name.clone(), ally_name.clone()];
+    let updates = hashmap![
+        ally_name => vec![Message::TeamGameStartUpdate {
+            ally: challenger_name.clone(),
+            opponents: opponent_names.clone()
+        }],
+        opponent_names[0].clone() => vec![Message::TeamGameStartUpdate {
+            ally: opponent_names[1].clone(),
+            opponents: challenger_team.clone()
+        }],
+        opponent_names[1].clone() => vec![Message::TeamGameStartUpdate {
+            ally: opponent_names[0].clone(),
+            opponents: challenger_team
+        }]
+    ];
+    return Result::respond_and_update_single(Message::OkResponse, updates, false);
+}
+
 pub fn handle_surrender_request(username: &String, lobby: &mut HashMap<String, Player>,
         games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
     let game;
@@ -285,47 +641,55 @@ pub fn handle_surrender_request(username: &String, lobby: &mut HashMap<String, P
     }
 
     if let Some(ref game) = game {
-        let updates = terminate_game(games, game.clone(), lobby, username, false,
-                                     Reason::Surrendered);
+        let updates = leave_game(games, game.clone(), lobby, username, Reason::Surrendered);
         return Result::respond_and_update_single(Message::OkResponse, updates, false);
     } else {
         return Result::respond(Message::NotYourTurnResponse, false);
     }
 }
 
-pub fn handle_report_error_request(errormessage: String, player: &mut PlayerHandle, lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
-    let mut termination_result = Result {
+pub fn handle_report_error_request(errormessage: String) -> Result {
+    println!("Client reported error: {}", errormessage);
+
+    // Terminate connection to client reporting ErrorRequest, which also ends a running game
+    return Result {
         response: None,
         updates: HashMap::new(),
         terminate_connection: true,
     };
+}
 
-    println!("Client reported error: {}", errormessage);
-
-    // For registered players we need to terminate a running game, if any
-    if let Some(ref username) = player.nickname {
-        termination_result.updates = terminate_player(username, lobby, games);
+/// Starts the game after all fleets have been placed and returns the update messages to send
+fn start_game(game: &mut Game) -> HashMap<String, Vec<Message>> {
+    game.start();
+    let mut updates = HashMap::new();
+    let active_player = game.get_active_player();
+    if !game.is_two_player() {
+        add_update_game(game, &mut updates, Message::PlayerTurnUpdate { nickname: active_player });
+    } else {
+        updates.insert(active_player, vec![Message::YourTurnUpdate]);
+        updates.insert(game.get_waiting_player(), vec![Message::EnemyTurnUpdate]);
     }
 
-    // Terminate connection to client reporting ErrorRequest
-    return termination_result;
-}
-
-fn placement2ships(placement: [ShipPlacement; 5]) -> Vec<Ship> {
-    let mut ships = vec![];
-    let lengths_and_hp = vec![5, 4, 3, 2, 2];
-    for (&ship_placement, &length_and_hp) in placement.iter().zip(lengths_and_hp.iter()) {
-        let ShipPlacement { x, y, direction } = ship_placement;
-        let ship = Ship {
-            x: x as isize,
-            y: y as isize,
-            direction: direction,
-            length: length_and_hp,
-            health_points: length_and_hp,
-        };
-        ships.push(ship);
+    let rules = *game.get_rules();
+    for player in game.get_player_names() {
+        let mut player_updates = vec![];
+        if rules.special_weapons_enabled() {
+            player_updates.push(Message::ChargesUpdate {
+                radar: rules.radar_charges,
+                torpedo: rules.torpedo_charges,
+            });
+        }
+        if rules.ship_abilities {
+            // tell players which of their ships carry an ability
+            player_updates.extend(game.get_board(&player).get_ships().iter()
+                .enumerate()
+                .filter(|&(_, ship)| ship.ability.is_some())
+                .map(|(ship_index, _)| Message::AbilityCooldownUpdate { id: ship_index as u8, turns: 0 }));
+        }
+        updates.entry(player).or_insert(vec![]).extend(player_updates);
     }
-    return ships;
+    return updates;
 }
 
 pub fn handle_place_ships_request(placement: [ShipPlacement; 5], player_name: &String, lobby: &mut HashMap<String, Player>) -> Result {
@@ -336,26 +700,25 @@ pub fn handle_place_ships_request(placement: [ShipPlacement; 5], player_name: &S
             return Result::respond(Message::InvalidRequestResponse, false);
         }
 
-        let ships = placement2ships(placement);
-        if Board::try_create(ships.clone(), false).is_none() {
-            return Result::respond(Message::InvalidRequestResponse, false);
+        let rules = *(*game).borrow().get_rules();
+        let ships = placement::fleet_from_placement(&placement, &rules);
+        let errors = placement::validate(&ships, &rules);
+        if !errors.is_empty() {
+            return Result::respond(Message::InvalidPlacementResponse { errors: errors }, false);
         }
-        let opponent_ready;
+        let all_placed;
         {
             println!("Computing initial placement for {}:", player_name);
             let mut game_ref = (*game).borrow_mut();
             *game_ref.get_board(player_name) = Board::try_create(ships, true).unwrap();
-            opponent_ready = game_ref.get_opponent_board(player_name).has_ships();
+            all_placed = game_ref.all_boards_placed();
         }
 
         let mut game_ref = (*game).borrow_mut();
-        // opponent also done placing ships?
-        if opponent_ready {
-            game_ref.start();
-            let mut result = Result::respond(Message::OkResponse, false);
-            result.updates.insert(game_ref.get_active_player(), vec![Message::YourTurnUpdate]);
-            result.updates.insert(game_ref.get_waiting_player(), vec![Message::EnemyTurnUpdate]);
-            return result;
+        // opponents also done placing ships?
+        if all_placed {
+            let updates = start_game(&mut game_ref);
+            return Result::respond_and_update_single(Message::OkResponse, updates, false);
         } else {
             return Result::respond(Message::OkResponse, false);
         }
@@ -380,93 +743,195 @@ fn handle_move(game: &mut Game, player_name: &String, movement: (usize, Directio
     return my_board.move_ship(ship_index as u8, direction);
 }
 
+/// Weapons that can be fired at the opponent's board
+#[derive(Copy, Clone)]
+enum Weapon {
+    Shot { x: u8, y: u8 },
+    Torpedo { x: u8, y: u8, direction: Direction },
+}
+
 fn handle_shoot(games: &mut Vec<Rc<RefCell<Game>>>, game: Rc<RefCell<Game>>,
-        lobby: &mut HashMap<String, Player>, player_name: &String, target_x: u8,
-        target_y: u8) -> Result {
+        lobby: &mut HashMap<String, Player>, player_name: &String, target_name: &String,
+        weapon: Weapon) -> Result {
     let game_over;
     let hit_result;
     let response_msg;
+    let target_x;
+    let target_y;
     let mut updates = HashMap::new();
-    let opponent_name;
+    let two_player;
 
     // evaluate shot
     {
         let mut game_ref = (*game).borrow_mut();
-        opponent_name = game_ref.get_opponent_name(player_name).to_owned();
+        two_player = game_ref.is_two_player();
+        let rules = *game_ref.get_rules();
+        let target_updates;
+        let target_eliminated;
 
         // enemy visibility updates
         {
-            let ref mut opponent_board = game_ref.get_opponent_board(player_name);
-            println!("Shooting on {}'s board at {}:{}:", opponent_name, target_x, target_y);
-            hit_result = opponent_board.hit(target_x as usize, target_y as usize);
-            let opponent_updates = hashmap![player_name.clone() => opponent_board.pop_updates()];
-            game_over = opponent_board.is_dead();
-            merge_updates(&mut updates, opponent_updates);
+            let ref mut target_board = game_ref.get_board(target_name);
+            match weapon {
+                Weapon::Shot { x, y } => {
+                    println!("Shooting on {}'s board at {}:{}:", target_name, x, y);
+                    hit_result = target_board.shoot(x as usize, y as usize, &rules);
+                    target_x = x;
+                    target_y = y;
+                },
+                Weapon::Torpedo { x, y, direction } => {
+                    println!("Firing torpedo on {}'s board from {}:{} towards {:?}:", target_name, x, y, direction);
+                    let (torpedo_result, impact_x, impact_y) = target_board.torpedo(x as usize, y as usize, direction, &rules);
+                    hit_result = torpedo_result;
+                    target_x = impact_x as u8;
+                    target_y = impact_y as u8;
+                },
+            }
+            target_updates = target_board.pop_updates();
+            target_eliminated = target_board.is_dead();
         }
 
         // my visibility updates
-        {
-            let ref mut my_board = game_ref.get_board(player_name);
-            let my_updates = hashmap![opponent_name.clone() => my_board.pop_updates()];
-            merge_updates(&mut updates, my_updates);
+        let my_updates = game_ref.get_board(player_name).pop_updates();
+
+        if two_player {
+            merge_updates(&mut updates, hashmap![player_name.clone() => target_updates]);
+            merge_updates(&mut updates, hashmap![target_name.clone() => my_updates]);
+        } else {
+            // everybody except the owner of a board and their ally learns about its changes
+            for player in game_ref.get_player_names() {
+                if !game_ref.is_ally(&player, target_name) {
+                    merge_updates(&mut updates, hashmap![player.clone() => name_board_updates(target_name, target_updates.clone())]);
+                }
+                if !game_ref.is_ally(&player, player_name) {
+                    merge_updates(&mut updates, hashmap![player.clone() => name_board_updates(player_name, my_updates.clone())]);
+                }
+            }
         }
+        game_over = game_ref.is_decided();
 
         // hit updates
+        let enemy_update;
         match hit_result {
             HitResult::Hit => {
                 response_msg = Message::HitResponse { x: target_x, y: target_y };
-                merge_updates(&mut updates, hashmap![opponent_name.clone() => vec![Message::EnemyHitUpdate { x: target_x, y: target_y }]]);
+                enemy_update = Some(Message::EnemyHitUpdate { x: target_x, y: target_y });
             },
             HitResult::Miss => {
                 response_msg = Message::MissResponse { x: target_x, y: target_y };
-                merge_updates(&mut updates, hashmap![opponent_name.clone() => vec![Message::EnemyMissUpdate { x: target_x, y: target_y }]]);
+                enemy_update = Some(Message::EnemyMissUpdate { x: target_x, y: target_y });
             },
             HitResult::Destroyed => {
                 response_msg = Message::DestroyedResponse { x: target_x, y: target_y };
-                if !game_over {
-                    merge_updates(&mut updates, hashmap![opponent_name.clone() => vec![Message::EnemyHitUpdate { x: target_x, y: target_y }]]);
-                }
+                enemy_update = if game_over { None } else { Some(Message::EnemyHitUpdate { x: target_x, y: target_y }) };
             },
         }
+
+        if !two_player {
+            add_update_game_except(&game_ref, player_name, &mut updates, Message::PlayerShotUpdate {
+                shooter: player_name.clone(),
+                target: target_name.clone(),
+                x: target_x,
+                y: target_y,
+                hit: hit_result != HitResult::Miss,
+            });
+            if target_eliminated {
+                add_update_game(&game_ref, &mut updates,
+                                Message::PlayerEliminatedUpdate { nickname: target_name.clone() });
+            }
+        } else {
+            // torpedoes are replayed on the opponent's client instead of reporting the hit cell only
+            let enemy_update = match weapon {
+                Weapon::Shot { .. } => enemy_update,
+                Weapon::Torpedo { x, y, direction } => Some(Message::EnemyTorpedoUpdate { x: x, y: y, direction: direction }),
+            };
+            if let Some(update) = enemy_update {
+                merge_updates(&mut updates, hashmap![target_name.clone() => vec![update]]);
+            }
+        }
     }
 
     if game_over {
-        updates = terminate_game(games, game, lobby, player_name, true, Reason::Obliterated);
+        let game_over_updates = terminate_game(games, game, lobby, player_name, true, Reason::Obliterated);
+        merge_updates(&mut updates, game_over_updates);
         return Result::respond_and_update_single(response_msg, updates, false);
     } else {
-        let mut game_ref = (*game).borrow_mut();
-        game_ref.switch_turns();
+        switch_turns(games, game, lobby, &mut updates);
         return Result::respond_and_update_single(response_msg, updates, false);
     }
 }
 
-pub fn handle_move_shoot_request(target_coords: (u8, u8),
-        ship_movement: Option<(usize, Direction)>, player_name: &String,
-        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
+/**
+ * Determines whose board a shot of the given player is aimed at. Free-for-all and team games
+ * require an explicit target that is still in the game, two-player games default to the opponent.
+ */
+fn get_target(game: &Game, player_name: &String, target_name: Option<String>) -> Option<String> {
+    match target_name {
+        Some(target_name) => {
+            if !game.has_player(&target_name) || game.is_ally(&target_name, player_name)
+                    || game.is_eliminated(&target_name) {
+                return None;
+            }
+            return Some(target_name);
+        },
+        None => {
+            if !game.is_two_player() {
+                return None;
+            }
+            return Some(game.get_opponent_name(player_name).clone());
+        },
+    }
+}
+
+/**
+ * Returns the running game in which it is the given player's turn.
+ * Otherwise the Result to respond with is returned as error.
+ */
+fn get_game_on_turn(player_name: &String, lobby: &HashMap<String, Player>)
+        -> ::std::result::Result<Rc<RefCell<Game>>, Result> {
     let game;
 
     {
         // get reference to active game, if there is any
-        let ref active_game = lobby.get_mut(player_name).unwrap().game;
+        let ref active_game = lobby.get(player_name).unwrap().game;
 
         if active_game.is_none() {
-            return Result::respond(Message::NotYourTurnResponse, false);
+            return Err(Result::respond(Message::NotYourTurnResponse, false));
         }
 
         game = active_game.as_ref().unwrap().clone();
     }
 
     {
-        // check if in valid state and handle move, if requested
-        let mut game_ref = (*game).borrow_mut();
+        let game_ref = (*game).borrow();
 
         if !game_ref.is_running() {
-            return Result::respond(Message::InvalidRequestResponse, false);
+            return Err(Result::respond(Message::InvalidRequestResponse, false));
         }
 
         if !game_ref.my_turn(player_name) {
-            return Result::respond(Message::NotYourTurnResponse, false);
+            return Err(Result::respond(Message::NotYourTurnResponse, false));
         }
+    }
+
+    return Ok(game);
+}
+
+pub fn handle_move_shoot_request(requested_target: Option<String>, target_coords: (u8, u8),
+        ship_movement: Option<(usize, Direction)>, player_name: &String,
+        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
+    let game = match get_game_on_turn(player_name, lobby) {
+        Ok(game) => game,
+        Err(result) => return result,
+    };
+
+    let target_name;
+    {
+        let mut game_ref = (*game).borrow_mut();
+        target_name = match get_target(&game_ref, player_name, requested_target) {
+            Some(name) => name,
+            None => return Result::respond(Message::InvalidRequestResponse, false),
+        };
 
         // move if requested
         if let Some(movement) = ship_movement {
@@ -478,28 +943,163 @@ pub fn handle_move_shoot_request(target_coords: (u8, u8),
     }
 
     // handle shot
-    return handle_shoot(games, game, lobby, player_name, target_coords.0, target_coords.1);
+    return handle_shoot(games, game, lobby, player_name, &target_name,
+                        Weapon::Shot { x: target_coords.0, y: target_coords.1 });
+}
+
+pub fn handle_radar_request(x: u8, y: u8, player_name: &String,
+        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
+    let game = match get_game_on_turn(player_name, lobby) {
+        Ok(game) => game,
+        Err(result) => return result,
+    };
+
+    let ship_detected;
+    let mut updates;
+    {
+        let mut game_ref = (*game).borrow_mut();
+        if x as usize >= W || y as usize >= H || !game_ref.use_radar_charge(player_name) {
+            return Result::respond(Message::InvalidRequestResponse, false);
+        }
+
+        let opponent_name = game_ref.get_opponent_name(player_name).clone();
+        ship_detected = game_ref.get_opponent_board(player_name).radar_sweep(x as usize, y as usize);
+        updates = hashmap![opponent_name => vec![Message::EnemyRadarUpdate { x: x, y: y }]];
+    }
+    switch_turns(games, game, lobby, &mut updates);
+
+    return Result::respond_and_update_single(
+        Message::RadarResponse { x: x, y: y, ship_detected: ship_detected }, updates, false);
+}
+
+pub fn handle_torpedo_request(x: u8, y: u8, direction: Direction, player_name: &String,
+        lobby: &mut HashMap<String, Player>, games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
+    let game = match get_game_on_turn(player_name, lobby) {
+        Ok(game) => game,
+        Err(result) => return result,
+    };
+
+    let opponent_name;
+    {
+        let mut game_ref = (*game).borrow_mut();
+        if x as usize >= W || y as usize >= H || !game_ref.use_torpedo_charge(player_name) {
+            return Result::respond(Message::InvalidRequestResponse, false);
+        }
+        opponent_name = game_ref.get_opponent_name(player_name).clone();
+    }
+
+    return handle_shoot(games, game, lobby, player_name, &opponent_name,
+                        Weapon::Torpedo { x: x, y: y, direction: direction });
+}
+
+/**
+ * Uses the ability of ship `ship_index`. A reconnaissance reveal replaces the shot, while
+ * submerging and double moves are followed by a shot at (x, y).
+ */
+pub fn handle_ability_request(ship_index: u8, direction: Direction, x: u8, y: u8,
+        player_name: &String, lobby: &mut HashMap<String, Player>,
+        games: &mut Vec<Rc<RefCell<Game>>>) -> Result {
+    let game = match get_game_on_turn(player_name, lobby) {
+        Ok(game) => game,
+        Err(result) => return result,
+    };
+
+    let ability;
+    let opponent_name;
+    let mut recon_updates = None;
+    {
+        let mut game_ref = (*game).borrow_mut();
+        opponent_name = game_ref.get_opponent_name(player_name).clone();
+        if !game_ref.get_rules().ship_abilities || ship_index > 4 || x as usize >= W || y as usize >= H
+                || game_ref.get_cooldown(player_name, ship_index as usize) > 0 {
+            return Result::respond(Message::InvalidRequestResponse, false);
+        }
+
+        let ship = game_ref.get_board(player_name).get_ships()[ship_index as usize];
+        if ship.is_dead() || ship.ability.is_none() {
+            return Result::respond(Message::InvalidRequestResponse, false);
+        }
+        ability = ship.ability.unwrap();
+
+        let ability_used = match ability {
+            Ability::Reconnaissance => true,
+            Ability::Submerge => game_ref.get_board(player_name).submerge_ship(ship_index),
+            Ability::DoubleMove => game_ref.get_board(player_name).double_move_ship(ship_index, direction),
+        };
+        if !ability_used {
+            return Result::respond(Message::InvalidRequestResponse, false);
+        }
+        game_ref.start_cooldown(player_name, ship_index as usize);
+
+        if ability == Ability::Reconnaissance {
+            let revealed = game_ref.get_opponent_board(player_name).reveal_area(x as usize, y as usize);
+            recon_updates = Some(hashmap![
+                player_name.clone() => revealed,
+                opponent_name.clone() => vec![Message::EnemyReconUpdate { x: x, y: y }]
+            ]);
+        }
+    }
+
+    if let Some(mut updates) = recon_updates {
+        switch_turns(games, game, lobby, &mut updates);
+        return Result::respond_and_update_single(Message::ReconResponse { x: x, y: y }, updates, false);
+    }
+
+    return handle_shoot(games, game, lobby, player_name, &opponent_name, Weapon::Shot { x: x, y: y });
 }
 
 pub fn handle_afk(game: Rc<RefCell<Game>>, lobby: &mut HashMap<String, Player>,
         games: &mut Vec<Rc<RefCell<Game>>>) -> HashMap<String, Vec<Message>> {
     let active_player;
+    let out_of_strikes;
+    let mut updates = HashMap::new();
 
     {
         let mut game_ref = (*game).borrow_mut();
         active_player = game_ref.get_active_player();
         let strike_count = game_ref.get_active_player_afk_count();
+        out_of_strikes = strike_count <= 1;
 
-        if strike_count > 1 {
-            let opponent_name = game_ref.get_opponent_name(&game_ref.get_active_player()).clone();
+        if !out_of_strikes {
             game_ref.dec_active_player_afk_count();
-            game_ref.switch_turns();
-            return hashmap![
-                active_player => vec![Message::AfkWarningUpdate { strikes: strike_count - 1 }],
-                opponent_name => vec![Message::EnemyAfkUpdate { strikes: strike_count - 1 }]
-            ];
+            updates.insert(active_player.clone(), vec![Message::AfkWarningUpdate { strikes: strike_count - 1 }]);
+            add_update_game_except(&game_ref, &active_player, &mut updates,
+                                   Message::EnemyAfkUpdate { strikes: strike_count - 1 });
         }
     }
 
-    return terminate_game(games, game, lobby, &active_player, false, Reason::Afk);
+    if out_of_strikes {
+        return leave_game(games, game, lobby, &active_player, Reason::Afk);
+    }
+    switch_turns(games, game, lobby, &mut updates);
+    return updates;
+}
+
+/**
+ * Ends a game which reached the maximum number of turns. The team with the best score wins,
+ * otherwise the best players draw.
+ */
+fn handle_turn_limit(game: Rc<RefCell<Game>>, lobby: &mut HashMap<String, Player>,
+        games: &mut Vec<Rc<RefCell<Game>>>) -> HashMap<String, Vec<Message>> {
+    let leaders = (*game).borrow().get_leaders();
+    let draw = {
+        let game_ref = (*game).borrow();
+        leaders.iter().any(|leader| !game_ref.is_ally(leader, &leaders[0]))
+    };
+
+    if !draw {
+        return terminate_game(games, game, lobby, &leaders[0], true, Reason::TurnLimit);
+    }
+    return end_game(games, game, lobby, Reason::TurnLimit, |game_ref, player_name| {
+        if leaders.iter().any(|leader| game_ref.is_ally(leader, player_name)) {
+            return Some(Outcome::Draw);
+        }
+        return Some(Outcome::Defeat);
+    });
+}
+
+/// Ends a game on behalf of the server's administrator, as a draw for everybody
+pub fn handle_force_end(game: Rc<RefCell<Game>>, lobby: &mut HashMap<String, Player>,
+        games: &mut Vec<Rc<RefCell<Game>>>) -> HashMap<String, Vec<Message>> {
+    return end_game(games, game, lobby, Reason::EndedByServer, |_, _| Some(Outcome::Draw));
 }
@@ -0,0 +1,233 @@
+//! Tests for the server's handling of requests, played out on a lobby without connections.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rusty_battleships::board::{Player, PlayerState};
+use rusty_battleships::game::Game;
+use rusty_battleships::message::{Direction, Message, Outcome, Reason, ShipPlacement};
+use rusty_battleships::rules::Rules;
+
+use server_::state;
+
+/// Rules that differ from the default in everything a game can be configured with
+fn configured_rules() -> Rules {
+    Rules {
+        radar_charges: 2,
+        torpedo_charges: 1,
+        ship_abilities: true,
+        ability_cooldown: 2,
+        max_turns: Some(40),
+        no_touching: true,
+        damaged_segments_miss: true,
+        turn_seconds: 30,
+        afk_strikes: 5,
+    }
+}
+
+fn names(count: usize) -> Vec<String> {
+    (0..count).map(|i| format!("player{}", i)).collect()
+}
+
+/// A lobby of players who are all ready to be challenged
+fn lobby(names: &[String]) -> HashMap<String, Player> {
+    names.iter().map(|name| (name.clone(), Player { state: PlayerState::Ready, game: None })).collect()
+}
+
+fn game_of(name: &String, lobby: &HashMap<String, Player>) -> Rc<RefCell<Game>> {
+    lobby[name].game.clone().expect("Player is in a game.")
+}
+
+/// Ships in every other row, which is valid with any rules
+fn placement() -> [ShipPlacement; 5] {
+    let ship = |y| ShipPlacement { x: 0, y: y, direction: Direction::East };
+    [ship(0), ship(2), ship(4), ship(6), ship(8)]
+}
+
+/// Places the fleets of all players of a game and returns the result of the last placement
+fn place_all(names: &[String], lobby: &mut HashMap<String, Player>) -> state::Result {
+    let (last, others) = names.split_last().unwrap();
+    for name in others {
+        assert_eq!(state::handle_place_ships_request(placement(), name, lobby).response, Some(Message::OkResponse));
+    }
+    return state::handle_place_ships_request(placement(), last, lobby);
+}
+
+#[test]
+fn free_for_all_games_take_up_to_eight_players() {
+    let names = names(9);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    let result = state::handle_challenge_players_request(names[1..].to_vec(), &names[0], &mut lobby, &mut games,
+                                                         &configured_rules());
+    assert_eq!(result.response, Some(Message::InvalidRequestResponse));
+    assert!(games.is_empty() && lobby.values().all(|player| player.state == PlayerState::Ready));
+
+    let result = state::handle_challenge_players_request(names[1..8].to_vec(), &names[0], &mut lobby, &mut games,
+                                                         &configured_rules());
+    assert_eq!(result.response, Some(Message::OkResponse));
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].borrow().get_player_names(), names[..8].to_vec());
+    for name in &names[1..8] {
+        let opponents: Vec<String> = names[..8].iter().filter(|n| *n != name).cloned().collect();
+        assert_eq!(result.updates[name], vec![Message::FreeForAllStartUpdate { nicknames: opponents }]);
+    }
+    for name in &names[..8] {
+        assert_eq!(lobby[name].state, PlayerState::Playing);
+        assert!(Rc::ptr_eq(&game_of(name, &lobby), &games[0]));
+    }
+    assert_eq!(lobby[&names[8]].state, PlayerState::Ready);
+}
+
+#[test]
+fn free_for_all_games_keep_the_configured_rules_but_special_weapons() {
+    let names = names(3);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    state::handle_challenge_players_request(names[1..].to_vec(), &names[0], &mut lobby, &mut games, &configured_rules());
+    let expected = Rules { radar_charges: 0, torpedo_charges: 0, ship_abilities: false, ..configured_rules() };
+    assert_eq!(*games[0].borrow().get_rules(), expected);
+}
+
+#[test]
+fn team_games_keep_the_configured_rules_but_special_weapons() {
+    let names = names(4);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    let result = state::handle_challenge_team_request(names[1].clone(), names[2..].to_vec(), &names[0], &mut lobby,
+                                                      &mut games, &configured_rules());
+    assert_eq!(result.response, Some(Message::OkResponse));
+    let expected = Rules { radar_charges: 0, torpedo_charges: 0, ship_abilities: false, ..configured_rules() };
+    assert_eq!(*games[0].borrow().get_rules(), expected);
+    assert!(games[0].borrow().is_ally(&names[0], &names[1]));
+}
+
+#[test]
+fn free_for_all_turns_go_round_the_table() {
+    let names = names(4);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    state::handle_challenge_players_request(names[1..].to_vec(), &names[0], &mut lobby, &mut games, &Rules::default());
+    let result = place_all(&names, &mut lobby);
+    let game = game_of(&names[0], &lobby);
+    let first = game.borrow().get_active_player();
+    for name in &names {
+        assert_eq!(result.updates[name][0], Message::PlayerTurnUpdate { nickname: first.clone() });
+    }
+
+    let mut active = names.iter().position(|name| *name == first).unwrap();
+    for _ in 0..2 * names.len() {
+        let waiting = &names[(active + 1) % names.len()];
+        let result = state::handle_move_shoot_request(Some(waiting.clone()), (9, 9), None, waiting, &mut lobby, &mut games);
+        assert_eq!(result.response, Some(Message::NotYourTurnResponse));
+
+        let result = state::handle_move_shoot_request(Some(waiting.clone()), (9, 9), None, &names[active], &mut lobby,
+                                                      &mut games);
+        assert_eq!(result.response, Some(Message::MissResponse { x: 9, y: 9 }));
+        active = (active + 1) % names.len();
+        assert_eq!(game.borrow().get_active_player(), names[active]);
+        assert!(result.updates[&names[0]].contains(&Message::PlayerTurnUpdate { nickname: names[active].clone() }));
+    }
+}
+
+#[test]
+fn games_end_as_soon_as_the_turn_limit_is_reached() {
+    let names = names(2);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    let rules = Rules { max_turns: Some(2), ..Rules::default() };
+    state::handle_challenge_player_request(names[1].clone(), &names[0], &mut lobby, &mut games, &rules);
+    place_all(&names, &mut lobby);
+    let first = game_of(&names[0], &lobby).borrow().get_active_player();
+    let second = names.iter().find(|name| **name != first).unwrap().clone();
+
+    // the first player hits the carrier, which puts them ahead on score
+    let result = state::handle_move_shoot_request(None, (0, 0), None, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::HitResponse { x: 0, y: 0 }));
+    assert_eq!(games.len(), 1);
+
+    let result = state::handle_move_shoot_request(None, (9, 9), None, &second, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::MissResponse { x: 9, y: 9 }));
+    assert!(games.is_empty());
+    assert!(result.updates[&first].contains(&Message::GameOverUpdate { outcome: Outcome::Victory, reason: Reason::TurnLimit }));
+    assert!(result.updates[&second].contains(&Message::GameOverUpdate { outcome: Outcome::Defeat, reason: Reason::TurnLimit }));
+    for name in &names {
+        assert!(lobby[name].game.is_none());
+        assert_eq!(lobby[name].state, PlayerState::Available);
+    }
+}
+
+#[test]
+fn special_weapons_are_refused_without_using_up_a_charge() {
+    let names = names(2);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    let rules = Rules { radar_charges: 1, torpedo_charges: 1, ..Rules::default() };
+    state::handle_challenge_player_request(names[1].clone(), &names[0], &mut lobby, &mut games, &rules);
+    place_all(&names, &mut lobby);
+    let game = game_of(&names[0], &lobby);
+    let first = game.borrow().get_active_player();
+    let second = names.iter().find(|name| **name != first).unwrap().clone();
+    let charges = |game: &Rc<RefCell<Game>>| {
+        let charges = game.borrow().get_charges(&first);
+        (charges.radar, charges.torpedo)
+    };
+
+    // off the board
+    let result = state::handle_radar_request(10, 0, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::InvalidRequestResponse));
+    let result = state::handle_torpedo_request(0, 10, Direction::North, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::InvalidRequestResponse));
+    assert_eq!(charges(&game), (1, 1));
+    assert!(game.borrow().my_turn(&first));
+
+    let result = state::handle_radar_request(0, 0, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::RadarResponse { x: 0, y: 0, ship_detected: true }));
+    state::handle_move_shoot_request(None, (9, 9), None, &second, &mut lobby, &mut games);
+    let result = state::handle_torpedo_request(9, 0, Direction::West, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::HitResponse { x: 4, y: 0 }));
+    state::handle_move_shoot_request(None, (9, 9), None, &second, &mut lobby, &mut games);
+    assert_eq!(charges(&game), (0, 0));
+
+    // no charges left
+    let result = state::handle_radar_request(0, 0, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::InvalidRequestResponse));
+    let result = state::handle_torpedo_request(9, 0, Direction::West, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::InvalidRequestResponse));
+    assert_eq!(charges(&game), (0, 0));
+    assert!(game.borrow().my_turn(&first));
+}
+
+#[test]
+fn reconnaissance_reports_every_revealed_cell() {
+    let names = names(2);
+    let mut lobby = lobby(&names);
+    let mut games = vec![];
+    let rules = Rules { ship_abilities: true, ..Rules::default() };
+    state::handle_challenge_player_request(names[1].clone(), &names[0], &mut lobby, &mut games, &rules);
+    place_all(&names, &mut lobby);
+    let game = game_of(&names[0], &lobby);
+    let first = game.borrow().get_active_player();
+    let second = names.iter().find(|name| **name != first).unwrap().clone();
+
+    // the carrier reveals the area around 1:1, with ships in the rows 0 and 2
+    let result = state::handle_ability_request(0, Direction::North, 1, 1, &first, &mut lobby, &mut games);
+    assert_eq!(result.response, Some(Message::ReconResponse { x: 1, y: 1 }));
+    let revealed: Vec<Message> = result.updates[&first].iter()
+        .filter(|update| match **update {
+            Message::EnemyVisibleUpdate { .. } | Message::EnemyInvisibleUpdate { .. } => true,
+            _ => false,
+        })
+        .cloned()
+        .collect();
+    assert_eq!(revealed.len(), 9);
+    for x in 0..3 {
+        assert!(revealed.contains(&Message::EnemyVisibleUpdate { x: x, y: 0 }));
+        assert!(revealed.contains(&Message::EnemyInvisibleUpdate { x: x, y: 1 }));
+        assert!(revealed.contains(&Message::EnemyVisibleUpdate { x: x, y: 2 }));
+    }
+    assert_eq!(result.updates[&second], vec![Message::EnemyReconUpdate { x: 1, y: 1 }]);
+    assert!(game.borrow().my_turn(&second));
+    assert_eq!(game.borrow().get_cooldown(&first, 0), rules.ability_cooldown);
+}
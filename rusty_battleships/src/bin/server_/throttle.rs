@@ -0,0 +1,82 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use rusty_battleships::message::Message;
+
+/// Time an address has to wait after its first failed password, doubled for every further one
+const FIRST_LOGIN_DELAY_MS: u64 = 1000;
+
+/// Longest time an address has to wait after failed passwords
+const MAX_LOGIN_DELAY_MS: u64 = 60000;
+
+/// Time an address has to wait after registering an account before it may register another one
+const REGISTRATION_INTERVAL_MS: u64 = 60000;
+
+/// A request that makes the server hash a password
+#[derive(Clone, Copy, PartialEq)]
+pub enum Attempt {
+    Registration,
+    PasswordLogin,
+}
+
+impl Attempt {
+    pub fn of(msg: &Message) -> Option<Attempt> {
+        match *msg {
+            Message::RegisterRequest { .. } => Some(Attempt::Registration),
+            Message::PasswordLoginRequest { .. } => Some(Attempt::PasswordLogin),
+            _ => None,
+        }
+    }
+}
+
+/**
+ * Limits how often each address can make the server hash a password. Hashing is slow on purpose
+ * and blocks every game while it runs, so neither guessing passwords nor registering accounts in
+ * bulk may keep the server busy.
+ */
+#[derive(Default)]
+pub struct Throttle {
+    // failed passwords in a row and when the next attempt is allowed
+    logins: HashMap<IpAddr, (u32, Instant)>,
+    // when the next registration is allowed
+    registrations: HashMap<IpAddr, Instant>,
+}
+
+impl Throttle {
+    /// @return The time the address has to wait before the attempt is allowed, None if it may go ahead
+    pub fn wait(&self, address: IpAddr, attempt: Attempt, now: Instant) -> Option<Duration> {
+        let allowed = match attempt {
+            Attempt::Registration => self.registrations.get(&address).cloned(),
+            Attempt::PasswordLogin => self.logins.get(&address).map(|&(_, allowed)| allowed),
+        };
+        allowed.and_then(|allowed| if allowed > now { Some(allowed - now) } else { None })
+    }
+
+    /// Takes note of an attempt that was let through, by the response it was answered with
+    pub fn count(&mut self, address: IpAddr, attempt: Attempt, response: &Message, now: Instant) {
+        self.forget_expired(now);
+        match (attempt, response) {
+            (Attempt::Registration, &Message::TokenResponse { .. }) => {
+                self.registrations.insert(address, now + Duration::from_millis(REGISTRATION_INTERVAL_MS));
+            },
+            (Attempt::PasswordLogin, &Message::TokenResponse { .. }) => {
+                self.logins.remove(&address);
+            },
+            (Attempt::PasswordLogin, &Message::WrongCredentialsResponse) => {
+                let failures = self.logins.get(&address).map_or(0, |&(failures, _)| failures) + 1;
+                let delay = cmp::min(FIRST_LOGIN_DELAY_MS << cmp::min(failures - 1, 16), MAX_LOGIN_DELAY_MS);
+                self.logins.insert(address, (failures, now + Duration::from_millis(delay)));
+            },
+            _ => {},
+        }
+    }
+
+    /// Failed passwords are forgiven once the address has not failed for the longest delay.
+    fn forget_expired(&mut self, now: Instant) {
+        let max_delay = Duration::from_millis(MAX_LOGIN_DELAY_MS);
+        self.logins.retain(|_, &mut (_, allowed)| allowed + max_delay > now);
+        self.registrations.retain(|_, &mut allowed| allowed > now);
+    }
+}
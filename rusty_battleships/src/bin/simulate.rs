@@ -0,0 +1,239 @@
+use std::fs::File;
+use std::io::{Result, Write};
+
+extern crate argparse;
+use argparse::{ArgumentParser, Collect, Print, Store, StoreOption, StoreTrue};
+
+extern crate rusty_battleships;
+use rusty_battleships::board::Board;
+use rusty_battleships::external;
+use rusty_battleships::knowledge::OpponentBoard;
+use rusty_battleships::message::Message;
+use rusty_battleships::rules::Rules;
+use rusty_battleships::ship::Ship;
+use rusty_battleships::strategy::{self, Strategy, Turn, STRATEGY_NAMES};
+
+macro_rules! description {
+    () => ( "rusty battleships: offline self-play simulator" )
+}
+macro_rules! version {
+    () => ( env!("CARGO_PKG_VERSION") )
+}
+macro_rules! version_string {
+    () => ( concat!(description!(), " v", version!()) )
+}
+
+/// Number of turns after which a game that is still running counts as a draw
+const TURN_LIMIT: usize = 1000;
+
+struct Config {
+    strategies: Vec<String>,
+    games: usize,
+    seed: usize,
+    rules: Rules,
+    csv: Option<String>,
+}
+
+/// Plays like the wrapped strategy, but never moves a ship
+struct Stationary {
+    strategy: Box<Strategy>,
+}
+
+impl Strategy for Stationary {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        self.strategy.place_fleet(rules)
+    }
+
+    fn choose_turn(&mut self, my_board: &Board, opponent: &OpponentBoard, rules: &Rules) -> Turn {
+        let mut turn = self.strategy.choose_turn(my_board, opponent, rules);
+        turn.movement = None;
+        return turn;
+    }
+
+    fn handle_message(&mut self, msg: &Message) {
+        self.strategy.handle_message(msg);
+    }
+}
+
+/// Results of all games between two strategies
+struct Matchup {
+    first: String,
+    second: String,
+    games: usize,
+    wins: [usize; 2],
+    draws: usize,
+    forfeits: usize,
+    turns: usize,
+    // games won by whoever had the first turn
+    first_mover_wins: usize,
+    games_with_moves: usize,
+    // games whose winner changes when they are replayed without moving ships
+    changed_by_moves: usize,
+}
+
+impl Matchup {
+    fn decided(&self) -> usize {
+        self.games - self.draws
+    }
+}
+
+fn percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    return 100.0 * count as f64 / total as f64;
+}
+
+fn create(spec: &str, seed: usize) -> Box<Strategy> {
+    match external::from_spec(spec, Some(seed)) {
+        Ok(strategy) => strategy,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+/**
+ * Plays the given number of games between two strategies, which take turns at moving first, so the
+ * same seed always gives the same games. Every game that saw a ship move is replayed with the same
+ * seeds and the same player starting, but without moving ships, to tell whether the movement
+ * decided the game.
+ */
+fn simulate(first: &str, second: &str, config: &Config) -> Matchup {
+    let mut matchup = Matchup {
+        first: first.to_owned(),
+        second: second.to_owned(),
+        games: config.games,
+        wins: [0, 0],
+        draws: 0,
+        forfeits: 0,
+        turns: 0,
+        first_mover_wins: 0,
+        games_with_moves: 0,
+        changed_by_moves: 0,
+    };
+
+    for game in 0..config.games {
+        let seeds = (config.seed + 2 * game, config.seed + 2 * game + 1);
+        let outcome = strategy::play_local_starting(&mut *create(first, seeds.0), &mut *create(second, seeds.1),
+                                                    &config.rules, TURN_LIMIT, game % 2);
+        matchup.turns += outcome.turns;
+        if outcome.forfeited {
+            matchup.forfeits += 1;
+        }
+        match outcome.winner {
+            Some(winner) => {
+                matchup.wins[winner] += 1;
+                if winner == outcome.first {
+                    matchup.first_mover_wins += 1;
+                }
+            },
+            None => matchup.draws += 1,
+        }
+
+        // without any moves, the replay would be the very same game
+        if outcome.moves > 0 {
+            matchup.games_with_moves += 1;
+            let replay = strategy::play_local_starting(
+                &mut Stationary { strategy: create(first, seeds.0) },
+                &mut Stationary { strategy: create(second, seeds.1) },
+                &config.rules, TURN_LIMIT, outcome.first);
+            if replay.winner != outcome.winner {
+                matchup.changed_by_moves += 1;
+            }
+        }
+    }
+    return matchup;
+}
+
+fn print_table(matchups: &[Matchup]) {
+    println!("{:<12} {:<12} {:>6} {:>8} {:>8} {:>7} {:>8} {:>9} {:>11} {:>11} {:>9}",
+             "first", "second", "games", "1st won", "2nd won", "draws", "forfeits", "avg turns",
+             "mover won", "with moves", "changed");
+    for m in matchups {
+        println!("{:<12} {:<12} {:>6} {:>7.1}% {:>7.1}% {:>6.1}% {:>8} {:>9.1} {:>10.1}% {:>10.1}% {:>8.1}%",
+                 m.first, m.second, m.games,
+                 percentage(m.wins[0], m.games),
+                 percentage(m.wins[1], m.games),
+                 percentage(m.draws, m.games),
+                 m.forfeits,
+                 m.turns as f64 / m.games as f64,
+                 percentage(m.first_mover_wins, m.decided()),
+                 percentage(m.games_with_moves, m.games),
+                 percentage(m.changed_by_moves, m.games_with_moves));
+    }
+}
+
+fn write_csv(path: &str, matchups: &[Matchup]) -> Result<()> {
+    let mut file = try!(File::create(path));
+    try!(writeln!(file, "first,second,games,first_wins,second_wins,draws,forfeits,average_turns,\
+                         first_mover_wins,games_with_moves,changed_by_moves"));
+    for m in matchups {
+        try!(writeln!(file, "{},{},{},{},{},{},{},{:.2},{},{},{}",
+                      m.first, m.second, m.games, m.wins[0], m.wins[1], m.draws, m.forfeits,
+                      m.turns as f64 / m.games as f64, m.first_mover_wins, m.games_with_moves,
+                      m.changed_by_moves));
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut config = Config {
+        strategies: vec![],
+        games: 1000,
+        seed: 0,
+        rules: Rules::default(),
+        csv: None,
+    };
+
+    {  // this block limits scope of borrows by ap.refer() method
+        let mut ap = ArgumentParser::new();
+        ap.set_description(description!());
+        ap.refer(&mut config.strategies).add_argument("STRATEGY", Collect,
+            "strategies to play against each other, every pair including itself (default all built-in ones); \
+             external bots are given by their command line, prefixed with text: if they speak the text protocol");
+        ap.refer(&mut config.games).add_option(&["-g", "--games"], Store,
+            "number of games per pair of strategies (default 1000)");
+        ap.refer(&mut config.seed).add_option(&["--seed"], Store,
+            "first seed for the strategies, to repeat a simulation (default 0)");
+        ap.refer(&mut config.rules.max_turns).add_option(&["--max-turns"], StoreOption,
+            "number of turns after which the game is decided by remaining hit points");
+        ap.refer(&mut config.rules.no_touching).add_option(&["--no-touching"], StoreTrue,
+            "do not allow ships to be placed next to each other");
+        ap.refer(&mut config.rules.damaged_segments_miss).add_option(&["--damaged-segments-miss"], StoreTrue,
+            "count shots at already damaged ship segments as misses");
+        ap.refer(&mut config.csv).add_option(&["--csv"], StoreOption,
+            "also write the results to the given CSV file");
+        ap.add_option(&["-v", "--version"], Print(version_string!().to_owned()),
+        "show version number");
+        ap.parse_args_or_exit();
+    }
+
+    if config.strategies.is_empty() {
+        config.strategies = STRATEGY_NAMES.iter().map(|name| name.to_string()).collect();
+    }
+    if config.games == 0 {
+        println!("At least one game per pair of strategies has to be played.");
+        std::process::exit(1);
+    }
+
+    let mut matchups = vec![];
+    for (index, first) in config.strategies.iter().enumerate() {
+        for second in &config.strategies[index..] {
+            matchups.push(simulate(first, second, &config));
+        }
+    }
+
+    print_table(&matchups);
+    println!("");
+    println!("mover won: share of decided games won by the player with the first turn");
+    println!("changed: share of games with moves whose winner differs when replayed without moves");
+
+    if let Some(ref path) = config.csv {
+        if let Err(e) = write_csv(path, &matchups) {
+            println!("Could not write {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs::File;
+use std::io::Write;
+
+extern crate argparse;
+use argparse::{ArgumentParser, Collect, Print, Store, StoreOption, StoreTrue};
+
+extern crate rusty_battleships;
+use rusty_battleships::external;
+use rusty_battleships::rating::{self, GameResult, Rating};
+use rusty_battleships::rules::Rules;
+use rusty_battleships::strategy::{self, Strategy, STRATEGY_NAMES};
+
+macro_rules! description {
+    () => ( "rusty battleships: bot tournament" )
+}
+macro_rules! version {
+    () => ( env!("CARGO_PKG_VERSION") )
+}
+macro_rules! version_string {
+    () => ( concat!(description!(), " v", version!()) )
+}
+
+/// Number of turns after which a game that is still running counts as a draw
+const TURN_LIMIT: usize = 1000;
+
+struct Config {
+    players: Vec<String>,
+    format: String,
+    rounds: usize,
+    games: usize,
+    seed: usize,
+    rules: Rules,
+    report: Option<String>,
+}
+
+/// Participant named after a built-in strategy or the command line of a program speaking the protocol
+struct Player {
+    name: String,
+    points: f64,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    forfeits: usize,
+    byes: usize,
+    opponents: Vec<usize>,
+}
+
+impl Player {
+    fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    fn create_strategy(&self, seed: usize) -> Result<Box<Strategy>, String> {
+        external::from_spec(&self.name, Some(seed))
+    }
+}
+
+/// Games between two players in one round, counted from the first player's point of view
+struct Match {
+    round: usize,
+    first: usize,
+    second: Option<usize>,
+    wins: usize,
+    draws: usize,
+    losses: usize,
+}
+
+struct Tournament {
+    config: Config,
+    players: Vec<Player>,
+    matches: Vec<Match>,
+    results: Vec<GameResult>,
+    games_played: usize,
+}
+
+impl Tournament {
+    fn new(config: Config) -> Tournament {
+        let players = config.players.iter().map(|name| Player {
+            name: name.clone(),
+            points: 0.0,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            forfeits: 0,
+            byes: 0,
+            opponents: vec![],
+        }).collect();
+        Tournament { config: config, players: players, matches: vec![], results: vec![], games_played: 0 }
+    }
+
+    fn ratings(&self) -> Vec<Rating> {
+        rating::elo_ratings(self.players.len(), &self.results)
+    }
+
+    /// Player indices ordered by match points, then by game score and rating
+    fn standings(&self) -> Vec<usize> {
+        let ratings = self.ratings();
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by(|&a, &b| {
+            let key = |index: usize| {
+                let player = &self.players[index];
+                (player.points, player.wins as f64 + 0.5 * player.draws as f64, ratings[index].elo)
+            };
+            key(b).partial_cmp(&key(a)).unwrap()
+        });
+        return order;
+    }
+
+    fn play_match(&mut self, round: usize, first: usize, second: usize) -> Result<(), String> {
+        let mut played = Match { round: round, first: first, second: Some(second), wins: 0, draws: 0, losses: 0 };
+        for _ in 0..self.config.games {
+            let seed = self.config.seed + 2 * self.games_played;
+            self.games_played += 1;
+            let mut first_strategy = try!(self.players[first].create_strategy(seed));
+            let mut second_strategy = try!(self.players[second].create_strategy(seed + 1));
+            let outcome = strategy::play_local(&mut *first_strategy, &mut *second_strategy, &self.config.rules, TURN_LIMIT);
+
+            let score = match outcome.winner {
+                Some(0) => { played.wins += 1; 1.0 },
+                Some(_) => { played.losses += 1; 0.0 },
+                None => { played.draws += 1; 0.5 },
+            };
+            if outcome.forfeited {
+                let loser = if outcome.winner == Some(0) { second } else { first };
+                self.players[loser].forfeits += 1;
+            }
+            self.results.push(GameResult { first: first, second: second, score: score });
+        }
+
+        self.players[first].wins += played.wins;
+        self.players[first].draws += played.draws;
+        self.players[first].losses += played.losses;
+        self.players[second].wins += played.losses;
+        self.players[second].draws += played.draws;
+        self.players[second].losses += played.wins;
+        let first_points = if played.wins > played.losses { 1.0 } else if played.wins == played.losses { 0.5 } else { 0.0 };
+        self.players[first].points += first_points;
+        self.players[second].points += 1.0 - first_points;
+        self.players[first].opponents.push(second);
+        self.players[second].opponents.push(first);
+        self.matches.push(played);
+        Ok(())
+    }
+
+    fn give_bye(&mut self, round: usize, player: usize) {
+        self.players[player].points += 1.0;
+        self.players[player].byes += 1;
+        self.matches.push(Match { round: round, first: player, second: None, wins: 0, draws: 0, losses: 0 });
+    }
+
+    fn play_round_robin(&mut self) -> Result<(), String> {
+        let count = self.players.len();
+        for first in 0..count {
+            for second in (first + 1)..count {
+                try!(self.play_match(1, first, second));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Every round, players are paired with the next player in the standings they have not played
+     * yet. With an odd number of players, the lowest ranked one who had no bye yet sits the round
+     * out and gets the points of a won match.
+     */
+    fn play_swiss(&mut self, rounds: usize) -> Result<(), String> {
+        for round in 1..(rounds + 1) {
+            let mut unpaired = self.standings();
+            if unpaired.len() % 2 == 1 {
+                let min_byes = unpaired.iter().map(|&index| self.players[index].byes).min().unwrap();
+                let position = unpaired.iter().rposition(|&index| self.players[index].byes == min_byes).unwrap();
+                let player = unpaired.remove(position);
+                self.give_bye(round, player);
+            }
+            while !unpaired.is_empty() {
+                let first = unpaired.remove(0);
+                // if everybody left has been played already, the next best player is played again
+                let position = unpaired.iter()
+                    .position(|index| !self.players[first].opponents.contains(index))
+                    .unwrap_or(0);
+                let second = unpaired.remove(position);
+                try!(self.play_match(round, first, second));
+            }
+        }
+        Ok(())
+    }
+
+    fn report(&self) -> String {
+        let mut report = String::new();
+        let ratings = self.ratings();
+        report.push_str(&format!("{} tournament, {} games per match, {} games in total\n\n",
+                                 self.config.format, self.config.games, self.results.len()));
+
+        report.push_str(&format!("{:>4}  {:<24} {:>6} {:>11} {:>6} {:>5} {:>5} {:>5} {:>7} {:>8}\n",
+                                 "rank", "player", "points", "elo", "games", "won", "drawn", "lost", "score", "forfeits"));
+        for (rank, &index) in self.standings().iter().enumerate() {
+            let player = &self.players[index];
+            let score = if player.games() == 0 { 0.0 }
+                        else { 100.0 * (player.wins as f64 + 0.5 * player.draws as f64) / player.games() as f64 };
+            report.push_str(&format!("{:>4}  {:<24} {:>6.1} {:>5.0} ±{:>4.0} {:>6} {:>5} {:>5} {:>5} {:>6.1}% {:>8}\n",
+                                     rank + 1, player.name, player.points, ratings[index].elo, ratings[index].error,
+                                     player.games(), player.wins, player.draws, player.losses, score, player.forfeits));
+        }
+
+        report.push_str("\nmatches (won-drawn-lost):\n");
+        for played in &self.matches {
+            if self.config.format == "swiss" {
+                report.push_str(&format!("round {:>2}: ", played.round));
+            }
+            let first = &self.players[played.first].name;
+            match played.second {
+                Some(second) => report.push_str(&format!("{} {}-{}-{} {}\n", first, played.wins, played.draws,
+                                                         played.losses, self.players[second].name)),
+                None => report.push_str(&format!("{} has a bye\n", first)),
+            }
+        }
+        return report;
+    }
+}
+
+fn main() {
+    let mut config = Config {
+        players: vec![],
+        format: "round-robin".to_owned(),
+        rounds: 0,
+        games: 10,
+        seed: 0,
+        rules: Rules::default(),
+        report: None,
+    };
+
+    {  // this block limits scope of borrows by ap.refer() method
+        let mut ap = ArgumentParser::new();
+        ap.set_description(description!());
+        ap.refer(&mut config.players).add_argument("PLAYER", Collect,
+            "built-in strategy or command line of a bot speaking the protocol over stdin and stdout, \
+             prefixed with text: for the text protocol (default all built-in strategies)");
+        ap.refer(&mut config.format).add_option(&["-f", "--format"], Store,
+            "round-robin or swiss (default round-robin)");
+        ap.refer(&mut config.rounds).add_option(&["-r", "--rounds"], Store,
+            "number of rounds of a swiss tournament (default enough to determine a winner)");
+        ap.refer(&mut config.games).add_option(&["-g", "--games"], Store,
+            "number of games per match (default 10)");
+        ap.refer(&mut config.seed).add_option(&["--seed"], Store,
+            "first seed for the built-in strategies, to repeat a tournament (default 0)");
+        ap.refer(&mut config.rules.max_turns).add_option(&["--max-turns"], StoreOption,
+            "number of turns after which the game is decided by remaining hit points");
+        ap.refer(&mut config.rules.no_touching).add_option(&["--no-touching"], StoreTrue,
+            "do not allow ships to be placed next to each other");
+        ap.refer(&mut config.rules.damaged_segments_miss).add_option(&["--damaged-segments-miss"], StoreTrue,
+            "count shots at already damaged ship segments as misses");
+        ap.refer(&mut config.report).add_option(&["--report"], StoreOption,
+            "also write the standings to the given file");
+        ap.add_option(&["-v", "--version"], Print(version_string!().to_owned()),
+        "show version number");
+        ap.parse_args_or_exit();
+    }
+
+    if config.players.is_empty() {
+        config.players = STRATEGY_NAMES.iter().map(|name| name.to_string()).collect();
+    }
+    if config.players.len() < 2 {
+        println!("A tournament needs at least two players.");
+        std::process::exit(1);
+    }
+    if config.players.iter().enumerate().any(|(index, name)| config.players[..index].contains(name)) {
+        println!("Every player can only take part once.");
+        std::process::exit(1);
+    }
+    if config.games == 0 {
+        println!("At least one game per match has to be played.");
+        std::process::exit(1);
+    }
+
+    let mut tournament = Tournament::new(config);
+    let result = match &tournament.config.format[..] {
+        "round-robin" => tournament.play_round_robin(),
+        "swiss" => {
+            let mut rounds = tournament.config.rounds;
+            if rounds == 0 {
+                // as many rounds as it takes a single undefeated player to remain
+                rounds = 1;
+                while (1 << rounds) < tournament.players.len() {
+                    rounds += 1;
+                }
+            }
+            tournament.play_swiss(rounds)
+        },
+        format => Err(format!("Unknown tournament format {}, choose round-robin or swiss.", format)),
+    };
+    if let Err(e) = result {
+        println!("{}", e);
+        std::process::exit(1);
+    }
+
+    let report = tournament.report();
+    print!("{}", report);
+    if let Some(ref path) = tournament.config.report {
+        if let Err(e) = File::create(path).and_then(|mut file| file.write_all(report.as_bytes())) {
+            println!("Could not write {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+use board::{W, H};
+
+const WORDS: usize = (W * H + 63) / 64;
+
+/**
+ * Set of cells on a board, one bit per cell. Cells are ordered by column first, so iterating over
+ * a set yields cells in the same order as nested loops over x and y.
+ */
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct BitBoard {
+    words: [u64; WORDS],
+}
+
+fn index(x: usize, y: usize) -> usize {
+    debug_assert!(x < W && y < H);
+    x * H + y
+}
+
+impl BitBoard {
+    pub fn new() -> BitBoard {
+        BitBoard { words: [0; WORDS] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        let i = index(x, y);
+        self.words[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn set(&mut self, x: usize, y: usize) {
+        let i = index(x, y);
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    pub fn unset(&mut self, x: usize, y: usize) {
+        let i = index(x, y);
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// Number of cells in the set
+    pub fn count(&self) -> usize {
+        self.words.iter().fold(0, |count, word| count + word.count_ones() as usize)
+    }
+
+    /// Whether both sets have at least one cell in common
+    pub fn intersects(&self, other: &BitBoard) -> bool {
+        self.words.iter().zip(other.words.iter()).any(|(a, b)| a & b != 0)
+    }
+
+    /// Iterates over the coordinates of all cells in the set
+    pub fn cells(&self) -> Cells {
+        Cells { words: self.words, word_index: 0 }
+    }
+}
+
+impl BitOr for BitBoard {
+    type Output = BitBoard;
+
+    fn bitor(mut self, other: BitBoard) -> BitBoard {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= *other_word;
+        }
+        self
+    }
+}
+
+impl BitAnd for BitBoard {
+    type Output = BitBoard;
+
+    fn bitand(mut self, other: BitBoard) -> BitBoard {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= *other_word;
+        }
+        self
+    }
+}
+
+/// Cells contained in exactly one of both sets
+impl BitXor for BitBoard {
+    type Output = BitBoard;
+
+    fn bitxor(mut self, other: BitBoard) -> BitBoard {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word ^= *other_word;
+        }
+        self
+    }
+}
+
+/// Cells contained in the first set but not in the second
+impl Sub for BitBoard {
+    type Output = BitBoard;
+
+    fn sub(mut self, other: BitBoard) -> BitBoard {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word &= !*other_word;
+        }
+        self
+    }
+}
+
+pub struct Cells {
+    words: [u64; WORDS],
+    word_index: usize,
+}
+
+impl Iterator for Cells {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        while self.word_index < WORDS {
+            let word = self.words[self.word_index];
+            if word != 0 {
+                // take the lowest bit out of the word
+                self.words[self.word_index] = word & (word - 1);
+                let i = self.word_index * 64 + word.trailing_zeros() as usize;
+                return Some((i / H, i % H));
+            }
+            self.word_index += 1;
+        }
+        return None;
+    }
+}
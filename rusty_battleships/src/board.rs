@@ -1,39 +1,22 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use std::sync::mpsc;
 use message::{Message, Direction};
+use bitboard::BitBoard;
 use game::Game;
+use rules::Rules;
 use ship::Ship;
 
-use ansi_term::Colour::{Green, Black, Red};
-
 pub const W: usize = 10;
 pub const H: usize = 10;
 
 pub type BoardState = [[CellState; H]; W];
 
-pub enum ToChildCommand {
-    Message(Message),
-    TerminateConnection
-}
-
-pub enum ToMainThreadCommand {
-    Message(Message),
-    Error(::std::io::Error),
-}
-
-pub struct PlayerHandle {
-    pub nickname: Option<String>,
-    pub from_child_endpoint: mpsc::Receiver<ToMainThreadCommand>,
-    pub to_child_endpoint: mpsc::Sender<ToChildCommand>,
-}
-
 pub struct Player {
     pub state: PlayerState,
     pub game: Option<Rc<RefCell<Game>>>,
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum PlayerState {
     Available,
     Ready,
@@ -46,6 +29,7 @@ pub struct CellState {
     pub ship_index: Option<u8>,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum HitResult {
     Hit,
     Miss,
@@ -66,51 +50,20 @@ impl CellState {
     }
 }
 
-type BoardArray = [[bool; H]; W];
-
-#[derive(Copy, Clone)]
-pub struct DumbBoard {
-    ship_at: BoardArray,
-    visible: BoardArray,
-}
-
-impl DumbBoard {
-    pub fn new() -> DumbBoard {
-        DumbBoard {
-            ship_at: [[false; H]; W],
-            visible: [[false; H]; W],
-        }
-    }
-
-    fn handle(&mut self, x: usize, y: usize, ship_at_coords: bool) {
-        self.visible[x][y] = true;
-        self.ship_at[x][y] = ship_at_coords;
-    }
-
-    // former hit(), visible()
-    pub fn set_ship(&mut self, x: u8, y: u8) {
-        self.handle(x as usize, y as usize, true);
-    }
-
-    // former miss(), invisible(), destroyed()
-    pub fn set_water(&mut self, x: u8, y: u8) {
-        self.handle(x as usize, y as usize, false);
-    }
-
-    pub fn is_visible_at(&self, x: usize, y: usize) -> bool {
-        self.visible[x][y]
-    }
-
-    pub fn has_ship_at(&self, x: usize, y: usize) -> bool {
-        self.ship_at[x][y]
-    }
-}
-
+/**
+ * Board of one player. Occupancy, visibility and damage are kept as bit sets which are updated
+ * incrementally, so that moves and shots only touch the cells of the ship concerned.
+ */
 #[derive(Debug, Clone)]
 pub struct Board {
     ships: Vec<Ship>,
-    state: BoardState,
-    old_states: Vec<BoardState>,
+    // cells covered by each ship, including destroyed ones
+    ship_cells: Vec<BitBoard>,
+    // cells covered by ships that have not been destroyed
+    occupied: BitBoard,
+    visible: BitBoard,
+    // cells covered by damaged segments of ships that have not been destroyed
+    damaged: BitBoard,
 
     handle_visibility_updates: bool,
     visibility_updates: Vec<Message>,
@@ -119,26 +72,78 @@ pub struct Board {
 impl Board {
     pub fn try_create(ships: Vec<Ship>, handle_visibility_updates: bool) -> Option<Board> {
         let mut board = Board {
-            state: [[CellState::new(); H]; W],
-            old_states: vec![],
+            ship_cells: vec![],
+            occupied: BitBoard::new(),
+            visible: BitBoard::new(),
+            damaged: BitBoard::new(),
             ships: ships,
             handle_visibility_updates: handle_visibility_updates,
             visibility_updates: vec![],
         };
-        if let Some(state) = board.compute_state() {
-            board.state = state;
-            return Some(board);
-        } else {
-            return None;
+        for ship_index in 0..board.ships.len() {
+            let ship = board.ships[ship_index];
+            let cells = Board::ship_mask(&ship);
+            if !ship.is_dead() {
+                match cells {
+                    Some(cells) if !cells.intersects(&board.occupied) => {
+                        board.occupied = board.occupied | cells;
+                        board.damaged = board.damaged | Board::damage_mask(&ship);
+                    },
+                    _ => {
+                        // coordinates are invalid or there is another ship at these coordinates
+                        return None;
+                    },
+                }
+            }
+            board.ship_cells.push(cells.unwrap_or(BitBoard::new()));
         }
+        return Some(board);
+    }
+
+    /// Cells covered by the ship, or None if it does not fit on the board
+    fn ship_mask(ship: &Ship) -> Option<BitBoard> {
+        let mut cells = BitBoard::new();
+        for i in 0..ship.length {
+            let (x, y) = Board::get_ship_dest_coords(ship, i);
+            if !Board::coords_valid(x, y) {
+                return None;
+            }
+            cells.set(x as usize, y as usize);
+        }
+        return Some(cells);
+    }
+
+    /// Cells covered by the damaged segments of a ship that fits on the board
+    fn damage_mask(ship: &Ship) -> BitBoard {
+        let mut cells = BitBoard::new();
+        for i in (0..ship.length).filter(|&i| ship.is_segment_damaged(i)) {
+            let (x, y) = Board::get_ship_dest_coords(ship, i);
+            cells.set(x as usize, y as usize);
+        }
+        return cells;
+    }
+
+    /// Index of the ship's segment at the given cell
+    fn segment_at(ship: &Ship, x: usize, y: usize) -> Option<usize> {
+        (0..ship.length).position(|i| Board::get_ship_dest_coords(ship, i) == (x as isize, y as isize))
     }
 
     pub fn is_visible_at(&self, x: usize, y: usize) -> bool {
-        self.state[x][y].visible
+        self.visible.get(x, y)
+    }
+
+    /// Whether the segment of the ship at the given cell has been hit
+    pub fn is_hit_at(&self, x: usize, y: usize) -> bool {
+        self.damaged.get(x, y)
     }
 
     pub fn get_ship_index_at(&self, x: usize, y: usize) -> Option<u8> {
-        self.state[x][y].ship_index
+        if !self.occupied.get(x, y) {
+            return None;
+        }
+        return self.ship_cells.iter().enumerate()
+            .position(|(ship_index, cells)| !self.ships[ship_index].is_dead() && cells.get(x, y))
+            .map(|ship_index| ship_index as u8);
     }
 
     pub fn get_ships(&self) -> &Vec<Ship> {
@@ -149,8 +154,44 @@ impl Board {
         self.ships.len() > 0
     }
 
+    /**
+     * Moves a ship one cell in the given direction. The ship stays where it is if it is destroyed
+     * or would leave the board or collide with another ship.
+     */
     pub fn move_ship(&mut self, ship_index: u8, direction: Direction) -> bool {
-        return self.ships[ship_index as usize].move_me(direction) && self.add_state();
+        let ship_index = ship_index as usize;
+        let old_ship = self.ships[ship_index];
+        if !self.ships[ship_index].move_me(direction) {
+            return false;
+        }
+        let old_cells = self.ship_cells[ship_index];
+        match Board::ship_mask(&self.ships[ship_index]) {
+            Some(new_cells) if !new_cells.intersects(&(self.occupied - old_cells)) => {
+                let (old_occupied, old_damaged) = (self.occupied, self.damaged);
+                self.ship_cells[ship_index] = new_cells;
+                self.occupied = (self.occupied - old_cells) | new_cells;
+                // damaged segments move along with the ship
+                self.damaged = (self.damaged - old_cells) | Board::damage_mask(&self.ships[ship_index]);
+                self.compute_visibility_updates(old_occupied, old_damaged);
+                true
+            },
+            _ => {
+                self.ships[ship_index] = old_ship;
+                false
+            },
+        }
+    }
+
+    /**
+     * Fires a shot at (x, y). A segment that has been hit before takes no further damage; whether
+     * the shot still counts as a hit is up to the rules.
+     */
+    pub fn shoot(&mut self, x: usize, y: usize, rules: &Rules) -> HitResult {
+        if rules.damaged_segments_miss && x < W && y < H && self.is_hit_at(x, y) {
+            self.set_visible_at(x, y);
+            return HitResult::Miss;
+        }
+        return self.hit(x, y);
     }
 
     pub fn hit(&mut self, x: usize, y: usize) -> HitResult {
@@ -158,33 +199,158 @@ impl Board {
             return HitResult::Miss;
         }
         self.set_visible_at(x, y);
-        let hit_result = match self.state[x][y].ship_index {
+        let ship_index = match self.get_ship_index_at(x, y) {
             // no ship
-            None => HitResult::Miss,
-            Some(ship_index) => {
-                let ref mut ship = self.ships[ship_index as usize];
-                ship.health_points -= 1;
-                match ship.health_points {
-                    0 => HitResult::Destroyed,
-                    _ => HitResult::Hit
+            None => return HitResult::Miss,
+            // submerged ships cannot be hit
+            Some(ship_index) if self.ships[ship_index as usize].submerged => return HitResult::Miss,
+            Some(ship_index) => ship_index as usize,
+        };
+        let segment = Board::segment_at(&self.ships[ship_index], x, y).unwrap();
+        if !self.ships[ship_index].damage_segment(segment) {
+            return HitResult::Hit;
+        }
+        let (old_occupied, old_damaged) = (self.occupied, self.damaged);
+        let hit_result = if self.ships[ship_index].is_dead() {
+            // destroyed ships no longer occupy any cells
+            self.occupied = self.occupied - self.ship_cells[ship_index];
+            self.damaged = self.damaged - self.ship_cells[ship_index];
+            HitResult::Destroyed
+        } else {
+            self.damaged.set(x, y);
+            HitResult::Hit
+        };
+        self.compute_visibility_updates(old_occupied, old_damaged);
+        return hit_result;
+    }
+
+    /**
+     * Moves a ship two cells in the given direction. If the second step is not possible, the ship
+     * does not move at all.
+     */
+    pub fn double_move_ship(&mut self, ship_index: u8, direction: Direction) -> bool {
+        let mut moved = self.clone();
+        // only the cells entered or left after both steps are of interest to the opponent
+        moved.handle_visibility_updates = false;
+        if moved.move_ship(ship_index, direction) && moved.move_ship(ship_index, direction) {
+            let (old_occupied, old_damaged) = (self.occupied, self.damaged);
+            moved.handle_visibility_updates = self.handle_visibility_updates;
+            *self = moved;
+            self.compute_visibility_updates(old_occupied, old_damaged);
+            return true;
+        }
+        return false;
+    }
+
+    /**
+     * Submerges a ship, making it immune to hits until surface_ships() is called.
+     */
+    pub fn submerge_ship(&mut self, ship_index: u8) -> bool {
+        let ref mut ship = self.ships[ship_index as usize];
+        if ship.is_dead() {
+            return false;
+        }
+        ship.submerged = true;
+        return true;
+    }
+
+    pub fn surface_ships(&mut self) {
+        for ship in self.ships.iter_mut() {
+            ship.submerged = false;
+        }
+    }
+
+    /**
+     * Reveals the 3x3 area centered at (x, y), clipped at the board borders.
+     * @return what the opponent sees in each revealed cell: a ship, water or a damaged segment
+     */
+    pub fn reveal_area(&mut self, x: usize, y: usize) -> Vec<Message> {
+        let mut revealed = vec![];
+        let (x, y) = (x as isize, y as isize);
+        for dx in -1..2 {
+            for dy in -1..2 {
+                if Board::coords_valid(x + dx, y + dy) {
+                    let (cell_x, cell_y) = ((x + dx) as usize, (y + dy) as usize);
+                    self.set_visible_at(cell_x, cell_y);
+                    revealed.push(self.cell_update(cell_x, cell_y));
                 }
             }
-        };
-        self.add_state();
+        }
+        return revealed;
+    }
 
-        self.print_me(Some((x, y)));
-        self.old_states.clear();
-        return hit_result;
+    /**
+     * Radar sweep of the 3x3 area centered at (x, y), clipped at the board borders.
+     * Only reports whether any ship occupies the area, no cell is revealed.
+     */
+    pub fn radar_sweep(&self, x: usize, y: usize) -> bool {
+        let (x, y) = (x as isize, y as isize);
+        for dx in -1..2 {
+            for dy in -1..2 {
+                if Board::coords_valid(x + dx, y + dy) && self.occupied.get((x + dx) as usize, (y + dy) as usize) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    /**
+     * Fire a torpedo starting at (x, y) travelling in the given direction. Every cell the torpedo
+     * passes becomes visible. It explodes at the first cell containing a ship or at the last cell
+     * before leaving the board.
+     * @return hit result and coordinates of the cell where the torpedo exploded
+     */
+    pub fn torpedo(&mut self, x: usize, y: usize, direction: Direction, rules: &Rules) -> (HitResult, usize, usize) {
+        if x >= W || y >= H {
+            return (HitResult::Miss, x, y);
+        }
+        let (mut cur_x, mut cur_y) = (x as isize, y as isize);
+        loop {
+            let (next_x, next_y) = match direction {
+                Direction::North => (cur_x, cur_y - 1),
+                Direction::East => (cur_x + 1, cur_y),
+                Direction::South => (cur_x, cur_y + 1),
+                Direction::West => (cur_x - 1, cur_y),
+            };
+            if self.occupied.get(cur_x as usize, cur_y as usize) || !Board::coords_valid(next_x, next_y) {
+                break;
+            }
+            self.set_visible_at(cur_x as usize, cur_y as usize);
+            cur_x = next_x;
+            cur_y = next_y;
+        }
+        let hit_result = self.shoot(cur_x as usize, cur_y as usize, rules);
+        return (hit_result, cur_x as usize, cur_y as usize);
+    }
+
+    /**
+     * Cells a torpedo fired from (x, y) in the given direction passed before exploding at
+     * (impact_x, impact_y), excluding the impact cell itself.
+     */
+    pub fn torpedo_path(x: u8, y: u8, direction: Direction, impact_x: u8, impact_y: u8) -> Vec<(u8, u8)> {
+        let mut path = vec![];
+        let (mut cur_x, mut cur_y) = (x, y);
+        while (cur_x, cur_y) != (impact_x, impact_y) && (cur_x as usize) < W && (cur_y as usize) < H {
+            path.push((cur_x, cur_y));
+            match direction {
+                Direction::North if cur_y > 0 => cur_y -= 1,
+                Direction::East => cur_x += 1,
+                Direction::South => cur_y += 1,
+                Direction::West if cur_x > 0 => cur_x -= 1,
+                _ => break,
+            }
+        }
+        return path;
     }
 
     pub fn set_visible_at(&mut self, x: usize, y: usize) {
-        self.state[x][y].visible = true;
+        self.visible.set(x, y);
     }
 
     /**
-     * Compute new board state.
-     * @return true if board state is valid, false otherwise (if ships overlap or are outside board
-     * boarders)
+     * Compute the board state from the current ship positions, without visibility information.
+     * @return None if ships overlap or are outside board boarders, the state otherwise
      */
     pub fn compute_state(&mut self) -> Option<BoardState> {
         let mut new_state = [[CellState::new(); H]; W];
@@ -195,10 +361,8 @@ impl Board {
             }
             for i in 0..ship.length  {
                 let (dest_x, dest_y) = Board::get_ship_dest_coords(ship, i);
-                if !self.coords_valid(dest_x, dest_y) || new_state[dest_x as usize][dest_y as usize].has_ship() {
+                if !Board::coords_valid(dest_x, dest_y) || new_state[dest_x as usize][dest_y as usize].has_ship() {
                     // coordinates are invalid or there is another ship at these coordinates
-                    println!("Coords invalid or collision detected at {}:{}, new ship index {}", dest_x, dest_y, ship_index);
-                    self.print_me(None);
                     return None;
                 } else {
                     new_state[dest_x as usize][dest_y as usize].set_ship((ship_index) as u8);
@@ -218,44 +382,35 @@ impl Board {
         return foo;
     }
 
-    fn add_state(&mut self) -> bool {
-        if let Some(new_state) = self.compute_state() {
-            self.old_states.push(self.state.clone());
-            self.state = new_state;
-            self.compute_visibility_updates();
-            true
-        } else {
-            false
+    fn compute_visibility_updates(&mut self, old_occupied: BitBoard, old_damaged: BitBoard) {
+        if !self.handle_visibility_updates {
+            return;
+        }
+        // Only visible cells which a ship or a damaged segment entered or left are of interest
+        let changed = ((old_occupied ^ self.occupied) | (old_damaged ^ self.damaged)) & self.visible;
+        for (x, y) in changed.cells() {
+            let update = self.cell_update(x, y);
+            self.visibility_updates.push(update);
         }
     }
 
-    fn compute_visibility_updates(&mut self) {
-        // Find all cells that had ships in old state (self.state) but no longer in new_state and
-        // vice versa -> some ship moved out of some cell
-        for x in 0..W {
-            for y in 0..H {
-                let ref old_cell = self.old_states.last().unwrap()[x][y];
-                let ref mut new_cell = self.state[x][y];
-                // copy visibility information to new state
-                new_cell.visible = new_cell.visible || old_cell.visible;
-                if self.handle_visibility_updates && new_cell.visible {
-                    let ship_entered_cell = !old_cell.has_ship() && new_cell.has_ship();
-                    let ship_left_cell = old_cell.has_ship() && !new_cell.has_ship();
-                    if ship_left_cell {
-                        self.visibility_updates.push(Message::EnemyInvisibleUpdate { x: x as u8, y: y as u8 });
-                    } else if ship_entered_cell {
-                        self.visibility_updates.push(Message::EnemyVisibleUpdate { x: x as u8, y: y as u8 });
-                    }
-                }
-            }
+    /// Tells the opponent what a visible cell holds
+    fn cell_update(&self, x: usize, y: usize) -> Message {
+        let (cell_x, cell_y) = (x as u8, y as u8);
+        if self.damaged.get(x, y) {
+            Message::EnemyDamagedUpdate { x: cell_x, y: cell_y }
+        } else if self.occupied.get(x, y) {
+            Message::EnemyVisibleUpdate { x: cell_x, y: cell_y }
+        } else {
+            Message::EnemyInvisibleUpdate { x: cell_x, y: cell_y }
         }
     }
 
-    fn coords_valid(&self, x: isize, y: isize) -> bool {
+    fn coords_valid(x: isize, y: isize) -> bool {
         return x >= 0 && y >= 0 && x < (W as isize) && y < (H as isize);
     }
 
-    fn get_ship_dest_coords(ship: &Ship, i: usize) -> (isize, isize) {
+    pub fn get_ship_dest_coords(ship: &Ship, i: usize) -> (isize, isize) {
         let mut dest = (ship.x, ship.y);
         match ship.direction {
             Direction::East => dest.0 += i as isize,
@@ -266,46 +421,42 @@ impl Board {
         return (dest.0, dest.1);
     }
 
-    fn print_state(state: &BoardState, target_coords: Option<(usize, usize)>) -> Vec<String> {
-        let mut lines = vec![];
-        for y in 0..H {
-            let mut line = String::new();
-            for x in 0..W {
-                let character = match state[x][y].ship_index {
-                    Some(index) => String::from(index.to_string()),
-                    None => String::from("-"),
-                };
-
-                if let Some((target_x, target_y)) = target_coords {
-                    if x == target_x && y == target_y {
-                        line.push_str(&format!("{}", Black.on(Red).paint(character)));
-                        continue;
-                    }
+    /**
+     * Checks that the cached cell sets agree with the ships: ships lie within the board and do not
+     * overlap, and every ship lost exactly one hit point per damaged segment.
+     * @return description of the first violated invariant
+     */
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let mut occupied = BitBoard::new();
+        let mut damaged = BitBoard::new();
+        for (ship_index, ship) in self.ships.iter().enumerate() {
+            let cells = self.ship_cells[ship_index];
+            let damaged_segments = ship.damage.count_ones() as usize;
+            if !ship.is_dead() {
+                if Board::ship_mask(ship) != Some(cells) {
+                    return Err(format!("Cells of ship {} do not match its position", ship_index));
                 }
-
-                if state[x][y].visible {
-                    line.push_str(&format!("{}", Black.on(Green).paint(character)));
-                } else {
-                    line.push_str(&character);
+                if cells.intersects(&occupied) {
+                    return Err(format!("Ship {} overlaps another ship", ship_index));
                 }
+                occupied = occupied | cells;
+                damaged = damaged | Board::damage_mask(ship);
             }
-            lines.push(line);
-        }
-        return lines;
-    }
-
-    fn print_me(&self, target_coords: Option<(usize, usize)>) {
-        println!("Printing state");
-        let mut printed_boards: Vec<Vec<String>> = self.old_states.iter().map(|state| Board::print_state(&state, None)).collect();
-        printed_boards.push(Board::print_state(&self.state, target_coords));
-
-        for i in 0..10 {
-            for board in &printed_boards {
-                print!("{}  |  ", board.get(i).unwrap());
+            if ship.damage.checked_shr(ship.length as u32).unwrap_or(0) != 0 {
+                return Err(format!("Ship {} has damage beyond its length", ship_index));
             }
-            print!("\n");
+            if ship.health_points + damaged_segments != ship.length {
+                return Err(format!("Ship {} has {} hit points but {} damaged segments",
+                    ship_index, ship.health_points, damaged_segments));
+            }
+        }
+        if occupied != self.occupied {
+            return Err("Occupied cells do not match the living ships".to_owned());
+        }
+        if damaged != self.damaged {
+            return Err("Damaged cells do not match the damaged segments".to_owned());
         }
-        println!("");
+        return Ok(());
     }
 
     pub fn is_dead(&self) -> bool {
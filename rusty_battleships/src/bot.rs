@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+use board::Board;
+use knowledge::OpponentBoard;
+use message::{Message, Outcome, ShipPlacement};
+use rules::Rules;
+use ship::Ship;
+use strategy::Strategy;
+
+/// Time to wait before challenging a player again who was not ready yet
+pub const CHALLENGE_DELAY_MS: u64 = 1000;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Status {
+    AwaitFeatures,
+    AwaitLogin,
+    AwaitReady,
+    AwaitChallenge,
+    Waiting,
+    PlacingShips,
+    Playing,
+    GameOver,
+}
+
+/**
+ * A computer player speaking the protocol: it logs in, waits to be challenged or challenges a
+ * player itself, and plays a game with its strategy. The bot only reacts to messages from the
+ * server, sending them is left to whoever drives it, be it over TCP or in-process.
+ */
+pub struct Bot {
+    strategy: Box<Strategy>,
+    status: Status,
+    // nickname to log in with and the one the server accepted
+    base_nickname: String,
+    nickname: String,
+    login_attempts: usize,
+    // password of the nickname's account, as long as the server supports accounts
+    password: Option<String>,
+    // whether the account did not exist and is being registered
+    registering: bool,
+    challenge: Option<String>,
+    // whether the challenged player was not ready yet, so the challenge is to be sent again
+    challenge_declined: bool,
+    rules: Rules,
+    fleet: Vec<Ship>,
+    my_board: Option<Board>,
+    // all opponents still in the game, in the order they were announced
+    opponents: Vec<String>,
+    opponent_boards: HashMap<String, OpponentBoard>,
+    my_turn: bool,
+    // opponent shot at, as long as the server has not responded to the shot yet
+    pending_target: Option<String>,
+    outcome: Option<Outcome>,
+}
+
+/// The rules the bot has to apply itself, as far as the server's features tell.
+fn rules_from_features(features: &Vec<String>) -> Rules {
+    let has_feature = |feature: &str| features.iter().any(|f| f == feature);
+    Rules {
+        no_touching: has_feature("No Touching Ships"),
+        damaged_segments_miss: has_feature("Damaged Segments Miss"),
+        ..Rules::default()
+    }
+}
+
+impl Bot {
+    /// A bot that waits to be challenged or, given a nickname, challenges that player
+    pub fn new(nickname: String, strategy: Box<Strategy>, challenge: Option<String>) -> Bot {
+        Bot {
+            strategy: strategy,
+            status: Status::AwaitFeatures,
+            base_nickname: nickname.clone(),
+            nickname: nickname,
+            login_attempts: 1,
+            password: None,
+            registering: false,
+            challenge: challenge,
+            challenge_declined: false,
+            rules: Rules::default(),
+            fleet: vec![],
+            my_board: None,
+            opponents: vec![],
+            opponent_boards: HashMap::new(),
+            my_turn: false,
+            pending_target: None,
+            outcome: None,
+        }
+    }
+
+    /**
+     * Logs in with the account of the nickname instead of as a guest, registering it if there is
+     * none yet. Servers without accounts are joined as a guest.
+     */
+    pub fn set_password(&mut self, password: String) {
+        self.password = Some(password);
+    }
+
+    /// The request to send right after connecting
+    pub fn connect(&mut self) -> Message {
+        self.status = Status::AwaitFeatures;
+        Message::GetFeaturesRequest
+    }
+
+    pub fn get_nickname(&self) -> &String {
+        &self.nickname
+    }
+
+    /// Outcome of the last game, once it is over
+    pub fn get_outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// Starts over in the lobby after a game without reconnecting
+    pub fn play_again(&mut self, strategy: Box<Strategy>) -> Message {
+        self.strategy = strategy;
+        self.outcome = None;
+        self.my_board = None;
+        self.opponents.clear();
+        self.opponent_boards.clear();
+        self.my_turn = false;
+        self.pending_target = None;
+        self.challenge_declined = false;
+        return self.enter_lobby();
+    }
+
+    /**
+     * The challenge to send again if the challenged player was not ready to play yet. Whoever
+     * drives the bot waits `CHALLENGE_DELAY_MS` before sending it, so as not to flood the server.
+     */
+    pub fn retry_challenge(&mut self) -> Option<Message> {
+        if !self.challenge_declined {
+            return None;
+        }
+        self.challenge_declined = false;
+        return self.challenge.clone().map(|opponent| Message::ChallengePlayerRequest { username: opponent });
+    }
+
+    fn enter_lobby(&mut self) -> Message {
+        if let Some(ref opponent) = self.challenge {
+            self.status = Status::AwaitChallenge;
+            return Message::ChallengePlayerRequest { username: opponent.clone() };
+        }
+        self.status = Status::AwaitReady;
+        return Message::ReadyRequest;
+    }
+
+    /**
+     * Handles a message from the server. Fails if the server rejected a request, after which it
+     * closes the connection.
+     * @return the requests to send in reply, in order
+     */
+    pub fn handle_message(&mut self, msg: Message) -> Result<Vec<Message>> {
+        let mut requests = vec![];
+        match msg {
+            Message::InvalidRequestResponse => {
+                return Err(Error::new(ErrorKind::InvalidData, "The server rejected a request."));
+            },
+            // the server lets running games finish before it shuts down
+            Message::ServerGoingDownUpdate { .. } if self.status == Status::Playing || self.status == Status::PlacingShips => {
+                return Ok(requests);
+            },
+            Message::ServerGoingDownUpdate { errormessage } => {
+                return Err(Error::new(ErrorKind::ConnectionAborted, errormessage));
+            },
+            Message::GameOverUpdate { outcome, .. } => {
+                self.outcome = Some(outcome);
+                self.status = Status::GameOver;
+                return Ok(requests);
+            },
+            _ => {},
+        }
+
+        match self.status {
+            Status::AwaitFeatures => match msg {
+                Message::FeaturesResponse { features } => {
+                    self.rules = rules_from_features(&features);
+                    self.status = Status::AwaitLogin;
+                    if !features.iter().any(|feature| feature == "Player Accounts") {
+                        self.password = None;
+                    }
+                    requests.push(match self.password {
+                        Some(ref password) => Message::PasswordLoginRequest { username: self.nickname.clone(), password: password.clone() },
+                        None => Message::LoginRequest { username: self.nickname.clone() },
+                    });
+                },
+                _ => {},
+            },
+            Status::AwaitLogin => match msg {
+                Message::OkResponse | Message::TokenResponse { .. } => requests.push(self.enter_lobby()),
+                // an account cannot be given another nickname
+                Message::NameTakenResponse { .. } if self.password.is_some() => {
+                    let reason = if self.registering { "is registered with another password" } else { "is already logged in" };
+                    return Err(Error::new(ErrorKind::PermissionDenied, format!("{} {}.", self.nickname, reason)));
+                },
+                Message::WrongCredentialsResponse if !self.registering => {
+                    self.registering = true;
+                    let password = self.password.clone().unwrap();
+                    requests.push(Message::RegisterRequest { username: self.nickname.clone(), password: password });
+                },
+                Message::AccountRequiredResponse => {
+                    return Err(Error::new(ErrorKind::PermissionDenied, "The server only admits players with an account."));
+                },
+                Message::NameTakenResponse { .. } => {
+                    self.login_attempts += 1;
+                    self.nickname = format!("{}{}", self.base_nickname, self.login_attempts);
+                    requests.push(Message::LoginRequest { username: self.nickname.clone() });
+                },
+                _ => {},
+            },
+            Status::AwaitReady => match msg {
+                Message::OkResponse => self.status = Status::Waiting,
+                _ => {},
+            },
+            Status::AwaitChallenge => match msg {
+                Message::OkResponse => {
+                    let opponent = self.challenge.clone().unwrap();
+                    requests.push(self.start_game(vec![opponent]));
+                },
+                Message::NotWaitingResponse { .. } | Message::NoSuchPlayerResponse { .. } => self.challenge_declined = true,
+                _ => {},
+            },
+            Status::Waiting => match msg {
+                Message::GameStartUpdate { nickname } => requests.push(self.start_game(vec![nickname])),
+                Message::FreeForAllStartUpdate { nicknames } => requests.push(self.start_game(nicknames)),
+                Message::TeamGameStartUpdate { opponents, .. } => requests.push(self.start_game(opponents)),
+                _ => {},
+            },
+            Status::PlacingShips => match msg {
+                Message::OkResponse => {
+                    self.my_board = Board::try_create(self.fleet.clone(), false);
+                    self.status = Status::Playing;
+                },
+                Message::InvalidPlacementResponse { .. } => requests.push(self.place_ships()),
+                _ => {},
+            },
+            Status::Playing => {
+                self.strategy.handle_message(&msg);
+                self.handle_game_message(msg);
+            },
+            Status::GameOver => {},
+        }
+
+        if self.status == Status::Playing && self.my_turn && self.pending_target.is_none() {
+            if let Some(request) = self.take_turn() {
+                requests.push(request);
+            }
+        }
+        return Ok(requests);
+    }
+
+    fn start_game(&mut self, opponents: Vec<String>) -> Message {
+        self.opponent_boards = opponents.iter().map(|name| (name.clone(), OpponentBoard::new())).collect();
+        self.opponents = opponents;
+        return self.place_ships();
+    }
+
+    fn place_ships(&mut self) -> Message {
+        self.status = Status::PlacingShips;
+        self.fleet = self.strategy.place_fleet(&self.rules);
+        let placement: Vec<ShipPlacement> = self.fleet.iter().map(|ship| ShipPlacement {
+            x: ship.x as u8,
+            y: ship.y as u8,
+            direction: ship.direction,
+        }).collect();
+        return Message::PlaceShipsRequest { placement: [
+            placement[0],
+            placement[1],
+            placement[2],
+            placement[3],
+            placement[4],
+        ]};
+    }
+
+    fn set_my_turn(&mut self, my_turn: bool) {
+        if !my_turn && self.my_turn {
+            // the opponents' ships that left a cell before our turn are no longer close by
+            for board in self.opponent_boards.values_mut() {
+                board.next_turn();
+            }
+        }
+        self.my_turn = my_turn;
+    }
+
+    fn handle_game_message(&mut self, msg: Message) {
+        let two_player = self.opponents.len() == 1;
+        match msg {
+            Message::YourTurnUpdate => self.set_my_turn(true),
+            Message::EnemyTurnUpdate | Message::AfkWarningUpdate { .. } => self.set_my_turn(false),
+            Message::PlayerTurnUpdate { nickname } => {
+                let my_turn = nickname == self.nickname;
+                self.set_my_turn(my_turn);
+            },
+            Message::NotYourTurnResponse => {
+                self.pending_target = None;
+                self.set_my_turn(false);
+            },
+            Message::HitResponse { .. } | Message::MissResponse { .. } | Message::DestroyedResponse { .. } => {
+                if let Some(target) = self.pending_target.take() {
+                    if let Some(board) = self.opponent_boards.get_mut(&target) {
+                        board.ingest(&msg);
+                    }
+                }
+                if two_player {
+                    self.set_my_turn(false);
+                }
+            },
+            // our own board in two-player games, after which it is our turn
+            Message::EnemyHitUpdate { x, y } | Message::EnemyMissUpdate { x, y } => {
+                if let Some(ref mut board) = self.my_board {
+                    board.hit(x as usize, y as usize);
+                }
+                self.set_my_turn(true);
+            },
+            Message::EnemyTorpedoUpdate { x, y, direction } => {
+                if let Some(ref mut board) = self.my_board {
+                    board.torpedo(x as usize, y as usize, direction, &self.rules);
+                }
+                self.set_my_turn(true);
+            },
+            Message::EnemyRadarUpdate { .. } |
+            Message::EnemyReconUpdate { .. } |
+            Message::EnemyAfkUpdate { .. } if two_player => self.set_my_turn(true),
+            // the opponent's board in two-player games
+            Message::EnemyVisibleUpdate { .. } | Message::EnemyInvisibleUpdate { .. } | Message::EnemyDamagedUpdate { .. } => {
+                if let Some(opponent) = self.opponents.first() {
+                    self.opponent_boards.get_mut(opponent).unwrap().ingest(&msg);
+                }
+            },
+            // any opponent's board in games with more players
+            Message::BoardVisibleUpdate { ref nickname, .. } |
+            Message::BoardInvisibleUpdate { ref nickname, .. } |
+            Message::BoardDamagedUpdate { ref nickname, .. } |
+            Message::PlayerShotUpdate { target: ref nickname, .. } => {
+                if let Message::PlayerShotUpdate { x, y, hit: true, .. } = msg {
+                    if *nickname == self.nickname {
+                        if let Some(ref mut board) = self.my_board {
+                            board.hit(x as usize, y as usize);
+                        }
+                    }
+                }
+                if let Some(board) = self.opponent_boards.get_mut(nickname) {
+                    board.ingest(&msg);
+                }
+            },
+            Message::PlayerEliminatedUpdate { nickname } => self.opponents.retain(|name| *name != nickname),
+            _ => {},
+        }
+    }
+
+    /// The opponent to shoot at: the one whose fleet is closest to being destroyed
+    fn target(&self) -> Option<String> {
+        self.opponents.iter()
+            .max_by_key(|name| self.opponent_boards.get(*name).map_or(0, |board| board.get_destroyed()))
+            .cloned()
+    }
+
+    fn take_turn(&mut self) -> Option<Message> {
+        let target = match self.target() {
+            Some(target) => target,
+            None => return None,
+        };
+        let mut turn = match self.my_board {
+            Some(ref board) => self.strategy.choose_turn(board, &self.opponent_boards[&target], &self.rules),
+            None => return None,
+        };
+        if let Some((id, direction)) = turn.movement {
+            let moved = match self.my_board {
+                Some(ref mut board) => (id as usize) < board.get_ships().len() && board.move_ship(id, direction),
+                None => false,
+            };
+            if !moved {
+                // the server would reject the whole request
+                turn.movement = None;
+            }
+        }
+        let request = if self.opponents.len() == 1 { turn.to_request() } else { turn.to_request_at(&target) };
+        self.pending_target = Some(target);
+        return Some(request);
+    }
+}
@@ -0,0 +1,275 @@
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Result, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use board::{Board, W, H};
+use knowledge::OpponentBoard;
+use message::{serialize_message, deserialize_message, Message};
+use placement;
+use rules::Rules;
+use ship::Ship;
+use strategy::{self, Strategy, Turn};
+use text_protocol;
+
+/// Nickname of the opponent the external program is told it plays against
+const OPPONENT_NAME: &'static str = "Opponent";
+
+/**
+ * A strategy played by another program speaking the protocol over its stdin and stdout, as if it
+ * was connected to a server: it is answered to the lobby requests it sends, challenged right away
+ * and receives the responses and updates of the game. Any error or unexpected request forfeits the
+ * game. The program is started anew for every game and killed once the strategy is dropped.
+ */
+pub struct ExternalStrategy {
+    program: Program,
+    first_turn: bool,
+    opponent_started: bool,
+}
+
+/// Prefix of a player that speaks the text protocol rather than the binary one
+pub const TEXT_PREFIX: &'static str = "text:";
+
+/**
+ * Creates a player from its description: the name of a built-in strategy, the command line of a
+ * program speaking the text protocol prefixed with `text:`, or that of a program speaking the
+ * binary protocol.
+ */
+pub fn from_spec(spec: &str, seed: Option<usize>) -> ::std::result::Result<Box<Strategy>, String> {
+    if let Some(strategy) = strategy::from_name(spec, seed) {
+        return Ok(strategy);
+    }
+    let result: Result<Box<Strategy>> = if spec.starts_with(TEXT_PREFIX) {
+        TextStrategy::spawn(&spec[TEXT_PREFIX.len()..]).map(|strategy| Box::new(strategy) as Box<Strategy>)
+    } else {
+        ExternalStrategy::spawn(spec).map(|strategy| Box::new(strategy) as Box<Strategy>)
+    };
+    return result.map_err(|e| format!("Could not start {}: {}", spec, e));
+}
+
+/**
+ * Another program the game is played with over its stdin and stdout. Once talking to it failed, it
+ * is not talked to anymore and loses the game by forfeit. It is killed once it is dropped.
+ */
+struct Program {
+    child: Child,
+    reader: BufReader<ChildStdout>,
+    writer: BufWriter<ChildStdin>,
+    // why the game could not be continued
+    failure: Option<Error>,
+}
+
+impl Program {
+    /// Starts the program given as a command line, with the arguments separated by whitespace
+    fn spawn(command_line: &str) -> Result<Program> {
+        let mut words = command_line.split_whitespace();
+        let program = try!(words.next().ok_or(Error::new(ErrorKind::InvalidInput, "No program given.")));
+        let mut child = try!(Command::new(program)
+            .args(&words.collect::<Vec<&str>>())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn());
+        let reader = BufReader::new(child.stdout.take().unwrap());
+        let writer = BufWriter::new(child.stdin.take().unwrap());
+        return Ok(Program { child: child, reader: reader, writer: writer, failure: None });
+    }
+
+    /// Talks to the program unless it failed before, and remembers why it failed if it does now.
+    fn talk<T, F>(&mut self, talk: F) -> Option<T> where F: FnOnce(&mut Program) -> Result<T> {
+        if self.failure.is_some() {
+            return None;
+        }
+        match talk(self) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                self.failure = Some(e);
+                None
+            },
+        }
+    }
+
+    /// Places the fleet as the program does, an empty fleet is not a valid placement and forfeits the game.
+    fn place_fleet<F>(&mut self, talk: F) -> Vec<Ship> where F: FnOnce(&mut Program) -> Result<Vec<Ship>> {
+        self.talk(talk).unwrap_or(vec![])
+    }
+
+    /// Takes the program's turn, a shot off the board forfeits the game.
+    fn choose_turn<F>(&mut self, talk: F) -> Turn where F: FnOnce(&mut Program) -> Result<Turn> {
+        self.talk(talk).unwrap_or(Turn::shot(W, H))
+    }
+
+    fn send_message(&mut self, msg: Message) -> Result<()> {
+        try!(self.writer.write(&serialize_message(msg)[..]));
+        try!(self.writer.flush());
+        Ok(())
+    }
+
+    fn receive_message(&mut self) -> Result<Message> {
+        deserialize_message(&mut self.reader)
+    }
+
+    fn send_line(&mut self, line: &str) -> Result<()> {
+        try!(writeln!(self.writer, "{}", line));
+        try!(self.writer.flush());
+        Ok(())
+    }
+
+    /// Skips empty lines and those starting with `#`.
+    fn receive_line(&mut self) -> Result<String> {
+        loop {
+            let mut line = String::new();
+            if try!(self.reader.read_line(&mut line)) == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "The program quit."));
+            }
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                return Ok(line.to_owned());
+            }
+        }
+    }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Features as announced by the server for the given rules, as far as strategies can apply them
+fn features(rules: &Rules) -> Vec<String> {
+    let mut features = vec![];
+    if rules.max_turns.is_some() {
+        features.push("Turn Limit".to_owned());
+    }
+    if rules.no_touching {
+        features.push("No Touching Ships".to_owned());
+    }
+    if rules.damaged_segments_miss {
+        features.push("Damaged Segments Miss".to_owned());
+    }
+    return features;
+}
+
+impl ExternalStrategy {
+    /// Starts the program given as a command line, with the arguments separated by whitespace
+    pub fn spawn(command_line: &str) -> Result<ExternalStrategy> {
+        Ok(ExternalStrategy { program: try!(Program::spawn(command_line)), first_turn: true, opponent_started: false })
+    }
+
+    /// Why the program lost the game by forfeit, if it did
+    pub fn get_failure(&self) -> Option<&Error> {
+        self.program.failure.as_ref()
+    }
+}
+
+/// Answers the lobby requests until the program is ready to play and returns its fleet.
+fn start_game(program: &mut Program, rules: &Rules) -> Result<Vec<Ship>> {
+    loop {
+        match try!(program.receive_message()) {
+            Message::GetFeaturesRequest => try!(program.send_message(Message::FeaturesResponse { features: features(rules) })),
+            Message::LoginRequest { .. } => try!(program.send_message(Message::OkResponse)),
+            Message::ReadyRequest => {
+                try!(program.send_message(Message::OkResponse));
+                try!(program.send_message(Message::GameStartUpdate { nickname: OPPONENT_NAME.to_owned() }));
+                break;
+            },
+            // whoever the program challenges accepts
+            Message::ChallengePlayerRequest { .. } => {
+                try!(program.send_message(Message::OkResponse));
+                break;
+            },
+            msg => return Err(Error::new(ErrorKind::InvalidData, format!("Unexpected {:?} in the lobby.", msg))),
+        }
+    }
+
+    match try!(program.receive_message()) {
+        Message::PlaceShipsRequest { placement } => {
+            try!(program.send_message(Message::OkResponse));
+            Ok(placement::fleet_from_placement(&placement, rules))
+        },
+        msg => Err(Error::new(ErrorKind::InvalidData, format!("Expected a placement, got {:?}.", msg))),
+    }
+}
+
+fn receive_turn(program: &mut Program, announce: bool) -> Result<Turn> {
+    if announce {
+        try!(program.send_message(Message::YourTurnUpdate));
+    }
+    match try!(program.receive_message()) {
+        Message::ShootRequest { x, y } => Ok(Turn { movement: None, x: x, y: y }),
+        Message::MoveAndShootRequest { id, direction, x, y } => Ok(Turn { movement: Some((id, direction)), x: x, y: y }),
+        msg => Err(Error::new(ErrorKind::InvalidData, format!("Expected a shot, got {:?}.", msg))),
+    }
+}
+
+impl Strategy for ExternalStrategy {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        self.program.place_fleet(|program| start_game(program, rules))
+    }
+
+    fn choose_turn(&mut self, _my_board: &Board, _opponent: &OpponentBoard, _rules: &Rules) -> Turn {
+        // afterwards, the turn passes on with the opponent's shots
+        let announce = self.first_turn && !self.opponent_started;
+        self.first_turn = false;
+        self.program.choose_turn(|program| receive_turn(program, announce))
+    }
+
+    fn handle_message(&mut self, msg: &Message) {
+        match *msg {
+            Message::EnemyHitUpdate { .. } | Message::EnemyMissUpdate { .. } if self.first_turn => {
+                self.opponent_started = true;
+            },
+            _ => {},
+        }
+        self.program.talk(|program| program.send_message(msg.clone()));
+    }
+}
+
+/**
+ * A strategy played by another program speaking the line-based text protocol over its stdin and
+ * stdout. Unlike with `ExternalStrategy`, the program only takes part in the game itself, so
+ * it can be written without knowing the binary protocol. Lines from the program starting with `#`
+ * are ignored. An invalid line forfeits the game.
+ */
+pub struct TextStrategy {
+    program: Program,
+}
+
+impl TextStrategy {
+    /// Starts the program given as a command line, with the arguments separated by whitespace
+    pub fn spawn(command_line: &str) -> Result<TextStrategy> {
+        Ok(TextStrategy { program: try!(Program::spawn(command_line)) })
+    }
+
+    /// Why the program lost the game by forfeit, if it did
+    pub fn get_failure(&self) -> Option<&Error> {
+        self.program.failure.as_ref()
+    }
+}
+
+fn request_fleet(program: &mut Program, rules: &Rules) -> Result<Vec<Ship>> {
+    try!(program.send_line(&text_protocol::rules_line(rules)));
+    try!(program.send_line("place"));
+    let placement = try!(text_protocol::parse_placement(&try!(program.receive_line())));
+    return Ok(placement::fleet_from_placement(&placement, rules));
+}
+
+fn request_turn(program: &mut Program) -> Result<Turn> {
+    try!(program.send_line("turn"));
+    return text_protocol::parse_turn(&try!(program.receive_line()));
+}
+
+impl Strategy for TextStrategy {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        self.program.place_fleet(|program| request_fleet(program, rules))
+    }
+
+    fn choose_turn(&mut self, _my_board: &Board, _opponent: &OpponentBoard, _rules: &Rules) -> Turn {
+        self.program.choose_turn(request_turn)
+    }
+
+    fn handle_message(&mut self, msg: &Message) {
+        if let Some(line) = text_protocol::to_line(msg) {
+            self.program.talk(|program| program.send_line(&line));
+        }
+    }
+}
@@ -1,4 +1,8 @@
+use std::collections::HashMap;
+
 use board::{Board};
+use message::Message;
+use rules::Rules;
 
 use rand::{thread_rng, Rng};
 
@@ -10,63 +14,283 @@ pub enum GameState {
     Running,
 }
 
-static LIMIT_SECONDS: i64 = 60;
+struct Participant {
+    name: String,
+    // team games only: index of the player's team
+    team: Option<u8>,
+    board: Board,
+    afk_count: u8,
+    charges: Charges,
+    // remaining turns until each ship's ability can be used again
+    cooldowns: [u8; 5],
+}
 
 pub struct Game {
-    board1: Board,
-    board2: Board,
-    player1: String,
-    player2: String,
+    // all players in turn order, including eliminated players who are spectating
+    participants: Vec<Participant>,
+    active_index: usize,
+    free_for_all: bool,
     last_turn_started_at: Option<time::PreciseTime>,
-    player1_active: bool,
-    player1_afk_count: u8,
-    player2_afk_count: u8,
+    turn_count: u16,
+    // how long the turns took that ended since they were last asked for
+    turn_durations: Vec<time::Duration>,
+    ability_updates: HashMap<String, Vec<Message>>,
+    rules: Rules,
     state: GameState,
 }
 
+/// Special weapon charges a player has left
+#[derive(Copy, Clone, Debug)]
+pub struct Charges {
+    pub radar: u8,
+    pub torpedo: u8,
+}
+
 impl PartialEq for Game {
     fn eq(&self, other: &Game) -> bool {
-        self.player1 == other.player1 && self.player2 == other.player2
+        self.get_player_names() == other.get_player_names()
     }
 }
 
 impl Game {
-    pub fn new(board1: Board, board2: Board, player1: String, player2: String) -> Game {
+    pub fn new(board1: Board, board2: Board, player1: String, player2: String, rules: Rules) -> Game {
+        Game::with_players(vec![(player1, None, board1), (player2, None, board2)], false, rules)
+    }
+
+    /// Creates a free-for-all game in which every player fights against all others
+    pub fn new_free_for_all(players: Vec<(String, Board)>, rules: Rules) -> Game {
+        let players = players.into_iter().map(|(name, board)| (name, None, board)).collect();
+        Game::with_players(players, true, rules)
+    }
+
+    /**
+     * Creates a game of two teams with two players each. Allies share what they find out about
+     * the opposing team's boards and the teams take turns alternately.
+     */
+    pub fn new_team_game(team1: Vec<(String, Board)>, team2: Vec<(String, Board)>, rules: Rules) -> Game {
+        assert!(team1.len() == 2 && team2.len() == 2, "Teams consist of two players.");
+        // seating members of both teams alternately makes the turn order alternate between teams
+        let mut players = vec![];
+        for ((name1, board1), (name2, board2)) in team1.into_iter().zip(team2.into_iter()) {
+            players.push((name1, Some(0), board1));
+            players.push((name2, Some(1), board2));
+        }
+        Game::with_players(players, false, rules)
+    }
+
+    fn with_players(players: Vec<(String, Option<u8>, Board)>, free_for_all: bool, rules: Rules) -> Game {
+        assert!(players.len() >= 2, "A game needs at least two players.");
+        let charges = Charges { radar: rules.radar_charges, torpedo: rules.torpedo_charges };
+        let player_count = players.len();
         Game {
-            board1: board1,
-            board2: board2,
-            player1: player1,
-            player2: player2,
+            participants: players.into_iter().map(|(name, team, board)| Participant {
+                name: name,
+                team: team,
+                board: board,
+                afk_count: rules.afk_strikes,
+                charges: charges,
+                cooldowns: [0; 5],
+            }).collect(),
+            active_index: thread_rng().gen_range(0, player_count),
+            free_for_all: free_for_all,
             last_turn_started_at: None,
-            player1_active: thread_rng().gen(),
-            player1_afk_count: 3,
-            player2_afk_count: 3,
+            turn_count: 0,
+            turn_durations: vec![],
+            ability_updates: HashMap::new(),
+            rules: rules,
             state: GameState::Placing,
         }
     }
 
+    fn participant(&self, player_name: &String) -> &Participant {
+        self.participants.iter().find(|p| p.name == *player_name).expect("Player is not part of this game.")
+    }
+
+    fn participant_mut(&mut self, player_name: &String) -> &mut Participant {
+        self.participants.iter_mut().find(|p| p.name == *player_name).expect("Player is not part of this game.")
+    }
+
+    pub fn is_free_for_all(&self) -> bool {
+        self.free_for_all
+    }
+
+    pub fn is_team_game(&self) -> bool {
+        self.participants.iter().any(|p| p.team.is_some())
+    }
+
+    /// Whether this is a classic game of two players, who do not need to name their target
+    pub fn is_two_player(&self) -> bool {
+        !self.free_for_all && !self.is_team_game()
+    }
+
+    /// Whether both players are on the same team. Every player is their own ally.
+    pub fn is_ally(&self, player_name: &String, other_name: &String) -> bool {
+        if player_name == other_name {
+            return true;
+        }
+        match (self.participant(player_name).team, self.participant(other_name).team) {
+            (Some(team), Some(other_team)) => team == other_team,
+            _ => false,
+        }
+    }
+
+    /// Name of the player's teammate in team games
+    pub fn get_ally_name(&self, player_name: &String) -> Option<String> {
+        self.participants.iter()
+            .find(|p| p.name != *player_name && self.is_ally(&p.name, player_name))
+            .map(|p| p.name.clone())
+    }
+
+    /// Whether the game is over because all remaining fleets belong to allies
+    pub fn is_decided(&self) -> bool {
+        let living_players = self.get_living_players();
+        match living_players.first() {
+            Some(first) => living_players.iter().all(|p| self.is_ally(p, first)),
+            None => true,
+        }
+    }
+
+    pub fn has_player(&self, player_name: &String) -> bool {
+        self.participants.iter().any(|p| p.name == *player_name)
+    }
+
+    /// Names of all players in turn order, including eliminated ones
+    pub fn get_player_names(&self) -> Vec<String> {
+        self.participants.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// Names of all players whose fleet has not been destroyed yet
+    pub fn get_living_players(&self) -> Vec<String> {
+        self.participants.iter()
+            .filter(|p| !self.is_eliminated(&p.name))
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    /// Whether the player's fleet has been destroyed. Nobody is eliminated while placing ships.
+    pub fn is_eliminated(&self, player_name: &String) -> bool {
+        self.is_running() && self.participant(player_name).board.is_dead()
+    }
+
+    /// In two-player games: the other player. Otherwise the next player in turn order.
     pub fn get_opponent_name(&self, player_name: &String) -> &String {
-        return if *self.player1 == *player_name { &self.player2 } else { &self.player1 };
+        let index = self.participants.iter().position(|p| p.name == *player_name).unwrap();
+        &self.participants[(index + 1) % self.participants.len()].name
     }
 
     pub fn get_board(&mut self, player_name: &String) -> &mut Board {
-        return if *self.player1 == *player_name { &mut self.board1 } else { &mut self.board2 };
+        &mut self.participant_mut(player_name).board
     }
 
     pub fn get_opponent_board(&mut self, player_name: &String) -> &mut Board {
-        return if *self.player1 == *player_name { &mut self.board2 } else { &mut self.board1 };
+        let opponent_name = self.get_opponent_name(player_name).clone();
+        self.get_board(&opponent_name)
+    }
+
+    /// Whether all players have placed their ships
+    pub fn all_boards_placed(&self) -> bool {
+        self.participants.iter().all(|p| p.board.has_ships())
+    }
+
+    /**
+     * Removes a player who left the game (surrender, disconnect, AFK). If it was their turn, the
+     * turn passes on to the next living player of the opposing side, without counting as played.
+     */
+    pub fn remove_player(&mut self, player_name: &String) {
+        let index = self.participants.iter().position(|p| p.name == *player_name).unwrap();
+        let was_active = index == self.active_index && self.is_running();
+        if was_active {
+            if let Some(next_index) = self.next_enemy_index(index) {
+                self.active_index = next_index;
+            }
+        }
+        self.participants.remove(index);
+        if index < self.active_index {
+            self.active_index -= 1;
+        }
+        self.active_index %= self.participants.len();
+        if was_active {
+            self.last_turn_started_at = Some(time::PreciseTime::now());
+            self.start_turn();
+        }
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn get_charges(&self, player_name: &String) -> Charges {
+        self.participant(player_name).charges
+    }
+
+    /// Uses up one radar charge. Returns false if the player has none left.
+    pub fn use_radar_charge(&mut self, player_name: &String) -> bool {
+        let ref mut charges = self.participant_mut(player_name).charges;
+        if charges.radar == 0 {
+            return false;
+        }
+        charges.radar -= 1;
+        true
+    }
+
+    /// Uses up one torpedo charge. Returns false if the player has none left.
+    pub fn use_torpedo_charge(&mut self, player_name: &String) -> bool {
+        let ref mut charges = self.participant_mut(player_name).charges;
+        if charges.torpedo == 0 {
+            return false;
+        }
+        charges.torpedo -= 1;
+        true
+    }
+
+    pub fn get_cooldown(&self, player_name: &String, ship_index: usize) -> u8 {
+        self.participant(player_name).cooldowns[ship_index]
+    }
+
+    /// Puts the ability of the given ship on cooldown after it has been used.
+    pub fn start_cooldown(&mut self, player_name: &String, ship_index: usize) {
+        let turns = self.rules.ability_cooldown;
+        self.participant_mut(player_name).cooldowns[ship_index] = turns;
+        self.ability_updates.entry(player_name.clone()).or_insert(vec![])
+            .push(Message::AbilityCooldownUpdate { id: ship_index as u8, turns: turns });
+    }
+
+    /// Returns the cooldown changes players need to be informed about.
+    pub fn pop_ability_updates(&mut self) -> HashMap<String, Vec<Message>> {
+        let updates = self.ability_updates.clone();
+        self.ability_updates.clear();
+        return updates;
+    }
+
+    /// Returns how long the turns took that ended since the last call.
+    pub fn pop_turn_durations(&mut self) -> Vec<time::Duration> {
+        ::std::mem::replace(&mut self.turn_durations, vec![])
+    }
+
+    /// Ends the turn that decided the game, which is not followed by another one
+    pub fn end_last_turn(&mut self) {
+        if let Some(start_time) = self.last_turn_started_at.take() {
+            self.turn_durations.push(start_time.to(time::PreciseTime::now()));
+        }
     }
 
     pub fn my_turn(&self, player_name: &String) -> bool {
-        return if *self.player1 == *player_name { self.player1_active } else { !self.player1_active };
+        self.participants[self.active_index].name == *player_name
     }
 
     pub fn get_active_player(&self) -> String {
-        return if self.player1_active { self.player1.clone() } else { self.player2.clone() };
+        self.participants[self.active_index].name.clone()
     }
 
+    /// In two-player games: the player who is not on turn. Otherwise the next player in turn order.
     pub fn get_waiting_player(&self) -> String {
-        return if self.player1_active { self.player2.clone() } else { self.player1.clone() };
+        self.get_opponent_name(&self.get_active_player()).clone()
+    }
+
+    /// Gives the first turn to the given player instead of a random one, before the game starts
+    pub fn set_active_player(&mut self, player_name: &String) {
+        self.active_index = self.participants.iter().position(|p| p.name == *player_name)
+            .expect("Player is not part of this game.");
     }
 
     pub fn start(&mut self) {
@@ -74,16 +298,89 @@ impl Game {
         self.last_turn_started_at = Some(time::PreciseTime::now());
     }
 
+    /**
+     * Passes the turn on to the next player whose fleet has not been destroyed. In team games,
+     * the turn always goes to the opposing team.
+     */
     pub fn switch_turns(&mut self) {
-        self.player1_active = !self.player1_active;
-        self.last_turn_started_at = Some(time::PreciseTime::now());
+        if let Some(next_index) = self.next_enemy_index(self.active_index) {
+            self.active_index = next_index;
+        }
+        let now = time::PreciseTime::now();
+        if let Some(start_time) = self.last_turn_started_at {
+            self.turn_durations.push(start_time.to(now));
+        }
+        self.last_turn_started_at = Some(now);
+        self.turn_count += 1;
+        self.start_turn();
+    }
+
+    /// Index of the next player after the given one in turn order who is neither eliminated nor their ally
+    fn next_enemy_index(&self, index: usize) -> Option<usize> {
+        let player_count = self.participants.len();
+        let ref name = self.participants[index].name;
+        (1..player_count)
+            .map(|offset| (index + offset) % player_count)
+            .find(|&other| !self.participants[other].board.is_dead() && !self.is_ally(&self.participants[other].name, name))
+    }
+
+    /// Submerged ships surface and cooldowns tick at the start of their owner's turn
+    fn start_turn(&mut self) {
+        let ref mut active = self.participants[self.active_index];
+        active.board.surface_ships();
+        for (ship_index, cooldown) in active.cooldowns.iter_mut().enumerate() {
+            if *cooldown > 0 {
+                *cooldown -= 1;
+                self.ability_updates.entry(active.name.clone()).or_insert(vec![])
+                    .push(Message::AbilityCooldownUpdate { id: ship_index as u8, turns: *cooldown });
+            }
+        }
     }
 
     pub fn turn_time_exceeded(&self) -> bool {
         match self.last_turn_started_at {
             None => false,
-            Some(start_time) => start_time.to(time::PreciseTime::now()) > time::Duration::seconds(LIMIT_SECONDS),
+            Some(start_time) => start_time.to(time::PreciseTime::now()) > time::Duration::seconds(self.rules.turn_seconds as i64),
+        }
+    }
+
+    /// Whether the maximum number of turns set by the rules has been played
+    pub fn turn_limit_reached(&self) -> bool {
+        match self.rules.max_turns {
+            Some(max_turns) => self.is_running() && self.turn_count >= max_turns,
+            None => false,
+        }
+    }
+
+    /**
+     * Score of the player's team when the game is decided by the turn limit: remaining hit
+     * points first, then the number of ships still afloat.
+     */
+    pub fn get_score(&self, player_name: &String) -> (usize, usize) {
+        let mut score = (0, 0);
+        for participant in self.participants.iter().filter(|p| self.is_ally(&p.name, player_name)) {
+            for ship in participant.board.get_ships() {
+                score.0 += ship.health_points;
+                if !ship.is_dead() {
+                    score.1 += 1;
+                }
+            }
         }
+        return score;
+    }
+
+    /// Names of all players with the best score. A draw if they are not all allies.
+    pub fn get_leaders(&self) -> Vec<String> {
+        let best_score = self.participants.iter().map(|p| self.get_score(&p.name)).max();
+        self.participants.iter()
+            .filter(|p| Some(self.get_score(&p.name)) == best_score)
+            .map(|p| p.name.clone())
+            .collect()
+    }
+
+    /// Number of turns played so far
+    pub fn get_turn_count(&self) -> u16 {
+        self.turn_count
     }
 
     pub fn is_running(&self) -> bool {
@@ -91,14 +388,10 @@ impl Game {
     }
 
     pub fn get_active_player_afk_count(&self) -> u8 {
-        if self.player1_active { self.player1_afk_count } else { self.player2_afk_count }
+        self.participants[self.active_index].afk_count
     }
 
     pub fn dec_active_player_afk_count(&mut self) {
-        if self.player1_active {
-            self.player1_afk_count -= 1;
-        } else {
-            self.player2_afk_count -= 1;
-        }
+        self.participants[self.active_index].afk_count -= 1;
     }
 }
@@ -0,0 +1,200 @@
+use bitboard::BitBoard;
+use board::{W, H};
+use message::Message;
+use placement::FLEET_LENGTHS;
+
+/// What a player knows about a cell of an opponent's board
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum CellKnowledge {
+    /// The cell has never been seen
+    Unknown,
+    /// The cell has been seen empty, e.g. after a miss
+    Water,
+    /// An intact ship segment is in sight
+    Ship,
+    /// A damaged ship segment is in sight
+    Hit,
+    /// A ship has been destroyed here
+    Sunk,
+    /// A ship left the cell since the player's last turn, so it is still close by
+    LastSeen,
+    /// A ship left the cell before the player's last turn
+    Stale,
+}
+
+/**
+ * Model of an opponent's board, built from the server's responses and updates. Once a cell has
+ * been seen, the server reports every ship entering or leaving it, so all cells but the unknown
+ * ones reflect the current state of the board.
+ */
+#[derive(Copy, Clone, Debug)]
+pub struct OpponentBoard {
+    cells: [[CellKnowledge; H]; W],
+    destroyed: usize,
+    // lengths of the destroyed ships, in the order they were destroyed
+    sunk_lengths: [usize; 5],
+}
+
+impl OpponentBoard {
+    pub fn new() -> OpponentBoard {
+        OpponentBoard {
+            cells: [[CellKnowledge::Unknown; H]; W],
+            destroyed: 0,
+            sunk_lengths: [0; 5],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> CellKnowledge {
+        self.cells[x][y]
+    }
+
+    pub fn is_visible_at(&self, x: usize, y: usize) -> bool {
+        self.cells[x][y] != CellKnowledge::Unknown
+    }
+
+    pub fn has_ship_at(&self, x: usize, y: usize) -> bool {
+        match self.cells[x][y] {
+            CellKnowledge::Ship | CellKnowledge::Hit => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_damaged_at(&self, x: usize, y: usize) -> bool {
+        self.cells[x][y] == CellKnowledge::Hit
+    }
+
+    /// Number of ships destroyed so far
+    pub fn get_destroyed(&self) -> usize {
+        self.destroyed
+    }
+
+    /**
+     * Lengths of the ships of the standard fleet that have not been destroyed yet. The length of
+     * a destroyed ship is derived from the segments marked as sunk along with it.
+     */
+    pub fn remaining_lengths(&self) -> Vec<usize> {
+        let mut lengths = FLEET_LENGTHS.to_vec();
+        for &sunk_length in self.sunk_lengths.iter().take(self.destroyed) {
+            // fall back to the longest shorter ship if the segments were miscounted
+            let position = lengths.iter().position(|&length| length == sunk_length)
+                .or_else(|| lengths.iter().position(|&length| length < sunk_length))
+                .unwrap_or(lengths.len() - 1);
+            lengths.remove(position);
+            if lengths.is_empty() {
+                break;
+            }
+        }
+        return lengths;
+    }
+
+    /// An intact ship segment is at the given cell
+    pub fn set_ship(&mut self, x: u8, y: u8) {
+        self.cells[x as usize][y as usize] = CellKnowledge::Ship;
+    }
+
+    /// No ship is at the given cell, or none anymore
+    pub fn set_water(&mut self, x: u8, y: u8) {
+        let ref mut cell = self.cells[x as usize][y as usize];
+        *cell = match *cell {
+            CellKnowledge::Ship | CellKnowledge::Hit => CellKnowledge::LastSeen,
+            CellKnowledge::Unknown => CellKnowledge::Water,
+            // keep what we know about the cell's history
+            other => other,
+        };
+    }
+
+    /// A damaged ship segment is at the given cell
+    pub fn set_damaged(&mut self, x: u8, y: u8) {
+        self.cells[x as usize][y as usize] = CellKnowledge::Hit;
+    }
+
+    /**
+     * The ship at the given cell has been destroyed. Its other segments must have been hit before,
+     * so the damaged cells in line with the given one are marked as well.
+     */
+    pub fn set_sunk(&mut self, x: u8, y: u8) {
+        let (x, y) = (x as isize, y as isize);
+        self.cells[x as usize][y as usize] = CellKnowledge::Sunk;
+        let mut length = 1;
+        for &(dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+            let (mut cur_x, mut cur_y) = (x + dx, y + dy);
+            while cur_x >= 0 && cur_y >= 0 && cur_x < W as isize && cur_y < H as isize
+                    && self.cells[cur_x as usize][cur_y as usize] == CellKnowledge::Hit {
+                self.cells[cur_x as usize][cur_y as usize] = CellKnowledge::Sunk;
+                cur_x += dx;
+                cur_y += dy;
+                length += 1;
+            }
+        }
+        if self.destroyed < self.sunk_lengths.len() {
+            self.sunk_lengths[self.destroyed] = length;
+        }
+        self.destroyed += 1;
+    }
+
+    /// The player's turn is over: ships that left a cell before are no longer close by.
+    pub fn next_turn(&mut self) {
+        for column in self.cells.iter_mut() {
+            for cell in column.iter_mut() {
+                if *cell == CellKnowledge::LastSeen {
+                    *cell = CellKnowledge::Stale;
+                }
+            }
+        }
+    }
+
+    /**
+     * Updates the model with a message about this board. Updates naming a board are taken into
+     * account regardless of the name, so only pass those concerning this board.
+     * @return false if the message carries no information about the board
+     */
+    pub fn ingest(&mut self, msg: &Message) -> bool {
+        match *msg {
+            Message::HitResponse { x, y } |
+            Message::EnemyDamagedUpdate { x, y } |
+            Message::BoardDamagedUpdate { x, y, .. } |
+            Message::PlayerShotUpdate { x, y, hit: true, .. } => self.set_damaged(x, y),
+            Message::MissResponse { x, y } |
+            Message::EnemyInvisibleUpdate { x, y } |
+            Message::BoardInvisibleUpdate { x, y, .. } |
+            Message::PlayerShotUpdate { x, y, hit: false, .. } => self.set_water(x, y),
+            Message::EnemyVisibleUpdate { x, y } |
+            Message::BoardVisibleUpdate { x, y, .. } => self.set_ship(x, y),
+            Message::DestroyedResponse { x, y } => self.set_sunk(x, y),
+            _ => return false,
+        }
+        return true;
+    }
+
+    /// Whether a ship may currently occupy the cell
+    pub fn could_contain_ship(&self, x: usize, y: usize) -> bool {
+        match self.cells[x][y] {
+            CellKnowledge::Unknown | CellKnowledge::Ship | CellKnowledge::Hit => true,
+            _ => false,
+        }
+    }
+
+    /// All horizontal and vertical positions a ship of the given length could currently occupy
+    pub fn possible_placements(&self, length: usize) -> Vec<BitBoard> {
+        let mut placements = vec![];
+        for x in 0..W {
+            for y in 0..H {
+                if x + length <= W && (x..x + length).all(|cell_x| self.could_contain_ship(cell_x, y)) {
+                    let mut placement = BitBoard::new();
+                    for cell_x in x..x + length {
+                        placement.set(cell_x, y);
+                    }
+                    placements.push(placement);
+                }
+                if length > 1 && y + length <= H && (y..y + length).all(|cell_y| self.could_contain_ship(x, cell_y)) {
+                    let mut placement = BitBoard::new();
+                    for cell_y in y..y + length {
+                        placement.set(x, cell_y);
+                    }
+                    placements.push(placement);
+                }
+            }
+        }
+        return placements;
+    }
+}
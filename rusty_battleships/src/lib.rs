@@ -1,10 +1,20 @@
-extern crate ansi_term; // TODO: remove dependency from lib
+extern crate crypto;
 extern crate rand;
 extern crate rustc_serialize;
 extern crate time;
 
+pub mod accounts;
+pub mod bitboard;
 pub mod board;
+pub mod bot;
+pub mod external;
 pub mod game;
+pub mod knowledge;
 pub mod message;
+pub mod placement;
+pub mod rating;
+pub mod rules;
 pub mod ship;
+pub mod strategy;
+pub mod text_protocol;
 pub mod timer;
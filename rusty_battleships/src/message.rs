@@ -1,5 +1,6 @@
-use std::io::{BufReader, Error, ErrorKind, Read, Result, Write};
-use std::net::TcpStream;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use placement::PlacementError;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub enum Message {
@@ -13,6 +14,27 @@ pub enum Message {
     ChallengePlayerRequest {
         username:String
     },
+    ChallengePlayersRequest {
+        usernames:Vec<String>
+    },
+    ChallengeTeamRequest {
+        ally:String,
+        opponents:Vec<String>
+    },
+    /// Creates an account for the nickname and logs in with it
+    RegisterRequest {
+        username:String,
+        password:String,
+    },
+    PasswordLoginRequest {
+        username:String,
+        password:String,
+    },
+    /// Logs in with a token from an earlier TokenResponse instead of the password
+    TokenLoginRequest {
+        username:String,
+        token:String,
+    },
     PlaceShipsRequest {
         placement:[ShipPlacement; 5]
     },
@@ -27,6 +49,33 @@ pub enum Message {
         y:u8,
     },
     SurrenderRequest,
+    RadarRequest {
+        x:u8,
+        y:u8,
+    },
+    TorpedoRequest {
+        x:u8,
+        y:u8,
+        direction:Direction,
+    },
+    AbilityRequest {
+        id:u8,
+        direction:Direction,
+        x:u8,
+        y:u8,
+    },
+    ShootAtRequest {
+        nickname:String,
+        x:u8,
+        y:u8,
+    },
+    MoveAndShootAtRequest {
+        nickname:String,
+        id:u8,
+        direction:Direction,
+        x:u8,
+        y:u8,
+    },
     ReportErrorRequest {
         errormessage:String,
     },
@@ -46,6 +95,14 @@ pub enum Message {
         nickname:String,
     },
     GameAlreadyStartedResponse,
+    /// Successful registration or login with a password, the token can be used to log in again
+    TokenResponse {
+        token:String,
+    },
+    /// No account with this nickname and password or token
+    WrongCredentialsResponse,
+    /// The server does not admit guests, players have to register or log in with their account
+    AccountRequiredResponse,
     HitResponse {
         x:u8,
         y:u8,
@@ -59,6 +116,18 @@ pub enum Message {
         y:u8,
     },
     NotYourTurnResponse,
+    RadarResponse {
+        x:u8,
+        y:u8,
+        ship_detected:bool,
+    },
+    ReconResponse {
+        x:u8,
+        y:u8,
+    },
+    InvalidPlacementResponse {
+        errors:Vec<PlacementError>,
+    },
     InvalidRequestResponse,
 
     // Updates
@@ -77,6 +146,13 @@ pub enum Message {
     GameStartUpdate {
         nickname:String, //Opponent's name
     },
+    FreeForAllStartUpdate {
+        nicknames:Vec<String>, //Opponents' names
+    },
+    TeamGameStartUpdate {
+        ally:String,
+        opponents:Vec<String>,
+    },
     YourTurnUpdate,
     EnemyTurnUpdate,
     EnemyVisibleUpdate {
@@ -96,7 +172,7 @@ pub enum Message {
         y:u8,
     },
     GameOverUpdate {
-        victorious:bool,
+        outcome:Outcome,
         reason:Reason,
     },
     AfkWarningUpdate {
@@ -105,6 +181,63 @@ pub enum Message {
     EnemyAfkUpdate {
         strikes:u8,
     },
+    EnemyRadarUpdate {
+        x:u8,
+        y:u8,
+    },
+    EnemyTorpedoUpdate {
+        x:u8,
+        y:u8,
+        direction:Direction,
+    },
+    ChargesUpdate {
+        radar:u8,
+        torpedo:u8,
+    },
+    EnemyReconUpdate {
+        x:u8,
+        y:u8,
+    },
+    AbilityCooldownUpdate {
+        id:u8,
+        turns:u8,
+    },
+    PlayerTurnUpdate {
+        nickname:String,
+    },
+    PlayerShotUpdate {
+        shooter:String,
+        target:String,
+        x:u8,
+        y:u8,
+        hit:bool,
+    },
+    BoardVisibleUpdate {
+        nickname:String,
+        x:u8,
+        y:u8,
+    },
+    BoardInvisibleUpdate {
+        nickname:String,
+        x:u8,
+        y:u8,
+    },
+    PlayerEliminatedUpdate {
+        nickname:String,
+    },
+    EnemyDamagedUpdate {
+        x:u8,
+        y:u8,
+    },
+    BoardDamagedUpdate {
+        nickname:String,
+        x:u8,
+        y:u8,
+    },
+    /// Announcement of the server's administrator to everybody connected
+    ServerMessageUpdate {
+        message:String,
+    },
     ServerGoingDownUpdate {
         errormessage:String,
     },
@@ -130,12 +263,22 @@ fn message_type(msg: Message) -> (MessageEnvironment, MessageType) {
         Message::LoginRequest{..} |
         Message::ReadyRequest |
         Message::NotReadyRequest |
-        Message::ChallengePlayerRequest {..} => (MessageEnvironment::Lobby, MessageType::Request),
+        Message::ChallengePlayerRequest {..} |
+        Message::ChallengePlayersRequest {..} |
+        Message::ChallengeTeamRequest {..} |
+        Message::RegisterRequest {..} |
+        Message::PasswordLoginRequest {..} |
+        Message::TokenLoginRequest {..} => (MessageEnvironment::Lobby, MessageType::Request),
 
         Message::PlaceShipsRequest {..} |
         Message::ShootRequest {..} |
         Message::MoveAndShootRequest {..} |
-        Message::SurrenderRequest => (MessageEnvironment::Game, MessageType::Request),
+        Message::SurrenderRequest |
+        Message::RadarRequest {..} |
+        Message::TorpedoRequest {..} |
+        Message::AbilityRequest {..} |
+        Message::ShootAtRequest {..} |
+        Message::MoveAndShootAtRequest {..} => (MessageEnvironment::Game, MessageType::Request),
 
         Message::ReportErrorRequest{..} => (MessageEnvironment::All, MessageType::Request),
 
@@ -144,12 +287,18 @@ fn message_type(msg: Message) -> (MessageEnvironment, MessageType) {
         Message::NameTakenResponse{..} |
         Message::NoSuchPlayerResponse{..} |
         Message::NotWaitingResponse{..} |
-        Message::GameAlreadyStartedResponse => (MessageEnvironment::Lobby, MessageType::Response),
+        Message::GameAlreadyStartedResponse |
+        Message::TokenResponse{..} |
+        Message::WrongCredentialsResponse |
+        Message::AccountRequiredResponse => (MessageEnvironment::Lobby, MessageType::Response),
 
         Message::HitResponse {..} |
         Message::MissResponse {..} |
         Message::DestroyedResponse {..} |
-        Message::NotYourTurnResponse => (MessageEnvironment::Game, MessageType::Response),
+        Message::NotYourTurnResponse |
+        Message::RadarResponse {..} |
+        Message::ReconResponse {..} |
+        Message::InvalidPlacementResponse {..} => (MessageEnvironment::Game, MessageType::Response),
 
         Message::OkResponse |
         Message::InvalidRequestResponse => (MessageEnvironment::All, MessageType::Response),
@@ -159,7 +308,9 @@ fn message_type(msg: Message) -> (MessageEnvironment, MessageType) {
         Message::PlayerLeftUpdate{..} |
         Message::PlayerReadyUpdate{..} |
         Message::PlayerNotReadyUpdate{..} |
-        Message::GameStartUpdate{..} => (MessageEnvironment::Lobby, MessageType::Update),
+        Message::GameStartUpdate{..} |
+        Message::FreeForAllStartUpdate{..} |
+        Message::TeamGameStartUpdate{..} => (MessageEnvironment::Lobby, MessageType::Update),
 
         Message::YourTurnUpdate |
         Message::EnemyTurnUpdate |
@@ -169,12 +320,40 @@ fn message_type(msg: Message) -> (MessageEnvironment, MessageType) {
         Message::EnemyMissUpdate{..} |
         Message::GameOverUpdate{..} |
         Message::AfkWarningUpdate{..} |
-        Message::EnemyAfkUpdate{..} => (MessageEnvironment::Game, MessageType::Update),
+        Message::EnemyAfkUpdate{..} |
+        Message::EnemyRadarUpdate{..} |
+        Message::EnemyTorpedoUpdate{..} |
+        Message::ChargesUpdate{..} |
+        Message::EnemyReconUpdate{..} |
+        Message::AbilityCooldownUpdate{..} |
+        Message::PlayerTurnUpdate{..} |
+        Message::PlayerShotUpdate{..} |
+        Message::BoardVisibleUpdate{..} |
+        Message::BoardInvisibleUpdate{..} |
+        Message::PlayerEliminatedUpdate{..} |
+        Message::EnemyDamagedUpdate{..} |
+        Message::BoardDamagedUpdate{..} => (MessageEnvironment::Game, MessageType::Update),
 
+        Message::ServerMessageUpdate{..} |
         Message::ServerGoingDownUpdate{..} => (MessageEnvironment::All, MessageType::Update),
     }
 }
 
+/// The message with passwords and tokens hidden, for logging
+pub fn redacted(msg: &Message) -> Message {
+    let hidden = || "***".to_owned();
+    match *msg {
+        Message::RegisterRequest { ref username, .. } =>
+            Message::RegisterRequest { username: username.clone(), password: hidden() },
+        Message::PasswordLoginRequest { ref username, .. } =>
+            Message::PasswordLoginRequest { username: username.clone(), password: hidden() },
+        Message::TokenLoginRequest { ref username, .. } =>
+            Message::TokenLoginRequest { username: username.clone(), token: hidden() },
+        Message::TokenResponse { .. } => Message::TokenResponse { token: hidden() },
+        _ => msg.clone(),
+    }
+}
+
 pub fn is_fatal_error(msg: Message) -> bool {
     match msg {
         Message::ReportErrorRequest{..} |
@@ -228,26 +407,38 @@ pub enum Reason {
     Disconnected = 1,
     Surrendered = 2,
     Afk = 3,
+    TurnLimit = 4,
+    /// The server's administrator ended the game
+    EndedByServer = 5,
+}
+
+/// Result of a finished game for a single player. Encoded like the former `victorious` flag,
+/// with draws added.
+#[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
+pub enum Outcome {
+    Defeat = 0,
+    Victory = 1,
+    Draw = 2,
 }
 
-fn read_into_buffer(mut message_buffer: &mut [u8], reader: &mut BufReader<TcpStream>)
+fn read_into_buffer<R: Read>(mut message_buffer: &mut [u8], reader: &mut R)
         -> Result<()> {
     reader.read_exact(&mut message_buffer).map_err(|e| {
         if e.kind() == ErrorKind::UnexpectedEof {
-            Error::new(ErrorKind::UnexpectedEof, "TCP stream closed unexpectedly.")
+            Error::new(ErrorKind::UnexpectedEof, "Stream closed unexpectedly.")
         } else {
             e
         }
     })
 }
 
-fn extract_number(mut reader: &mut BufReader<TcpStream>) -> Result<u8> {
+fn extract_number<R: Read>(mut reader: &mut R) -> Result<u8> {
     let mut message_buffer:[u8;1] = [0;1];
     try!(read_into_buffer(&mut message_buffer, &mut reader));
     return Ok(message_buffer[0]);
 }
 
-fn extract_bool(mut reader: &mut BufReader<TcpStream>) -> Result<bool> {
+fn extract_bool<R: Read>(mut reader: &mut R) -> Result<bool> {
     match try!(extract_number(&mut reader)) {
         1 => Ok(true),
         0 => Ok(false),
@@ -255,7 +446,7 @@ fn extract_bool(mut reader: &mut BufReader<TcpStream>) -> Result<bool> {
     }
 }
 
-pub fn extract_direction(mut reader: &mut BufReader<TcpStream>) -> Result<Direction> {
+pub fn extract_direction<R: Read>(mut reader: &mut R) -> Result<Direction> {
     match try!(extract_number(&mut reader)) {
         0 => Ok(Direction::North),
         1 => Ok(Direction::East),
@@ -265,17 +456,28 @@ pub fn extract_direction(mut reader: &mut BufReader<TcpStream>) -> Result<Direct
     }
 }
 
-pub fn extract_reason(mut reader: &mut BufReader<TcpStream>) -> Result<Reason> {
+pub fn extract_reason<R: Read>(mut reader: &mut R) -> Result<Reason> {
     match try!(extract_number(&mut reader)) {
         0 => Ok(Reason::Obliterated),
         1 => Ok(Reason::Disconnected),
         2 => Ok(Reason::Surrendered),
         3 => Ok(Reason::Afk),
+        4 => Ok(Reason::TurnLimit),
+        5 => Ok(Reason::EndedByServer),
         _ => Err(Error::new(ErrorKind::InvalidData, "Invalid reason value."))
     }
 }
 
-fn extract_string(mut reader: &mut BufReader<TcpStream>, allow_space: bool)
+pub fn extract_outcome<R: Read>(mut reader: &mut R) -> Result<Outcome> {
+    match try!(extract_number(&mut reader)) {
+        0 => Ok(Outcome::Defeat),
+        1 => Ok(Outcome::Victory),
+        2 => Ok(Outcome::Draw),
+        _ => Err(Error::new(ErrorKind::InvalidData, "Invalid outcome value."))
+    }
+}
+
+fn extract_string<R: Read>(mut reader: &mut R, allow_space: bool)
         -> Result<String> {
     let strlen = try!(extract_number(&mut reader)) as usize;
     let mut string_buffer = vec![0;strlen];
@@ -292,7 +494,7 @@ fn extract_string(mut reader: &mut BufReader<TcpStream>, allow_space: bool)
     return Ok(String::from_utf8(string_buffer).unwrap());
 }
 
-fn extract_features(mut reader: &mut BufReader<TcpStream>) -> Result<Vec<String>> {
+fn extract_features<R: Read>(mut reader: &mut R) -> Result<Vec<String>> {
     let numfeatures = try!(extract_number(&mut reader));
     let mut features = Vec::new();
     for _ in 0..numfeatures {
@@ -301,7 +503,38 @@ fn extract_features(mut reader: &mut BufReader<TcpStream>) -> Result<Vec<String>
     return Ok(features);
 }
 
-fn extract_placement(mut reader: &mut BufReader<TcpStream>) -> Result<[ShipPlacement; 5]> {
+fn extract_nicknames<R: Read>(mut reader: &mut R) -> Result<Vec<String>> {
+    let numnicknames = try!(extract_number(&mut reader));
+    let mut nicknames = Vec::new();
+    for _ in 0..numnicknames {
+        nicknames.push(try!(extract_string(&mut reader, false)));
+    }
+    return Ok(nicknames);
+}
+
+fn extract_placement_errors<R: Read>(mut reader: &mut R) -> Result<Vec<PlacementError>> {
+    let numerrors = try!(extract_number(&mut reader));
+    let mut errors = Vec::new();
+    for _ in 0..numerrors {
+        errors.push(match try!(extract_number(&mut reader)) {
+            0 => PlacementError::OutOfBounds {
+                ship: try!(extract_number(&mut reader))
+            },
+            1 => PlacementError::Overlap {
+                ship: try!(extract_number(&mut reader)),
+                other: try!(extract_number(&mut reader))
+            },
+            2 => PlacementError::Touching {
+                ship: try!(extract_number(&mut reader)),
+                other: try!(extract_number(&mut reader))
+            },
+            _ => return Err(Error::new(ErrorKind::InvalidData, "Invalid placement error value."))
+        });
+    }
+    return Ok(errors);
+}
+
+fn extract_placement<R: Read>(mut reader: &mut R) -> Result<[ShipPlacement; 5]> {
     let mut placement:[ShipPlacement; 5]
             = [ShipPlacement { x: 0, y: 0, direction: Direction::North }; 5];
     for i in 0..5 {
@@ -314,7 +547,7 @@ fn extract_placement(mut reader: &mut BufReader<TcpStream>) -> Result<[ShipPlace
     return Ok(placement);
 }
 
-pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Message> {
+pub fn deserialize_message<R: Read>(mut reader: &mut R) -> Result<Message> {
     match try!(extract_number(&mut reader)) {
         000 => Ok(Message::GetFeaturesRequest),
         001 => Ok(Message::LoginRequest {
@@ -325,6 +558,25 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
         004 => Ok(Message::ChallengePlayerRequest {
             username: try!(extract_string(&mut reader, false))
         }),
+        005 => Ok(Message::ChallengePlayersRequest {
+            usernames: try!(extract_nicknames(&mut reader))
+        }),
+        006 => Ok(Message::ChallengeTeamRequest {
+            ally: try!(extract_string(&mut reader, false)),
+            opponents: try!(extract_nicknames(&mut reader))
+        }),
+        007 => Ok(Message::RegisterRequest {
+            username: try!(extract_string(&mut reader, false)),
+            password: try!(extract_string(&mut reader, true))
+        }),
+        008 => Ok(Message::PasswordLoginRequest {
+            username: try!(extract_string(&mut reader, false)),
+            password: try!(extract_string(&mut reader, true))
+        }),
+        009 => Ok(Message::TokenLoginRequest {
+            username: try!(extract_string(&mut reader, false)),
+            token: try!(extract_string(&mut reader, false))
+        }),
         010 => Ok(Message::PlaceShipsRequest {
             placement: try!(extract_placement(&mut reader))
         }),
@@ -339,6 +591,33 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
             y: try!(extract_number(&mut reader))
         }),
         013 => Ok(Message::SurrenderRequest),
+        014 => Ok(Message::RadarRequest {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        015 => Ok(Message::TorpedoRequest {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader)),
+            direction: try!(extract_direction(&mut reader))
+        }),
+        016 => Ok(Message::AbilityRequest {
+            id: try!(extract_number(&mut reader)),
+            direction: try!(extract_direction(&mut reader)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        017 => Ok(Message::ShootAtRequest {
+            nickname: try!(extract_string(&mut reader, false)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        018 => Ok(Message::MoveAndShootAtRequest {
+            nickname: try!(extract_string(&mut reader, false)),
+            id: try!(extract_number(&mut reader)),
+            direction: try!(extract_direction(&mut reader)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
         099 => Ok(Message::ReportErrorRequest {
             errormessage: try!(extract_string(&mut reader, true))
         }),
@@ -358,6 +637,11 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
             nickname: try!(extract_string(&mut reader, false))
         }),
         105 => Ok(Message::GameAlreadyStartedResponse),
+        106 => Ok(Message::TokenResponse {
+            token: try!(extract_string(&mut reader, false))
+        }),
+        107 => Ok(Message::WrongCredentialsResponse),
+        108 => Ok(Message::AccountRequiredResponse),
         111 => Ok(Message::HitResponse {
             x: try!(extract_number(&mut reader)),
             y: try!(extract_number(&mut reader))
@@ -371,6 +655,18 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
             y: try!(extract_number(&mut reader))
         }),
         114 => Ok(Message::NotYourTurnResponse),
+        115 => Ok(Message::RadarResponse {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader)),
+            ship_detected: try!(extract_bool(&mut reader))
+        }),
+        116 => Ok(Message::ReconResponse {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        117 => Ok(Message::InvalidPlacementResponse {
+            errors: try!(extract_placement_errors(&mut reader))
+        }),
         199 => Ok(Message::InvalidRequestResponse),
 
 
@@ -389,6 +685,13 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
         204 => Ok(Message::GameStartUpdate {
             nickname: try!(extract_string(&mut reader, false))
         }),
+        205 => Ok(Message::FreeForAllStartUpdate {
+            nicknames: try!(extract_nicknames(&mut reader))
+        }),
+        206 => Ok(Message::TeamGameStartUpdate {
+            ally: try!(extract_string(&mut reader, false)),
+            opponents: try!(extract_nicknames(&mut reader))
+        }),
         210 => Ok(Message::YourTurnUpdate),
         211 => Ok(Message::EnemyTurnUpdate),
         212 => Ok(Message::EnemyVisibleUpdate {
@@ -408,7 +711,7 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
             y: try!(extract_number(&mut reader))
         }),
         216 => Ok(Message::GameOverUpdate {
-            victorious: try!(extract_bool(&mut reader)),
+            outcome: try!(extract_outcome(&mut reader)),
             reason: try!(extract_reason(&mut reader))
         }),
         217 => Ok(Message::AfkWarningUpdate {
@@ -417,7 +720,63 @@ pub fn deserialize_message(mut reader: &mut BufReader<TcpStream>) -> Result<Mess
         218 => Ok(Message::EnemyAfkUpdate{
             strikes: try!(extract_number(&mut reader))
         }),
+        219 => Ok(Message::EnemyRadarUpdate {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        220 => Ok(Message::EnemyTorpedoUpdate {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader)),
+            direction: try!(extract_direction(&mut reader))
+        }),
+        221 => Ok(Message::ChargesUpdate {
+            radar: try!(extract_number(&mut reader)),
+            torpedo: try!(extract_number(&mut reader))
+        }),
+        222 => Ok(Message::EnemyReconUpdate {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        223 => Ok(Message::AbilityCooldownUpdate {
+            id: try!(extract_number(&mut reader)),
+            turns: try!(extract_number(&mut reader))
+        }),
+        224 => Ok(Message::PlayerTurnUpdate {
+            nickname: try!(extract_string(&mut reader, false))
+        }),
+        225 => Ok(Message::PlayerShotUpdate {
+            shooter: try!(extract_string(&mut reader, false)),
+            target: try!(extract_string(&mut reader, false)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader)),
+            hit: try!(extract_bool(&mut reader))
+        }),
+        226 => Ok(Message::BoardVisibleUpdate {
+            nickname: try!(extract_string(&mut reader, false)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        227 => Ok(Message::BoardInvisibleUpdate {
+            nickname: try!(extract_string(&mut reader, false)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        228 => Ok(Message::PlayerEliminatedUpdate {
+            nickname: try!(extract_string(&mut reader, false))
+        }),
+        229 => Ok(Message::EnemyDamagedUpdate {
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
+        230 => Ok(Message::BoardDamagedUpdate {
+            nickname: try!(extract_string(&mut reader, false)),
+            x: try!(extract_number(&mut reader)),
+            y: try!(extract_number(&mut reader))
+        }),
 
+        254 => Ok(Message::ServerMessageUpdate {
+            message: try!(extract_string(&mut reader, true))
+        }),
         255 => Ok(Message::ServerGoingDownUpdate{
             errormessage: try!(extract_string(&mut reader, true))
         }),
@@ -432,6 +791,14 @@ fn append_string(mut buf: &mut Vec<u8>, string: String) {
     write!(&mut buf, "{}", string).unwrap();
 }
 
+fn append_strings(mut buf: &mut Vec<u8>, strings: Vec<String>) {
+    assert!(strings.len() <= 255, "Too many strings.");
+    buf.push(strings.len() as u8);
+    for string in strings {
+        append_string(&mut buf, string);
+    }
+}
+
 pub fn serialize_message(msg: Message) -> Vec<u8> {
     let mut msgbuf = Vec::new();
     match msg {
@@ -446,6 +813,30 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             msgbuf.push(004);
             append_string(&mut msgbuf, username);
         },
+        Message::ChallengePlayersRequest { usernames } => {
+            msgbuf.push(005);
+            append_strings(&mut msgbuf, usernames);
+        },
+        Message::ChallengeTeamRequest { ally, opponents } => {
+            msgbuf.push(006);
+            append_string(&mut msgbuf, ally);
+            append_strings(&mut msgbuf, opponents);
+        },
+        Message::RegisterRequest { username, password } => {
+            msgbuf.push(007);
+            append_string(&mut msgbuf, username);
+            append_string(&mut msgbuf, password);
+        },
+        Message::PasswordLoginRequest { username, password } => {
+            msgbuf.push(008);
+            append_string(&mut msgbuf, username);
+            append_string(&mut msgbuf, password);
+        },
+        Message::TokenLoginRequest { username, token } => {
+            msgbuf.push(009);
+            append_string(&mut msgbuf, username);
+            append_string(&mut msgbuf, token);
+        },
         Message::PlaceShipsRequest { placement } => {
             msgbuf.push(010);
             for ship_placement in &placement {
@@ -467,6 +858,38 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             msgbuf.push(y);
         },
         Message::SurrenderRequest => msgbuf.push(013),
+        Message::RadarRequest { x, y } => {
+            msgbuf.push(014);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::TorpedoRequest { x, y, direction } => {
+            msgbuf.push(015);
+            msgbuf.push(x);
+            msgbuf.push(y);
+            msgbuf.push(direction as u8);
+        },
+        Message::AbilityRequest { id, direction, x, y } => {
+            msgbuf.push(016);
+            msgbuf.push(id);
+            msgbuf.push(direction as u8);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::ShootAtRequest { nickname, x, y } => {
+            msgbuf.push(017);
+            append_string(&mut msgbuf, nickname);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::MoveAndShootAtRequest { nickname, id, direction, x, y } => {
+            msgbuf.push(018);
+            append_string(&mut msgbuf, nickname);
+            msgbuf.push(id);
+            msgbuf.push(direction as u8);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
         Message::ReportErrorRequest { errormessage } => {
             msgbuf.push(099);
             append_string(&mut msgbuf, errormessage);
@@ -495,6 +918,12 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             append_string(&mut msgbuf, nickname);
         },
         Message::GameAlreadyStartedResponse => msgbuf.push(105),
+        Message::TokenResponse { token } => {
+            msgbuf.push(106);
+            append_string(&mut msgbuf, token);
+        },
+        Message::WrongCredentialsResponse => msgbuf.push(107),
+        Message::AccountRequiredResponse => msgbuf.push(108),
         Message::HitResponse { x, y } => {
             msgbuf.push(111);
             msgbuf.push(x);
@@ -511,6 +940,39 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             msgbuf.push(y);
         },
         Message::NotYourTurnResponse => msgbuf.push(114),
+        Message::RadarResponse { x, y, ship_detected } => {
+            msgbuf.push(115);
+            msgbuf.push(x);
+            msgbuf.push(y);
+            msgbuf.push(ship_detected as u8);
+        },
+        Message::ReconResponse { x, y } => {
+            msgbuf.push(116);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::InvalidPlacementResponse { errors } => {
+            msgbuf.push(117);
+            msgbuf.push(errors.len() as u8);
+            for error in errors {
+                match error {
+                    PlacementError::OutOfBounds { ship } => {
+                        msgbuf.push(0);
+                        msgbuf.push(ship);
+                    },
+                    PlacementError::Overlap { ship, other } => {
+                        msgbuf.push(1);
+                        msgbuf.push(ship);
+                        msgbuf.push(other);
+                    },
+                    PlacementError::Touching { ship, other } => {
+                        msgbuf.push(2);
+                        msgbuf.push(ship);
+                        msgbuf.push(other);
+                    },
+                }
+            }
+        },
         Message::InvalidRequestResponse => msgbuf.push(199),
 
 
@@ -534,6 +996,15 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             msgbuf.push(204);
             append_string(&mut msgbuf, nickname);
         },
+        Message::FreeForAllStartUpdate { nicknames } => {
+            msgbuf.push(205);
+            append_strings(&mut msgbuf, nicknames);
+        },
+        Message::TeamGameStartUpdate { ally, opponents } => {
+            msgbuf.push(206);
+            append_string(&mut msgbuf, ally);
+            append_strings(&mut msgbuf, opponents);
+        },
 
         Message::YourTurnUpdate => msgbuf.push(210),
         Message::EnemyTurnUpdate => msgbuf.push(211),
@@ -558,9 +1029,9 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             msgbuf.push(y);
         },
 
-        Message::GameOverUpdate { victorious, reason } => {
+        Message::GameOverUpdate { outcome, reason } => {
             msgbuf.push(216);
-            msgbuf.push(victorious as u8);
+            msgbuf.push(outcome as u8);
             msgbuf.push(reason as u8);
         },
         Message::AfkWarningUpdate { strikes } => {
@@ -571,7 +1042,76 @@ pub fn serialize_message(msg: Message) -> Vec<u8> {
             msgbuf.push(218);
             msgbuf.push(strikes);
         },
+        Message::EnemyRadarUpdate { x, y } => {
+            msgbuf.push(219);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::EnemyTorpedoUpdate { x, y, direction } => {
+            msgbuf.push(220);
+            msgbuf.push(x);
+            msgbuf.push(y);
+            msgbuf.push(direction as u8);
+        },
+        Message::ChargesUpdate { radar, torpedo } => {
+            msgbuf.push(221);
+            msgbuf.push(radar);
+            msgbuf.push(torpedo);
+        },
+        Message::EnemyReconUpdate { x, y } => {
+            msgbuf.push(222);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::AbilityCooldownUpdate { id, turns } => {
+            msgbuf.push(223);
+            msgbuf.push(id);
+            msgbuf.push(turns);
+        },
+        Message::PlayerTurnUpdate { nickname } => {
+            msgbuf.push(224);
+            append_string(&mut msgbuf, nickname);
+        },
+        Message::PlayerShotUpdate { shooter, target, x, y, hit } => {
+            msgbuf.push(225);
+            append_string(&mut msgbuf, shooter);
+            append_string(&mut msgbuf, target);
+            msgbuf.push(x);
+            msgbuf.push(y);
+            msgbuf.push(hit as u8);
+        },
+        Message::BoardVisibleUpdate { nickname, x, y } => {
+            msgbuf.push(226);
+            append_string(&mut msgbuf, nickname);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::BoardInvisibleUpdate { nickname, x, y } => {
+            msgbuf.push(227);
+            append_string(&mut msgbuf, nickname);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::PlayerEliminatedUpdate { nickname } => {
+            msgbuf.push(228);
+            append_string(&mut msgbuf, nickname);
+        },
+        Message::EnemyDamagedUpdate { x, y } => {
+            msgbuf.push(229);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
+        Message::BoardDamagedUpdate { nickname, x, y } => {
+            msgbuf.push(230);
+            append_string(&mut msgbuf, nickname);
+            msgbuf.push(x);
+            msgbuf.push(y);
+        },
 
+        Message::ServerMessageUpdate { message } => {
+            msgbuf.push(254);
+            append_string(&mut msgbuf, message);
+        },
         Message::ServerGoingDownUpdate { errormessage } => {
             msgbuf.push(255);
             append_string(&mut msgbuf, errormessage);
@@ -0,0 +1,211 @@
+use board::{Board, W, H};
+use message::{Direction, ShipPlacement};
+use rules::Rules;
+use ship::{Ship, class_ability};
+
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
+
+/// Lengths of the ships of the standard fleet, in fleet order (see `ship::class_ability`)
+pub const FLEET_LENGTHS: [usize; 5] = [5, 4, 3, 2, 2];
+
+/// Number of times the generator starts over when it runs out of space for the remaining ships
+const MAX_ATTEMPTS: usize = 100;
+
+/// Preference of the random placement generator for where ships go
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Bias {
+    /// Every position is equally likely
+    Uniform,
+    /// Ships are preferably placed along the edges of the board
+    EdgeHugging,
+    /// Ships are preferably placed far away from each other
+    SpreadOut,
+}
+
+/// Problem with a fleet placement. Ships are identified by their index in the fleet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum PlacementError {
+    /// The ship does not fit on the board
+    OutOfBounds { ship: u8 },
+    /// Both ships occupy the same cell
+    Overlap { ship: u8, other: u8 },
+    /// Both ships are adjacent, although the rules demand water between ships
+    Touching { ship: u8, other: u8 },
+}
+
+impl PlacementError {
+    /// Indices of all ships involved in the problem
+    pub fn ships(&self) -> Vec<u8> {
+        match *self {
+            PlacementError::OutOfBounds { ship } => vec![ship],
+            PlacementError::Overlap { ship, other } |
+            PlacementError::Touching { ship, other } => vec![ship, other],
+        }
+    }
+}
+
+fn cells(ship: &Ship) -> Vec<(isize, isize)> {
+    (0..ship.length).map(|i| Board::get_ship_dest_coords(ship, i)).collect()
+}
+
+fn is_adjacent((x1, y1): (isize, isize), (x2, y2): (isize, isize)) -> bool {
+    (x1 - x2).abs() <= 1 && (y1 - y2).abs() <= 1
+}
+
+/**
+ * Checks a fleet placement against the board size and the rules. Unlike `Board::try_create`,
+ * which stops at the first collision, all problems are returned. An empty result means the
+ * placement is valid.
+ */
+pub fn validate(ships: &[Ship], rules: &Rules) -> Vec<PlacementError> {
+    let mut errors = vec![];
+    let ship_cells: Vec<Vec<(isize, isize)>> = ships.iter().map(cells).collect();
+
+    for (index, cells) in ship_cells.iter().enumerate() {
+        if cells.iter().any(|&(x, y)| x < 0 || y < 0 || x >= W as isize || y >= H as isize) {
+            errors.push(PlacementError::OutOfBounds { ship: index as u8 });
+        }
+    }
+
+    for first in 0..ships.len() {
+        for second in first + 1..ships.len() {
+            let overlap = ship_cells[first].iter().any(|cell| ship_cells[second].contains(cell));
+            let touching = ship_cells[first].iter().any(|&cell| {
+                ship_cells[second].iter().any(|&other| is_adjacent(cell, other))
+            });
+            if overlap {
+                errors.push(PlacementError::Overlap { ship: first as u8, other: second as u8 });
+            } else if touching && rules.no_touching {
+                errors.push(PlacementError::Touching { ship: first as u8, other: second as u8 });
+            }
+        }
+    }
+
+    return errors;
+}
+
+/// The standard fleet as placed with a `PlaceShipsRequest`
+pub fn fleet_from_placement(placement: &[ShipPlacement; 5], rules: &Rules) -> Vec<Ship> {
+    let mut ships = vec![];
+    for (index, (&ship_placement, &length_and_hp)) in placement.iter().zip(FLEET_LENGTHS.iter()).enumerate() {
+        let ShipPlacement { x, y, direction } = ship_placement;
+        let ship = Ship {
+            x: x as isize,
+            y: y as isize,
+            direction: direction,
+            length: length_and_hp,
+            health_points: length_and_hp,
+            damage: 0,
+            ability: if rules.ship_abilities { class_ability(index) } else { None },
+            submerged: false,
+        };
+        ships.push(ship);
+    }
+    return ships;
+}
+
+/**
+ * Places the standard fleet at random on the standard board, obeying the rules. With a seed, the
+ * same placement is generated every time.
+ */
+pub fn random_fleet(rules: &Rules, bias: Bias, seed: Option<usize>) -> Vec<Ship> {
+    let placement = match seed {
+        Some(seed) => {
+            let seed: &[_] = &[seed];
+            let mut rng: StdRng = SeedableRng::from_seed(seed);
+            random_placement(&mut rng, W, H, &FLEET_LENGTHS, rules, bias)
+        },
+        None => random_placement(&mut thread_rng(), W, H, &FLEET_LENGTHS, rules, bias),
+    };
+    return placement.expect("The standard fleet always fits on the standard board.");
+}
+
+/**
+ * Places ships of the given lengths at random on a board of the given size, obeying the rules.
+ * Ships are placed one after another in a free spot; if there is none left, the generator starts
+ * over. Returns None if no valid placement was found.
+ */
+pub fn random_placement<R: Rng>(rng: &mut R, width: usize, height: usize, lengths: &[usize],
+                                rules: &Rules, bias: Bias) -> Option<Vec<Ship>> {
+    for _ in 0..MAX_ATTEMPTS {
+        if let Some(ships) = try_random_placement(rng, width, height, lengths, rules, bias) {
+            return Some(ships);
+        }
+    }
+    return None;
+}
+
+fn try_random_placement<R: Rng>(rng: &mut R, width: usize, height: usize, lengths: &[usize],
+                                rules: &Rules, bias: Bias) -> Option<Vec<Ship>> {
+    let mut ships = vec![];
+    let mut occupied = vec![];
+
+    for (index, &length) in lengths.iter().enumerate() {
+        let mut candidates = vec![];
+        let mut total_weight = 0;
+        for x in 0..width {
+            for y in 0..height {
+                for &direction in &[Direction::North, Direction::East, Direction::South, Direction::West] {
+                    let ship = Ship {
+                        x: x as isize,
+                        y: y as isize,
+                        length: length,
+                        direction: direction,
+                        health_points: length,
+                        damage: 0,
+                        ability: if rules.ship_abilities { class_ability(index) } else { None },
+                        submerged: false,
+                    };
+                    let ship_cells = cells(&ship);
+                    let fits = ship_cells.iter().all(|&(cell_x, cell_y)| {
+                        cell_x >= 0 && cell_y >= 0 && cell_x < width as isize && cell_y < height as isize
+                            && !occupied.iter().any(|&other| {
+                                (cell_x, cell_y) == other
+                                    || (rules.no_touching && is_adjacent((cell_x, cell_y), other))
+                            })
+                    });
+                    if fits {
+                        let weight = bias_weight(&ship_cells, &occupied, width, height, bias);
+                        total_weight += weight;
+                        candidates.push((ship, ship_cells, weight));
+                    }
+                }
+            }
+        }
+
+        if candidates.is_empty() {
+            return None;
+        }
+        let mut choice = rng.gen_range(0, total_weight);
+        for (ship, ship_cells, weight) in candidates {
+            if choice < weight {
+                ships.push(ship);
+                occupied.extend(ship_cells);
+                break;
+            }
+            choice -= weight;
+        }
+    }
+    return Some(ships);
+}
+
+/// How likely a candidate position is chosen, relative to the other candidates
+fn bias_weight(ship_cells: &[(isize, isize)], occupied: &[(isize, isize)], width: usize, height: usize,
+               bias: Bias) -> usize {
+    match bias {
+        Bias::Uniform => 1,
+        Bias::EdgeHugging => {
+            let edge_cells = ship_cells.iter().filter(|&&(x, y)| {
+                x == 0 || y == 0 || x == width as isize - 1 || y == height as isize - 1
+            }).count();
+            1 + 4 * edge_cells
+        },
+        Bias::SpreadOut => {
+            // distance to the closest ship placed so far, counting diagonal steps as one
+            let distance = ship_cells.iter().flat_map(|&(x1, y1)| {
+                occupied.iter().map(move |&(x2, y2)| ::std::cmp::max((x1 - x2).abs(), (y1 - y2).abs()))
+            }).min().unwrap_or(1) as usize;
+            distance * distance
+        },
+    }
+}
@@ -0,0 +1,87 @@
+/// Rating of a player who won as many games as they lost against players rated the same
+pub const BASE_ELO: f64 = 1500.0;
+
+/// Factor for the standard error of a rating to give a 95% confidence interval
+const CONFIDENCE_Z: f64 = 1.96;
+
+const MAX_ITERATIONS: usize = 10000;
+const PRECISION: f64 = 1e-9;
+
+/// A game between two players, given by their index
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GameResult {
+    pub first: usize,
+    pub second: usize,
+    /// Score of the first player: 1 for a win, 0.5 for a draw and 0 for a loss
+    pub score: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rating {
+    pub elo: f64,
+    /// Half the width of the 95% confidence interval around the rating
+    pub error: f64,
+}
+
+/// Probability of a player with the first strength beating one with the second
+fn expected_score(strength: f64, opponent_strength: f64) -> f64 {
+    strength / (strength + opponent_strength)
+}
+
+/**
+ * Elo ratings of the given number of players, fitted to all games at once so that the order of the
+ * games does not matter (maximum likelihood in the Bradley-Terry model). Every player is assumed to
+ * have drawn one more game against a player rated `BASE_ELO`, which anchors the scale and keeps the
+ * ratings of players who won or lost every game finite.
+ */
+pub fn elo_ratings(player_count: usize, games: &[GameResult]) -> Vec<Rating> {
+    // strength 1 corresponds to BASE_ELO, a factor of 10 to 400 points
+    let mut strengths = vec![1.0; player_count];
+    let mut scores = vec![0.5; player_count];
+    for game in games {
+        scores[game.first] += game.score;
+        scores[game.second] += 1.0 - game.score;
+    }
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut next = vec![0.0; player_count];
+        let mut change: f64 = 0.0;
+        for player in 0..player_count {
+            let mut denominator = 1.0 / (strengths[player] + 1.0);
+            for game in games {
+                if game.first == player {
+                    denominator += 1.0 / (strengths[player] + strengths[game.second]);
+                } else if game.second == player {
+                    denominator += 1.0 / (strengths[player] + strengths[game.first]);
+                }
+            }
+            next[player] = scores[player] / denominator;
+            change = change.max((next[player] / strengths[player]).ln().abs());
+        }
+        strengths = next;
+        if change < PRECISION {
+            break;
+        }
+    }
+
+    return (0..player_count).map(|player| {
+        // Fisher information of the player's log-strength
+        let mut information = expected_score(strengths[player], 1.0) * expected_score(1.0, strengths[player]);
+        for game in games {
+            let opponent = if game.first == player {
+                game.second
+            } else if game.second == player {
+                game.first
+            } else {
+                continue;
+            };
+            let p = expected_score(strengths[player], strengths[opponent]);
+            information += p * (1.0 - p);
+        }
+        let scale = 400.0 / 10f64.ln();
+        Rating {
+            elo: BASE_ELO + scale * strengths[player].ln(),
+            error: CONFIDENCE_Z * scale / information.sqrt(),
+        }
+    }).collect();
+}
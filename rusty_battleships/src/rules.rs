@@ -0,0 +1,46 @@
+/// Rule set a game is played with. The default rule set corresponds to the classic game: single
+/// shots only, no special weapons or ship abilities.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Rules {
+    /// Number of radar sweeps each player may use per game
+    pub radar_charges: u8,
+    /// Number of torpedoes each player may fire per game
+    pub torpedo_charges: u8,
+    /// Whether ship classes carry their special ability (see `ship::class_ability`)
+    pub ship_abilities: bool,
+    /// Number of own turns until a ship ability can be used again
+    pub ability_cooldown: u8,
+    /// Number of turns after which the game is decided by score, if any
+    pub max_turns: Option<u16>,
+    /// Whether ships must not be placed next to each other, not even diagonally
+    pub no_touching: bool,
+    /// Whether shots at an already damaged segment are misses. Otherwise they count as hits, but
+    /// deal no further damage.
+    pub damaged_segments_miss: bool,
+    /// Number of seconds a player has for each turn before it is skipped
+    pub turn_seconds: u16,
+    /// Number of skipped turns after which a player loses the game
+    pub afk_strikes: u8,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            radar_charges: 0,
+            torpedo_charges: 0,
+            ship_abilities: false,
+            ability_cooldown: 3,
+            max_turns: None,
+            no_touching: false,
+            damaged_segments_miss: false,
+            turn_seconds: 60,
+            afk_strikes: 3,
+        }
+    }
+}
+
+impl Rules {
+    pub fn special_weapons_enabled(&self) -> bool {
+        self.radar_charges > 0 || self.torpedo_charges > 0
+    }
+}
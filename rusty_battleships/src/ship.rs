@@ -1,5 +1,27 @@
 use message::Direction;
 
+/// Special abilities of ship classes
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Ability {
+    /// Reveal a 3x3 area of the opponent's board (aircraft carrier)
+    Reconnaissance,
+    /// Become immune to hits during the opponent's next turn (submarine)
+    Submerge,
+    /// Move two cells instead of one (destroyer)
+    DoubleMove,
+}
+
+/// Ability of the ship class at the given fleet index. The fleet consists of aircraft carrier,
+/// battleship, cruiser, destroyer and submarine, in this order.
+pub fn class_ability(index: usize) -> Option<Ability> {
+    match index {
+        0 => Some(Ability::Reconnaissance),
+        3 => Some(Ability::DoubleMove),
+        4 => Some(Ability::Submerge),
+        _ => None,
+    }
+}
+
 #[derive(Copy, Clone,Debug, Eq, PartialEq, Hash)]
 pub struct Ship {
     pub x: isize,
@@ -7,6 +29,10 @@ pub struct Ship {
     pub length: usize,
     pub direction: Direction,
     pub health_points: usize,
+    /// Bit i is set if segment i, counted from (x, y) in the ship's direction, has been hit
+    pub damage: u16,
+    pub ability: Option<Ability>,
+    pub submerged: bool,
 }
 
 impl Ship {
@@ -15,14 +41,12 @@ impl Ship {
         if self.health_points == 0 {
             return false;
         }
-        println!("Moving ship len {} from current position {}:{} to {:?}", self.length, self.x, self.y, direction);
         match direction {
             Direction::North => self.y -= 1,
             Direction::East => self.x += 1,
             Direction::South => self.y += 1,
             Direction::West => self.x -= 1,
         }
-        println!("Finished moving ship len {}, new ship pos {}:{}", self.length, self.x, self.y);
         return true;
     }
 
@@ -30,6 +54,23 @@ impl Ship {
         self.health_points == 0
     }
 
+    pub fn is_segment_damaged(&self, segment: usize) -> bool {
+        self.damage & (1 << segment) != 0
+    }
+
+    /**
+     * Damages a segment of the ship. A segment can only be damaged once.
+     * @return false if the segment had already been damaged
+     */
+    pub fn damage_segment(&mut self, segment: usize) -> bool {
+        if self.is_segment_damaged(segment) {
+            return false;
+        }
+        self.damage |= 1 << segment;
+        self.health_points -= 1;
+        return true;
+    }
+
     pub fn is_horizontal(&self) -> bool {
         self.direction == Direction::East || self.direction == Direction::West
     }
@@ -0,0 +1,498 @@
+use bitboard::BitBoard;
+use board::{Board, HitResult, W, H};
+use game::Game;
+use knowledge::{CellKnowledge, OpponentBoard};
+use message::{Direction, Message};
+use placement::{self, Bias, FLEET_LENGTHS};
+use rules::Rules;
+use ship::Ship;
+
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
+
+/// Names of the built-in strategies, as accepted by `from_name`
+pub const STRATEGY_NAMES: [&'static str; 4] = ["random", "hunt-target", "probability", "tracker"];
+
+/// Weight of a possible ship position for every damaged segment it covers
+const HIT_WEIGHT: u32 = 50;
+
+/// Weight of the cells next to one a ship just left, as one of them is covered by that ship
+const TRACK_WEIGHT: f64 = 20.0;
+
+/// Share of the tracking weight that remains per turn, as the ship may have moved on since
+const TRACK_DECAY: f64 = 0.5;
+
+const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+/// A turn chosen by a strategy: an optional ship movement, followed by a shot
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Turn {
+    /// Index of the ship to move and the direction to move it in
+    pub movement: Option<(u8, Direction)>,
+    pub x: u8,
+    pub y: u8,
+}
+
+impl Turn {
+    pub fn shot(x: usize, y: usize) -> Turn {
+        Turn { movement: None, x: x as u8, y: y as u8 }
+    }
+
+    /// The request to send to the server for this turn
+    pub fn to_request(&self) -> Message {
+        match self.movement {
+            Some((id, direction)) => Message::MoveAndShootRequest { id: id, direction: direction, x: self.x, y: self.y },
+            None => Message::ShootRequest { x: self.x, y: self.y },
+        }
+    }
+
+    /// The request to send to the server for this turn in a game with more than one opponent
+    pub fn to_request_at(&self, nickname: &String) -> Message {
+        match self.movement {
+            Some((id, direction)) => Message::MoveAndShootAtRequest {
+                nickname: nickname.clone(), id: id, direction: direction, x: self.x, y: self.y },
+            None => Message::ShootAtRequest { nickname: nickname.clone(), x: self.x, y: self.y },
+        }
+    }
+}
+
+/**
+ * A computer player. The strategy only decides what to do; keeping track of the boards is left to
+ * whoever drives it, be it a network client or `play_local`.
+ */
+pub trait Strategy {
+    /// Places the fleet at the start of a game
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship>;
+
+    /**
+     * Chooses the next turn given the own board, which tells what the opponent has seen of it,
+     * and what is known about the opponent's board
+     */
+    fn choose_turn(&mut self, my_board: &Board, opponent: &OpponentBoard, rules: &Rules) -> Turn;
+
+    /// Informs the strategy about a response or update the player received
+    fn handle_message(&mut self, _msg: &Message) {}
+}
+
+/// Creates the built-in strategy with the given name. With a seed, it plays the same way every time.
+pub fn from_name(name: &str, seed: Option<usize>) -> Option<Box<Strategy>> {
+    match name {
+        "random" => Some(Box::new(RandomStrategy::new(seed))),
+        "hunt-target" => Some(Box::new(HuntTargetStrategy::new(seed))),
+        "probability" => Some(Box::new(ProbabilityStrategy::new(seed))),
+        "tracker" => Some(Box::new(TrackerStrategy::new(seed))),
+        _ => None,
+    }
+}
+
+fn create_rng(seed: Option<usize>) -> StdRng {
+    let seed: &[_] = &[seed.unwrap_or_else(|| thread_rng().gen())];
+    SeedableRng::from_seed(seed)
+}
+
+fn random_fleet(rng: &mut StdRng, rules: &Rules, bias: Bias) -> Vec<Ship> {
+    placement::random_placement(rng, W, H, &FLEET_LENGTHS, rules, bias)
+        .expect("The standard fleet always fits on the standard board.")
+}
+
+/// Whether shooting at the cell may hit a ship that has not been hit there before
+fn is_target(opponent: &OpponentBoard, x: usize, y: usize) -> bool {
+    match opponent.get(x, y) {
+        CellKnowledge::Unknown | CellKnowledge::Ship => true,
+        _ => false,
+    }
+}
+
+/// All cells matching the predicate, in column order
+fn cells_where<F: Fn(usize, usize) -> bool>(predicate: F) -> Vec<(usize, usize)> {
+    let mut cells = vec![];
+    for x in 0..W {
+        for y in 0..H {
+            if predicate(x, y) {
+                cells.push((x, y));
+            }
+        }
+    }
+    return cells;
+}
+
+/// Shoots at a random cell among the given ones, or anywhere if there is none
+fn shoot_any(rng: &mut StdRng, cells: &[(usize, usize)]) -> Turn {
+    match rng.choose(cells) {
+        Some(&(x, y)) => Turn::shot(x, y),
+        None => Turn::shot(rng.gen_range(0, W), rng.gen_range(0, H)),
+    }
+}
+
+/// Shoots at random cells that have not been shot at yet, never moving any ships.
+pub struct RandomStrategy {
+    rng: StdRng,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: Option<usize>) -> RandomStrategy {
+        RandomStrategy { rng: create_rng(seed) }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        random_fleet(&mut self.rng, rules, Bias::Uniform)
+    }
+
+    fn choose_turn(&mut self, _my_board: &Board, opponent: &OpponentBoard, _rules: &Rules) -> Turn {
+        let targets = cells_where(|x, y| is_target(opponent, x, y));
+        return shoot_any(&mut self.rng, &targets);
+    }
+}
+
+/**
+ * Hunts for ships on a checkerboard pattern, as every ship covers at least one of its cells. Once
+ * a ship has been hit, the cells next to the damaged segments are targeted until it is destroyed.
+ */
+pub struct HuntTargetStrategy {
+    rng: StdRng,
+}
+
+impl HuntTargetStrategy {
+    pub fn new(seed: Option<usize>) -> HuntTargetStrategy {
+        HuntTargetStrategy { rng: create_rng(seed) }
+    }
+}
+
+impl Strategy for HuntTargetStrategy {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        random_fleet(&mut self.rng, rules, Bias::SpreadOut)
+    }
+
+    fn choose_turn(&mut self, _my_board: &Board, opponent: &OpponentBoard, _rules: &Rules) -> Turn {
+        // a ship in sight is a sure hit
+        let visible_ships = cells_where(|x, y| opponent.get(x, y) == CellKnowledge::Ship);
+        if !visible_ships.is_empty() {
+            return shoot_any(&mut self.rng, &visible_ships);
+        }
+
+        // target mode: next to a damaged segment, preferably in line with another one
+        let mut in_line = vec![];
+        let mut adjacent = vec![];
+        for (x, y) in cells_where(|x, y| opponent.is_damaged_at(x, y)) {
+            for &(dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+                let (next_x, next_y) = (x as isize + dx, y as isize + dy);
+                let (prev_x, prev_y) = (x as isize - dx, y as isize - dy);
+                if !in_bounds(next_x, next_y) || !is_target(opponent, next_x as usize, next_y as usize) {
+                    continue;
+                }
+                if in_bounds(prev_x, prev_y) && opponent.is_damaged_at(prev_x as usize, prev_y as usize) {
+                    in_line.push((next_x as usize, next_y as usize));
+                } else {
+                    adjacent.push((next_x as usize, next_y as usize));
+                }
+            }
+        }
+        if !in_line.is_empty() {
+            return shoot_any(&mut self.rng, &in_line);
+        }
+        if !adjacent.is_empty() {
+            return shoot_any(&mut self.rng, &adjacent);
+        }
+
+        // hunt mode
+        let parity = cells_where(|x, y| (x + y) % 2 == 0 && is_target(opponent, x, y));
+        if !parity.is_empty() {
+            return shoot_any(&mut self.rng, &parity);
+        }
+        let targets = cells_where(|x, y| is_target(opponent, x, y));
+        return shoot_any(&mut self.rng, &targets);
+    }
+}
+
+fn in_bounds(x: isize, y: isize) -> bool {
+    x >= 0 && y >= 0 && x < W as isize && y < H as isize
+}
+
+/**
+ * Shoots at the cell covered by the most positions the remaining ships could currently occupy.
+ * Positions covering damaged segments are weighted higher, so damaged ships are finished off.
+ */
+pub struct ProbabilityStrategy {
+    rng: StdRng,
+}
+
+impl ProbabilityStrategy {
+    pub fn new(seed: Option<usize>) -> ProbabilityStrategy {
+        ProbabilityStrategy { rng: create_rng(seed) }
+    }
+}
+
+/// Weighted number of possible positions of the remaining ships covering each cell
+pub fn ship_density(opponent: &OpponentBoard) -> [[u32; H]; W] {
+    let mut damaged = BitBoard::new();
+    for (x, y) in cells_where(|x, y| opponent.is_damaged_at(x, y)) {
+        damaged.set(x, y);
+    }
+
+    let mut density = [[0; H]; W];
+    for length in opponent.remaining_lengths() {
+        for position in opponent.possible_placements(length) {
+            let weight = 1 + HIT_WEIGHT * (position & damaged).count() as u32;
+            for (x, y) in position.cells() {
+                density[x][y] += weight;
+            }
+        }
+    }
+    return density;
+}
+
+impl Strategy for ProbabilityStrategy {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        random_fleet(&mut self.rng, rules, Bias::EdgeHugging)
+    }
+
+    fn choose_turn(&mut self, _my_board: &Board, opponent: &OpponentBoard, _rules: &Rules) -> Turn {
+        let visible_ships = cells_where(|x, y| opponent.get(x, y) == CellKnowledge::Ship);
+        if !visible_ships.is_empty() {
+            return shoot_any(&mut self.rng, &visible_ships);
+        }
+
+        let density = ship_density(opponent);
+        let best = cells_where(|x, y| is_target(opponent, x, y)).into_iter().map(|(x, y)| density[x][y]).max();
+        let best_cells = cells_where(|x, y| is_target(opponent, x, y) && Some(density[x][y]) == best);
+        return shoot_any(&mut self.rng, &best_cells);
+    }
+}
+
+/**
+ * Like `ProbabilityStrategy`, but follows ships that moved out of sight: the cells next to those
+ * a ship just left are weighted higher, and this weight spreads and fades as the ship may move
+ * further. Damaged ships of its own are moved away from the cells the opponent has seen.
+ */
+pub struct TrackerStrategy {
+    rng: StdRng,
+    // additional weight of the cells ships that were lost from sight could have moved to
+    tracks: [[f64; H]; W],
+}
+
+impl TrackerStrategy {
+    pub fn new(seed: Option<usize>) -> TrackerStrategy {
+        TrackerStrategy { rng: create_rng(seed), tracks: [[0.0; H]; W] }
+    }
+
+    /// Takes the opponent's last turn into account
+    fn update_tracks(&mut self, opponent: &OpponentBoard) {
+        let mut tracks = [[0.0; H]; W];
+        for x in 0..W {
+            for y in 0..H {
+                if !opponent.could_contain_ship(x, y) {
+                    continue;
+                }
+                // a ship that was near a cell may have moved there in the meantime
+                let mut weight = self.tracks[x][y];
+                for (neighbour_x, neighbour_y) in neighbours(x, y) {
+                    weight = weight.max(self.tracks[neighbour_x][neighbour_y]);
+                    if opponent.get(neighbour_x, neighbour_y) == CellKnowledge::LastSeen {
+                        weight = weight.max(TRACK_WEIGHT / TRACK_DECAY);
+                    }
+                }
+                tracks[x][y] = weight * TRACK_DECAY;
+            }
+        }
+        self.tracks = tracks;
+    }
+}
+
+fn neighbours(x: usize, y: usize) -> Vec<(usize, usize)> {
+    let mut cells = vec![];
+    for &(dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+        let (neighbour_x, neighbour_y) = (x as isize + dx, y as isize + dy);
+        if in_bounds(neighbour_x, neighbour_y) {
+            cells.push((neighbour_x as usize, neighbour_y as usize));
+        }
+    }
+    return cells;
+}
+
+/**
+ * How exposed the intact segments of a ship are: a segment in a cell the opponent has seen is a
+ * sure hit, one in a cell next to a damaged segment the opponent has seen is likely to be hit.
+ */
+fn exposure(ship: &Ship, board: &Board, known_hits: &BitBoard) -> u32 {
+    let mut exposure = 0;
+    for segment in 0..ship.length {
+        if ship.is_segment_damaged(segment) {
+            continue;
+        }
+        let (x, y) = Board::get_ship_dest_coords(ship, segment);
+        let (x, y) = (x as usize, y as usize);
+        if board.is_visible_at(x, y) {
+            exposure += 2;
+        } else if neighbours(x, y).into_iter().any(|(neighbour_x, neighbour_y)| known_hits.get(neighbour_x, neighbour_y)) {
+            exposure += 1;
+        }
+    }
+    return exposure;
+}
+
+/**
+ * Chooses the move that takes a damaged ship furthest out of the areas the opponent knows to have
+ * been hit, if there is any move that makes a ship less exposed.
+ */
+pub fn evasive_move(my_board: &Board) -> Option<(u8, Direction)> {
+    let mut known_hits = BitBoard::new();
+    for (x, y) in cells_where(|x, y| my_board.is_visible_at(x, y) && my_board.is_hit_at(x, y)) {
+        known_hits.set(x, y);
+    }
+
+    let mut best_move = None;
+    let mut best_gain = 0;
+    for (index, ship) in my_board.get_ships().iter().enumerate() {
+        if ship.is_dead() || ship.damage == 0 {
+            continue;
+        }
+        let current_exposure = exposure(ship, my_board, &known_hits);
+        for &direction in DIRECTIONS.iter() {
+            let mut moved = my_board.clone();
+            if !moved.move_ship(index as u8, direction) {
+                continue;
+            }
+            let moved_exposure = exposure(&moved.get_ships()[index], my_board, &known_hits);
+            if moved_exposure < current_exposure && current_exposure - moved_exposure > best_gain {
+                best_gain = current_exposure - moved_exposure;
+                best_move = Some((index as u8, direction));
+            }
+        }
+    }
+    return best_move;
+}
+
+impl Strategy for TrackerStrategy {
+    fn place_fleet(&mut self, rules: &Rules) -> Vec<Ship> {
+        random_fleet(&mut self.rng, rules, Bias::EdgeHugging)
+    }
+
+    fn choose_turn(&mut self, my_board: &Board, opponent: &OpponentBoard, _rules: &Rules) -> Turn {
+        self.update_tracks(opponent);
+        let movement = evasive_move(my_board);
+
+        let visible_ships = cells_where(|x, y| opponent.get(x, y) == CellKnowledge::Ship);
+        let mut turn = if !visible_ships.is_empty() {
+            shoot_any(&mut self.rng, &visible_ships)
+        } else {
+            let density = ship_density(opponent);
+            let tracks = self.tracks;
+            let score = |x: usize, y: usize| density[x][y] as f64 * (1.0 + tracks[x][y]);
+            let targets = cells_where(|x, y| is_target(opponent, x, y));
+            let best = targets.iter().map(|&(x, y)| score(x, y)).fold(0.0, f64::max);
+            let best_cells: Vec<(usize, usize)> = targets.into_iter().filter(|&(x, y)| score(x, y) >= best).collect();
+            shoot_any(&mut self.rng, &best_cells)
+        };
+        turn.movement = movement;
+        return turn;
+    }
+}
+
+/// Result of a game played by `play_local`
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LocalOutcome {
+    /// Index of the winning strategy (0 for the first one passed), None for a draw
+    pub winner: Option<usize>,
+    /// Index of the strategy that had the first turn
+    pub first: usize,
+    /// Number of turns played
+    pub turns: usize,
+    /// Number of turns in which a ship was moved, by either strategy
+    pub moves: usize,
+    /// Whether the game was lost because of a ship placement or turn the rules do not allow
+    pub forfeited: bool,
+}
+
+/**
+ * Plays a two-player game between two strategies directly on the boards of a `Game`, without
+ * networking. Both strategies receive the same responses and updates as from the server. The game
+ * is a draw once `turn_limit` turns have been played.
+ */
+pub fn play_local(first_strategy: &mut Strategy, second_strategy: &mut Strategy, rules: &Rules,
+                  turn_limit: usize) -> LocalOutcome {
+    play(first_strategy, second_strategy, rules, turn_limit, None)
+}
+
+/// Like `play_local`, but the strategy with the given index has the first turn instead of a random one
+pub fn play_local_starting(first_strategy: &mut Strategy, second_strategy: &mut Strategy, rules: &Rules,
+                           turn_limit: usize, first: usize) -> LocalOutcome {
+    play(first_strategy, second_strategy, rules, turn_limit, Some(first))
+}
+
+fn play(first_strategy: &mut Strategy, second_strategy: &mut Strategy, rules: &Rules, turn_limit: usize,
+        first_turn: Option<usize>) -> LocalOutcome {
+    let strategies: [&mut Strategy; 2] = [first_strategy, second_strategy];
+    let names = [String::from("player1"), String::from("player2")];
+
+    let mut boards = vec![];
+    for index in 0..2 {
+        let fleet = strategies[index].place_fleet(rules);
+        if fleet.len() != FLEET_LENGTHS.len() || !placement::validate(&fleet, rules).is_empty() {
+            return LocalOutcome { winner: Some(1 - index), first: 0, turns: 0, moves: 0, forfeited: true };
+        }
+        boards.push(Board::try_create(fleet, true).expect("Validated fleets create a board."));
+    }
+    let second_board = boards.pop().unwrap();
+    let first_board = boards.pop().unwrap();
+    let mut game = Game::new(first_board, second_board, names[0].clone(), names[1].clone(), *rules);
+    if let Some(index) = first_turn {
+        game.set_active_player(&names[index]);
+    }
+    game.start();
+
+    let mut knowledge = [OpponentBoard::new(), OpponentBoard::new()];
+    let index_of = |name: &String| if *name == names[0] { 0 } else { 1 };
+    let first = index_of(&game.get_active_player());
+    let mut turns = 0;
+    let mut moves = 0;
+
+    while turns < turn_limit {
+        let me = index_of(&game.get_active_player());
+        let opponent = 1 - me;
+        let turn = strategies[me].choose_turn(game.get_board(&names[me]), &knowledge[me], rules);
+        let fleet_size = game.get_board(&names[me]).get_ships().len();
+        turns += 1;
+
+        if turn.x as usize >= W || turn.y as usize >= H {
+            return LocalOutcome { winner: Some(opponent), first: first, turns: turns, moves: moves, forfeited: true };
+        }
+        if let Some((id, direction)) = turn.movement {
+            if id as usize >= fleet_size || !game.get_board(&names[me]).move_ship(id, direction) {
+                return LocalOutcome { winner: Some(opponent), first: first, turns: turns, moves: moves, forfeited: true };
+            }
+            moves += 1;
+        }
+        let my_updates = game.get_board(&names[me]).pop_updates();
+
+        let (x, y) = (turn.x, turn.y);
+        let (response, enemy_update) = match game.get_board(&names[opponent]).shoot(x as usize, y as usize, rules) {
+            HitResult::Hit => (Message::HitResponse { x: x, y: y }, Message::EnemyHitUpdate { x: x, y: y }),
+            HitResult::Miss => (Message::MissResponse { x: x, y: y }, Message::EnemyMissUpdate { x: x, y: y }),
+            HitResult::Destroyed => (Message::DestroyedResponse { x: x, y: y }, Message::EnemyHitUpdate { x: x, y: y }),
+        };
+        let target_updates = game.get_board(&names[opponent]).pop_updates();
+
+        // as the server does, the response comes first, followed by the updates
+        for msg in Some(response).iter().chain(target_updates.iter()) {
+            knowledge[me].ingest(msg);
+            strategies[me].handle_message(msg);
+        }
+        for msg in my_updates.iter().chain(Some(enemy_update).iter()) {
+            knowledge[opponent].ingest(msg);
+            strategies[opponent].handle_message(msg);
+        }
+        knowledge[me].next_turn();
+
+        if game.is_decided() {
+            return LocalOutcome { winner: Some(me), first: first, turns: turns, moves: moves, forfeited: false };
+        }
+        game.switch_turns();
+        if game.turn_limit_reached() {
+            let leaders = game.get_leaders();
+            let winner = if leaders.len() == 1 { Some(index_of(&leaders[0])) } else { None };
+            return LocalOutcome { winner: winner, first: first, turns: turns, moves: moves, forfeited: false };
+        }
+    }
+    return LocalOutcome { winner: None, first: first, turns: turns, moves: moves, forfeited: false };
+}
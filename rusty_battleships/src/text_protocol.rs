@@ -0,0 +1,125 @@
+use std::io::{Error, ErrorKind, Result};
+
+use message::{Direction, Message, ShipPlacement};
+use rules::Rules;
+use strategy::Turn;
+
+fn invalid(line: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid line: {}", line))
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::North => "N",
+        Direction::East => "E",
+        Direction::South => "S",
+        Direction::West => "W",
+    }
+}
+
+fn parse_direction(word: &str) -> Option<Direction> {
+    match word {
+        "N" => Some(Direction::North),
+        "E" => Some(Direction::East),
+        "S" => Some(Direction::South),
+        "W" => Some(Direction::West),
+        _ => None,
+    }
+}
+
+/// The line announcing the rules the bot has to obey, e.g. `rules no-touching turn-limit 100`
+pub fn rules_line(rules: &Rules) -> String {
+    let mut line = String::from("rules");
+    if rules.no_touching {
+        line.push_str(" no-touching");
+    }
+    if rules.damaged_segments_miss {
+        line.push_str(" damaged-segments-miss");
+    }
+    if let Some(max_turns) = rules.max_turns {
+        line.push_str(&format!(" turn-limit {}", max_turns));
+    }
+    return line;
+}
+
+/// The line telling the bot about a message from the server, if it is one the bot needs to know
+pub fn to_line(msg: &Message) -> Option<String> {
+    let (event, x, y) = match *msg {
+        Message::HitResponse { x, y } => ("hit", x, y),
+        Message::MissResponse { x, y } => ("miss", x, y),
+        Message::DestroyedResponse { x, y } => ("destroyed", x, y),
+        Message::EnemyHitUpdate { x, y } => ("enemy hit", x, y),
+        Message::EnemyMissUpdate { x, y } => ("enemy miss", x, y),
+        Message::EnemyVisibleUpdate { x, y } => ("visible", x, y),
+        Message::EnemyInvisibleUpdate { x, y } => ("invisible", x, y),
+        Message::EnemyDamagedUpdate { x, y } => ("damaged", x, y),
+        _ => return None,
+    };
+    return Some(format!("{} {} {}", event, x, y));
+}
+
+/// Parses a coordinate pair from two words
+fn parse_coordinates(words: &[&str]) -> Option<(u8, u8)> {
+    if words.len() != 2 {
+        return None;
+    }
+    match (words[0].parse(), words[1].parse()) {
+        (Ok(x), Ok(y)) => Some((x, y)),
+        _ => None,
+    }
+}
+
+/**
+ * Parses the bot's fleet, e.g. `place 0 0 E 0 2 E 0 4 E 0 6 E 0 8 E`: the position and direction
+ * of each ship of the standard fleet in fleet order.
+ */
+pub fn parse_placement(line: &str) -> Result<[ShipPlacement; 5]> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.len() != 16 || words[0] != "place" {
+        return Err(invalid(line));
+    }
+    let mut placement = [ShipPlacement { x: 0, y: 0, direction: Direction::North }; 5];
+    for (index, ship) in words[1..].chunks(3).enumerate() {
+        let (x, y) = try!(parse_coordinates(&ship[..2]).ok_or(invalid(line)));
+        let direction = try!(parse_direction(ship[2]).ok_or(invalid(line)));
+        placement[index] = ShipPlacement { x: x, y: y, direction: direction };
+    }
+    return Ok(placement);
+}
+
+/// Parses the bot's turn, either `shoot 3 4` or `move 2 N shoot 3 4` to move a ship first
+pub fn parse_turn(line: &str) -> Result<Turn> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (movement, shot) = match words.first() {
+        Some(&"shoot") => (None, &words[..]),
+        Some(&"move") if words.len() > 3 => {
+            let id = try!(words[1].parse().map_err(|_| invalid(line)));
+            let direction = try!(parse_direction(words[2]).ok_or(invalid(line)));
+            (Some((id, direction)), &words[3..])
+        },
+        _ => return Err(invalid(line)),
+    };
+    if shot[0] != "shoot" {
+        return Err(invalid(line));
+    }
+    let (x, y) = try!(parse_coordinates(&shot[1..]).ok_or(invalid(line)));
+    return Ok(Turn { movement: movement, x: x, y: y });
+}
+
+/// The line the bot sends for a turn, the counterpart of `parse_turn`
+pub fn turn_line(turn: &Turn) -> String {
+    let shot = format!("shoot {} {}", turn.x, turn.y);
+    match turn.movement {
+        Some((id, direction)) => format!("move {} {} {}", id, direction_name(direction), shot),
+        None => shot,
+    }
+}
+
+/// The line the bot sends for its fleet, the counterpart of `parse_placement`
+pub fn placement_line(placement: &[ShipPlacement; 5]) -> String {
+    let mut line = String::from("place");
+    for ship in placement.iter() {
+        line.push_str(&format!(" {} {} {}", ship.x, ship.y, direction_name(ship.direction)));
+    }
+    return line;
+}
@@ -0,0 +1,100 @@
+//! Tests for the registered player accounts and the file they are kept in.
+
+extern crate rusty_battleships;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process;
+
+use rusty_battleships::accounts::{Accounts, MAX_TOKENS};
+
+/// A directory of its own for every test, as they run in parallel.
+fn directory(test: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("rusty_battleships_{}_{}", test, process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn open(directory: &PathBuf) -> Accounts {
+    Accounts::open(directory.join("accounts.json").to_str().unwrap()).unwrap()
+}
+
+#[test]
+fn passwords_are_checked() {
+    let directory = directory("passwords");
+    let mut accounts = open(&directory);
+    let eve = "Eve".to_owned();
+    assert!(accounts.register(&eve, "correct horse").unwrap().is_some());
+    assert!(accounts.exists(&eve));
+    assert!(accounts.log_in_with_password(&eve, "correct horse").unwrap().is_some());
+    assert!(accounts.log_in_with_password(&eve, "wrong horse").unwrap().is_none());
+    assert!(accounts.log_in_with_password(&"Bob".to_owned(), "correct horse").unwrap().is_none());
+    // the nickname stays reserved
+    assert!(accounts.register(&eve, "another").unwrap().is_none());
+    assert!(accounts.log_in_with_password(&eve, "another").unwrap().is_none());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn old_tokens_expire() {
+    let directory = directory("tokens");
+    let mut accounts = open(&directory);
+    let eve = "Eve".to_owned();
+    let mut tokens = vec![accounts.register(&eve, "secret").unwrap().unwrap()];
+    for _ in 0..MAX_TOKENS {
+        tokens.push(accounts.log_in_with_password(&eve, "secret").unwrap().unwrap());
+    }
+    assert!(!accounts.log_in_with_token(&eve, &tokens[0]));
+    for token in &tokens[1..] {
+        assert!(accounts.log_in_with_token(&eve, token));
+    }
+    assert!(!accounts.log_in_with_token(&eve, "0123456789abcdef0123456789abcdef"));
+    assert!(!accounts.log_in_with_token(&"Bob".to_owned(), &tokens[1]));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn accounts_survive_reopening() {
+    let directory = directory("reopening");
+    let eve = "Eve".to_owned();
+    let token = open(&directory).register(&eve, "secret").unwrap().unwrap();
+
+    let mut accounts = open(&directory);
+    assert!(accounts.exists(&eve));
+    assert!(accounts.log_in_with_token(&eve, &token));
+    assert!(accounts.log_in_with_password(&eve, "secret").unwrap().is_some());
+    // neither the password nor the token are stored as they are
+    let mut text = String::new();
+    File::open(directory.join("accounts.json")).unwrap().read_to_string(&mut text).unwrap();
+    assert!(!text.contains("secret") && !text.contains(&token));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn nothing_changes_if_the_file_cannot_be_written() {
+    let directory = directory("unwritable");
+    let mut accounts = open(&directory);
+    let eve = "Eve".to_owned();
+    let token = accounts.register(&eve, "secret").unwrap().unwrap();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(accounts.register(&"Bob".to_owned(), "secret").is_err());
+    assert!(!accounts.exists(&"Bob".to_owned()));
+    assert!(accounts.log_in_with_password(&eve, "secret").is_err());
+    // the token that could not be saved was taken back, the old one is still valid
+    assert!(accounts.log_in_with_token(&eve, &token));
+}
+
+#[test]
+fn invalid_files_are_rejected() {
+    let directory = directory("invalid");
+    for text in &["", "[]", "{\"accounts\": []}", "{\"accounts\": {\"Eve\": {\"tokens\": []}}}",
+                  "{\"accounts\": {\"Eve\": {\"password\": \"x\", \"tokens\": [1]}}}"] {
+        File::create(directory.join("accounts.json")).unwrap().write_all(text.as_bytes()).unwrap();
+        assert!(Accounts::open(directory.join("accounts.json").to_str().unwrap()).is_err(), "{:?} was accepted", text);
+    }
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,388 @@
+//! Property tests for `Board`, `Ship` and `Game`: random fleets play random moves and shots,
+//! and the invariants are checked after every step.
+
+extern crate rand;
+extern crate rusty_battleships;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use rusty_battleships::board::{Board, HitResult, W, H};
+use rusty_battleships::game::Game;
+use rusty_battleships::message::{Direction, Message, ShipPlacement};
+use rusty_battleships::placement::{self, Bias};
+use rusty_battleships::rules::Rules;
+use rusty_battleships::ship::Ship;
+
+const SEEDS: usize = 200;
+const STEPS: usize = 100;
+
+const DIRECTIONS: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+
+fn rng(seed: usize) -> StdRng {
+    let seed: &[_] = &[seed];
+    SeedableRng::from_seed(seed)
+}
+
+fn random_board(seed: usize) -> Board {
+    let rules = Rules { ship_abilities: true, ..Rules::default() };
+    let fleet = placement::random_fleet(&rules, Bias::Uniform, Some(seed));
+    Board::try_create(fleet, true).expect("Random fleets are valid.")
+}
+
+/**
+ * Board with the ships at known places: the carrier from 0:0 to 4:0, the battleship from 0:2 to
+ * 3:2, the cruiser from 9:0 to 9:2, the destroyer from 5:5 to 6:5 and the submarine from 0:9 to 1:9
+ */
+fn fixed_board() -> Board {
+    let ship = |x, y, direction| ShipPlacement { x: x, y: y, direction: direction };
+    let placement = [ship(0, 0, Direction::East), ship(0, 2, Direction::East), ship(9, 0, Direction::South),
+                     ship(5, 5, Direction::East), ship(0, 9, Direction::East)];
+    let rules = Rules { ship_abilities: true, ..Rules::default() };
+    Board::try_create(placement::fleet_from_placement(&placement, &rules), true).expect("The fixed fleet is valid.")
+}
+
+fn ship_cells(ship: &Ship) -> Vec<(usize, usize)> {
+    (0..ship.length).map(|i| {
+        let (x, y) = Board::get_ship_dest_coords(ship, i);
+        (x as usize, y as usize)
+    }).collect()
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Cell {
+    Water,
+    Ship,
+    Damaged,
+}
+
+fn cells(board: &Board) -> Vec<Vec<Cell>> {
+    (0..W).map(|x| (0..H).map(|y| match board.get_ship_index_at(x, y) {
+        None => Cell::Water,
+        Some(_) if board.is_hit_at(x, y) => Cell::Damaged,
+        Some(_) => Cell::Ship,
+    }).collect()).collect()
+}
+
+fn visibility(board: &Board) -> Vec<Vec<bool>> {
+    (0..W).map(|x| (0..H).map(|y| board.is_visible_at(x, y)).collect()).collect()
+}
+
+/// Performs a random action on the board, as both players could during a game
+fn random_action(board: &mut Board, rng: &mut StdRng) {
+    let ship_index = rng.gen_range(0, board.get_ships().len()) as u8;
+    let direction = DIRECTIONS[rng.gen_range(0, 4)];
+    let (x, y) = (rng.gen_range(0, W), rng.gen_range(0, H));
+    match rng.gen_range(0, 8) {
+        0 | 1 => {
+            let ships_before = board.get_ships().clone();
+            if !board.move_ship(ship_index, direction) {
+                assert_eq!(*board.get_ships(), ships_before, "Failed move changed the fleet");
+            }
+        },
+        2 => {
+            let ships_before = board.get_ships().clone();
+            if !board.double_move_ship(ship_index, direction) {
+                assert_eq!(*board.get_ships(), ships_before, "Failed double move changed the fleet");
+            }
+        },
+        3 => { board.torpedo(x, y, direction, &Rules::default()); },
+        4 => { board.reveal_area(x, y); },
+        5 => { board.submerge_ship(ship_index); },
+        6 => board.surface_ships(),
+        _ => { board.hit(x, y); },
+    }
+}
+
+fn check_board(board: &Board) {
+    if let Err(message) = board.check_invariants() {
+        panic!("{}", message);
+    }
+
+    let ships = board.get_ships();
+    let mut covered = vec![];
+    for (ship_index, ship) in ships.iter().enumerate() {
+        if ship.is_dead() {
+            continue;
+        }
+        let cells = ship_cells(ship);
+        let hits = cells.iter().filter(|&&(x, y)| board.is_hit_at(x, y)).count();
+        assert_eq!(ship.health_points, ship.length - hits, "HP of ship {} do not match its hits", ship_index);
+        for &(x, y) in &cells {
+            assert!(!covered.contains(&(x, y)), "Ships overlap at {}:{}", x, y);
+            assert_eq!(board.get_ship_index_at(x, y), Some(ship_index as u8));
+            covered.push((x, y));
+        }
+    }
+    assert_eq!(board.is_dead(), ships.iter().all(|ship| ship.is_dead()));
+    assert_eq!(board.is_dead(), covered.is_empty());
+}
+
+#[test]
+fn random_placements_are_valid() {
+    for seed in 0..SEEDS {
+        let mut rng = rng(seed);
+        for &bias in &[Bias::Uniform, Bias::EdgeHugging, Bias::SpreadOut] {
+            for &no_touching in &[false, true] {
+                let rules = Rules { no_touching: no_touching, ..Rules::default() };
+                let fleet = placement::random_placement(&mut rng, W, H, &placement::FLEET_LENGTHS, &rules, bias)
+                    .expect("The standard fleet always fits.");
+                assert!(placement::validate(&fleet, &rules).is_empty());
+                check_board(&Board::try_create(fleet, false).expect("Valid placements create a board."));
+            }
+        }
+    }
+}
+
+#[test]
+fn random_actions_keep_invariants() {
+    for seed in 0..SEEDS {
+        let mut rng = rng(seed);
+        let mut board = random_board(seed);
+        for _ in 0..STEPS {
+            let cells_before = cells(&board);
+            let visible_before = visibility(&board);
+
+            random_action(&mut board, &mut rng);
+            check_board(&board);
+
+            // visibility never gets lost
+            let visible_after = visibility(&board);
+            for x in 0..W {
+                for y in 0..H {
+                    assert!(!visible_before[x][y] || visible_after[x][y], "{}:{} became invisible", x, y);
+                }
+            }
+
+            // updates report exactly the visible cells a ship or a damaged segment entered or left
+            let cells_after = cells(&board);
+            let mut expected = vec![];
+            for x in 0..W {
+                for y in 0..H {
+                    if visible_after[x][y] && cells_before[x][y] != cells_after[x][y] {
+                        expected.push((x as u8, y as u8, cells_after[x][y]));
+                    }
+                }
+            }
+            let updates: Vec<(u8, u8, Cell)> = board.pop_updates().into_iter().map(|update| match update {
+                Message::EnemyVisibleUpdate { x, y } => (x, y, Cell::Ship),
+                Message::EnemyInvisibleUpdate { x, y } => (x, y, Cell::Water),
+                Message::EnemyDamagedUpdate { x, y } => (x, y, Cell::Damaged),
+                _ => panic!("Unexpected update {:?}", update),
+            }).collect();
+            assert_eq!(updates, expected);
+        }
+    }
+}
+
+#[test]
+fn overlapping_or_outside_ships_are_rejected() {
+    let ship = |x, y, direction| Ship {
+        x: x,
+        y: y,
+        length: 3,
+        direction: direction,
+        health_points: 3,
+        damage: 0,
+        ability: None,
+        submerged: false,
+    };
+    assert!(Board::try_create(vec![ship(0, 0, Direction::East), ship(1, 1, Direction::North)], false).is_none());
+    assert!(Board::try_create(vec![ship(8, 0, Direction::East)], false).is_none());
+    assert!(Board::try_create(vec![ship(0, 0, Direction::East), ship(0, 1, Direction::East)], false).is_some());
+}
+
+#[test]
+fn hitting_a_segment_twice_deals_no_further_damage() {
+    for seed in 0..SEEDS {
+        for &damaged_segments_miss in &[false, true] {
+            let rules = Rules { damaged_segments_miss: damaged_segments_miss, ..Rules::default() };
+            let mut board = random_board(seed);
+            let destroyer = board.get_ships()[3];
+            let (x, y) = ship_cells(&destroyer)[0];
+            assert_eq!(board.shoot(x, y, &rules), HitResult::Hit);
+            let expected = if damaged_segments_miss { HitResult::Miss } else { HitResult::Hit };
+            assert_eq!(board.shoot(x, y, &rules), expected);
+            assert_eq!(board.get_ships()[3].health_points, 1);
+            assert_eq!(board.get_ships()[3].damage, 0b01);
+        }
+    }
+}
+
+#[test]
+fn damaged_segments_move_along_with_the_ship() {
+    for seed in 0..SEEDS {
+        let mut board = random_board(seed);
+        let cruiser = board.get_ships()[2];
+        let (x, y) = ship_cells(&cruiser)[0];
+        board.hit(x, y);
+
+        let direction = match DIRECTIONS.iter().find(|&&direction| board.clone().move_ship(2, direction)) {
+            Some(&direction) => direction,
+            None => continue,
+        };
+        assert!(board.move_ship(2, direction));
+        let (moved_x, moved_y) = ship_cells(&board.get_ships()[2])[0];
+        assert!(board.is_hit_at(moved_x, moved_y));
+        assert!(board.get_ships()[2].is_segment_damaged(0));
+        assert_eq!(board.hit(moved_x, moved_y), HitResult::Hit);
+        assert_eq!(board.get_ships()[2].health_points, 2);
+        check_board(&board);
+    }
+}
+
+#[test]
+fn destroyed_ships_cannot_move() {
+    let mut ship = Ship {
+        x: 5,
+        y: 5,
+        length: 2,
+        direction: Direction::South,
+        health_points: 0,
+        damage: 0b11,
+        ability: None,
+        submerged: false,
+    };
+    assert!(ship.is_dead());
+    assert!(!ship.move_me(Direction::North));
+    assert_eq!((ship.x, ship.y), (5, 5));
+    assert!(!ship.is_horizontal() && !ship.is_reverse());
+}
+
+#[test]
+fn turns_only_go_to_living_opponents() {
+    for seed in 0..SEEDS {
+        let mut rng = rng(seed);
+        let player_count = 2 + seed % 3;
+        let players = (0..player_count).map(|i| (format!("player{}", i), random_board(seed * 4 + i))).collect();
+        let mut game = if player_count == 2 {
+            let mut players: Vec<(String, Board)> = players;
+            let (name2, board2) = players.pop().unwrap();
+            let (name1, board1) = players.pop().unwrap();
+            Game::new(board1, board2, name1, name2, Rules::default())
+        } else {
+            Game::new_free_for_all(players, Rules::default())
+        };
+        game.start();
+
+        while !game.is_decided() {
+            let active = game.get_active_player();
+            assert!(!game.is_eliminated(&active), "Eliminated player {} is on turn", active);
+            let opponents: Vec<String> = game.get_living_players().into_iter().filter(|p| *p != active).collect();
+            let target = rng.choose(&opponents).unwrap().clone();
+            let (x, y) = (rng.gen_range(0, W), rng.gen_range(0, H));
+            game.get_board(&target).hit(x, y);
+            check_board(game.get_board(&target));
+            if !game.is_decided() {
+                game.switch_turns();
+                assert!(game.get_active_player() != active || game.get_living_players().len() == 1);
+            }
+        }
+        assert_eq!(game.get_living_players().len(), 1);
+    }
+}
+
+#[test]
+fn torpedoes_explode_at_the_first_ship_in_their_way() {
+    let rules = Rules::default();
+    let mut board = fixed_board();
+    assert_eq!(board.torpedo(9, 5, Direction::West, &rules), (HitResult::Hit, 6, 5));
+    assert_eq!(Board::torpedo_path(9, 5, Direction::West, 6, 5), vec![(9, 5), (8, 5), (7, 5)]);
+    for x in 6..W {
+        assert!(board.is_visible_at(x, 5));
+    }
+    // the torpedo does not go on behind the ship it hit
+    assert!(!board.is_visible_at(5, 5));
+    assert_eq!(board.get_ships()[3].health_points, 1);
+
+    // in a free column, it explodes in the last cell before leaving the board
+    assert_eq!(board.torpedo(7, 9, Direction::North, &rules), (HitResult::Miss, 7, 0));
+    assert_eq!(Board::torpedo_path(7, 9, Direction::North, 7, 0), (1..H).rev().map(|y| (7, y as u8)).collect::<Vec<_>>());
+    assert!((0..H).all(|y| board.is_visible_at(7, y)));
+
+    // fired from a ship, it explodes right away
+    assert_eq!(board.torpedo(2, 0, Direction::East, &rules), (HitResult::Hit, 2, 0));
+    assert!(Board::torpedo_path(2, 0, Direction::East, 2, 0).is_empty());
+    assert!(!board.is_visible_at(3, 0));
+    check_board(&board);
+}
+
+#[test]
+fn radar_sweeps_cover_the_area_around_their_center_within_the_board() {
+    let board = fixed_board();
+    // in the corners, only the cells on the board are swept
+    assert!(board.radar_sweep(0, 0));
+    assert!(board.radar_sweep(0, 9));
+    assert!(!board.radar_sweep(9, 9));
+    // at the edge, the cruiser's end at 9:2 is found from one row below only
+    assert!(board.radar_sweep(9, 3));
+    assert!(board.radar_sweep(8, 3));
+    assert!(!board.radar_sweep(9, 4));
+    // ships right outside the area are not found
+    assert!(board.radar_sweep(7, 6));
+    assert!(!board.radar_sweep(7, 7));
+    assert!(!board.radar_sweep(5, 3));
+    // sweeps reveal nothing
+    assert!(visibility(&board).iter().all(|column| column.iter().all(|&visible| !visible)));
+}
+
+#[test]
+fn reconnaissance_reveals_what_every_cell_of_the_area_holds() {
+    let mut board = fixed_board();
+    board.hit(1, 0);
+    assert_eq!(board.reveal_area(1, 1), vec![
+        Message::EnemyVisibleUpdate { x: 0, y: 0 },
+        Message::EnemyInvisibleUpdate { x: 0, y: 1 },
+        Message::EnemyVisibleUpdate { x: 0, y: 2 },
+        Message::EnemyDamagedUpdate { x: 1, y: 0 },
+        Message::EnemyInvisibleUpdate { x: 1, y: 1 },
+        Message::EnemyVisibleUpdate { x: 1, y: 2 },
+        Message::EnemyVisibleUpdate { x: 2, y: 0 },
+        Message::EnemyInvisibleUpdate { x: 2, y: 1 },
+        Message::EnemyVisibleUpdate { x: 2, y: 2 },
+    ]);
+    assert!((0..3).all(|x| (0..3).all(|y| board.is_visible_at(x, y))));
+    // in a corner, only the cells on the board are revealed
+    assert_eq!(board.reveal_area(9, 9), vec![
+        Message::EnemyInvisibleUpdate { x: 8, y: 8 },
+        Message::EnemyInvisibleUpdate { x: 8, y: 9 },
+        Message::EnemyInvisibleUpdate { x: 9, y: 8 },
+        Message::EnemyInvisibleUpdate { x: 9, y: 9 },
+    ]);
+}
+
+#[test]
+fn submerged_ships_cannot_be_hit_until_they_surface() {
+    let mut board = fixed_board();
+    assert!(board.submerge_ship(4));
+    assert_eq!(board.hit(0, 9), HitResult::Miss);
+    assert_eq!(board.get_ships()[4].health_points, 2);
+    board.surface_ships();
+    assert!(!board.get_ships()[4].submerged);
+    assert_eq!(board.hit(0, 9), HitResult::Hit);
+
+    // destroyed ships do not dive
+    board.hit(5, 5);
+    board.hit(6, 5);
+    assert!(!board.submerge_ship(3));
+    check_board(&board);
+}
+
+#[test]
+fn double_moves_take_two_cells_or_none() {
+    let mut board = fixed_board();
+    assert!(board.double_move_ship(3, Direction::North));
+    assert_eq!((board.get_ships()[3].x, board.get_ships()[3].y), (5, 3));
+    assert_eq!(board.get_ship_index_at(6, 3), Some(3));
+    assert_eq!(board.get_ship_index_at(5, 5), None);
+
+    // the battleship has room for one step north, but not for a second one next to the carrier
+    let ships_before = board.get_ships().clone();
+    assert!(!board.double_move_ship(1, Direction::North));
+    assert_eq!(*board.get_ships(), ships_before);
+    assert_eq!(board.get_ship_index_at(0, 2), Some(1));
+    assert_eq!(board.get_ship_index_at(0, 1), None);
+    // neither may the submarine leave the board with its first step
+    assert!(!board.double_move_ship(4, Direction::South));
+    assert_eq!(*board.get_ships(), ships_before);
+    check_board(&board);
+}
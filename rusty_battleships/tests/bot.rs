@@ -0,0 +1,93 @@
+//! Tests for `Bot`: the requests it answers the server's messages with, without networking.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::bot::Bot;
+use rusty_battleships::message::Message;
+use rusty_battleships::strategy;
+
+fn bot(challenge: Option<String>) -> Bot {
+    let strategy = strategy::from_name("random", Some(0)).expect("Built-in strategies can be created by name.");
+    Bot::new("Bot".to_owned(), strategy, challenge)
+}
+
+fn reply(bot: &mut Bot, msg: Message) -> Vec<Message> {
+    bot.handle_message(msg).expect("The bot keeps playing.")
+}
+
+/// Logs the bot in, after which it is ready or has challenged its opponent
+fn log_in(bot: &mut Bot) -> Vec<Message> {
+    assert_eq!(bot.connect(), Message::GetFeaturesRequest);
+    assert_eq!(reply(bot, Message::FeaturesResponse { features: vec![] }),
+               vec![Message::LoginRequest { username: "Bot".to_owned() }]);
+    return reply(bot, Message::OkResponse);
+}
+
+/// A bot in a running two-player game against Ann, waiting for its turn
+fn playing_bot() -> Bot {
+    let mut bot = bot(None);
+    assert_eq!(log_in(&mut bot), vec![Message::ReadyRequest]);
+    assert!(reply(&mut bot, Message::OkResponse).is_empty());
+    let requests = reply(&mut bot, Message::GameStartUpdate { nickname: "Ann".to_owned() });
+    match requests.first() {
+        Some(&Message::PlaceShipsRequest { .. }) if requests.len() == 1 => {},
+        _ => panic!("Expected a placement, got {:?}", requests),
+    }
+    assert!(reply(&mut bot, Message::OkResponse).is_empty());
+    assert!(reply(&mut bot, Message::EnemyTurnUpdate).is_empty());
+    return bot;
+}
+
+fn is_turn(requests: &Vec<Message>) -> bool {
+    match requests.first() {
+        Some(&Message::ShootRequest { .. }) | Some(&Message::MoveAndShootRequest { .. }) => requests.len() == 1,
+        _ => false,
+    }
+}
+
+#[test]
+fn turns_start_after_the_opponent_shot() {
+    let mut bot = playing_bot();
+    assert!(is_turn(&reply(&mut bot, Message::EnemyMissUpdate { x: 9, y: 9 })));
+}
+
+#[test]
+fn turns_start_after_the_opponent_used_a_special_weapon_or_ability() {
+    let updates = vec![
+        Message::EnemyRadarUpdate { x: 0, y: 0 },
+        Message::EnemyReconUpdate { x: 0, y: 0 },
+        Message::EnemyAfkUpdate { strikes: 1 },
+    ];
+    for update in updates {
+        let mut bot = playing_bot();
+        assert!(is_turn(&reply(&mut bot, update)));
+    }
+}
+
+#[test]
+fn challenges_are_sent_again_by_whoever_drives_the_bot() {
+    let mut bot = bot(Some("Ann".to_owned()));
+    let challenge = Message::ChallengePlayerRequest { username: "Ann".to_owned() };
+    assert_eq!(log_in(&mut bot), vec![challenge.clone()]);
+    assert_eq!(bot.retry_challenge(), None);
+
+    // the bot does not wait itself, it leaves sending the challenge again to its driver
+    assert!(reply(&mut bot, Message::NotWaitingResponse { nickname: "Ann".to_owned() }).is_empty());
+    assert_eq!(bot.retry_challenge(), Some(challenge.clone()));
+    assert_eq!(bot.retry_challenge(), None);
+    assert!(reply(&mut bot, Message::NoSuchPlayerResponse { nickname: "Ann".to_owned() }).is_empty());
+    assert_eq!(bot.retry_challenge(), Some(challenge));
+}
+
+#[test]
+fn running_games_are_played_on_while_the_server_shuts_down() {
+    let going_down = || Message::ServerGoingDownUpdate { errormessage: "The server is shutting down.".to_owned() };
+    let mut playing = playing_bot();
+    assert!(reply(&mut playing, going_down()).is_empty());
+    assert!(is_turn(&reply(&mut playing, Message::EnemyMissUpdate { x: 9, y: 9 })));
+
+    // a bot waiting in the lobby has nothing left to do
+    let mut waiting = bot(None);
+    log_in(&mut waiting);
+    assert!(waiting.handle_message(going_down()).is_err());
+}
@@ -0,0 +1,171 @@
+//! Tests for `Game`: seating and turn order, and what players can use up during a game.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::board::Board;
+use rusty_battleships::game::Game;
+use rusty_battleships::message::Message;
+use rusty_battleships::placement::{self, Bias};
+use rusty_battleships::rules::Rules;
+
+fn random_board(seed: usize) -> Board {
+    let fleet = placement::random_fleet(&Rules::default(), Bias::Uniform, Some(seed));
+    Board::try_create(fleet, true).expect("Random fleets are valid.")
+}
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|&name| name.to_owned()).collect()
+}
+
+/// A running team game of Ann and Amy against Bob and Ben, in which Ann has the first turn
+fn team_game() -> Game {
+    let team = |first: &str, second: &str, seed: usize| {
+        vec![(first.to_owned(), random_board(seed)), (second.to_owned(), random_board(seed + 1))]
+    };
+    let mut game = Game::new_team_game(team("Ann", "Amy", 0), team("Bob", "Ben", 2), Rules::default());
+    game.set_active_player(&"Ann".to_owned());
+    game.start();
+    return game;
+}
+
+/// Destroys every ship of the player's fleet
+fn sink_fleet(game: &mut Game, player_name: &str) {
+    let board = game.get_board(&player_name.to_owned());
+    for ship in board.get_ships().clone() {
+        for i in 0..ship.length {
+            let (x, y) = Board::get_ship_dest_coords(&ship, i);
+            board.hit(x as usize, y as usize);
+        }
+    }
+    assert!(board.is_dead());
+}
+
+#[test]
+fn teams_are_seated_alternately() {
+    let game = team_game();
+    assert_eq!(game.get_player_names(), names(&["Ann", "Bob", "Amy", "Ben"]));
+    assert!(game.is_team_game() && !game.is_free_for_all() && !game.is_two_player());
+    assert!(game.is_ally(&"Ann".to_owned(), &"Amy".to_owned()));
+    assert!(game.is_ally(&"Ben".to_owned(), &"Bob".to_owned()));
+    assert!(!game.is_ally(&"Ann".to_owned(), &"Bob".to_owned()));
+    assert_eq!(game.get_ally_name(&"Ann".to_owned()), Some("Amy".to_owned()));
+    assert_eq!(game.get_ally_name(&"Ben".to_owned()), Some("Bob".to_owned()));
+}
+
+#[test]
+fn turns_alternate_between_teams() {
+    let mut game = team_game();
+    for &name in &["Bob", "Amy", "Ben", "Ann", "Bob"] {
+        game.switch_turns();
+        assert_eq!(game.get_active_player(), name);
+    }
+    assert_eq!(game.get_turn_count(), 5);
+}
+
+#[test]
+fn turns_skip_allies_and_eliminated_opponents() {
+    let mut game = team_game();
+    sink_fleet(&mut game, "Bob");
+    assert!(game.is_eliminated(&"Bob".to_owned()));
+    // neither Bob, who is out, nor Amy, who is on Ann's team, is next
+    game.switch_turns();
+    assert_eq!(game.get_active_player(), "Ben");
+    game.switch_turns();
+    assert_eq!(game.get_active_player(), "Ann");
+}
+
+#[test]
+fn removing_the_active_player_passes_the_turn_to_an_opponent() {
+    let mut game = team_game();
+    game.remove_player(&"Ann".to_owned());
+    assert_eq!(game.get_active_player(), "Bob");
+    // the turn Ann did not play is not counted
+    assert_eq!(game.get_turn_count(), 0);
+    assert!(game.pop_turn_durations().is_empty());
+
+    game.remove_player(&"Bob".to_owned());
+    assert_eq!(game.get_active_player(), "Amy");
+    assert_eq!(game.get_player_names(), names(&["Amy", "Ben"]));
+}
+
+#[test]
+fn removing_a_waiting_player_keeps_the_turn() {
+    let mut game = team_game();
+    game.switch_turns();
+    game.remove_player(&"Ann".to_owned());
+    assert_eq!(game.get_active_player(), "Bob");
+    game.remove_player(&"Ben".to_owned());
+    assert_eq!(game.get_active_player(), "Bob");
+    assert_eq!(game.get_turn_count(), 1);
+}
+
+#[test]
+fn a_team_wins_once_both_opponents_are_sunk() {
+    let mut game = team_game();
+    sink_fleet(&mut game, "Bob");
+    assert!(!game.is_decided());
+    // a team stays in the game as long as one of its fleets is afloat
+    sink_fleet(&mut game, "Ann");
+    assert!(!game.is_decided());
+    sink_fleet(&mut game, "Ben");
+    assert!(game.is_decided());
+    assert_eq!(game.get_living_players(), names(&["Amy"]));
+}
+
+#[test]
+fn special_weapons_are_refused_without_charges_left() {
+    let (ann, bob) = ("Ann".to_owned(), "Bob".to_owned());
+    let rules = Rules { radar_charges: 1, torpedo_charges: 2, ..Rules::default() };
+    let mut game = Game::new(random_board(0), random_board(1), ann.clone(), bob.clone(), rules);
+    assert!(game.use_radar_charge(&ann));
+    assert!(!game.use_radar_charge(&ann));
+    assert_eq!(game.get_charges(&ann).radar, 0);
+    assert!(game.use_torpedo_charge(&ann) && game.use_torpedo_charge(&ann));
+    assert!(!game.use_torpedo_charge(&ann));
+    assert_eq!(game.get_charges(&ann).torpedo, 0);
+    // every player has charges of their own
+    assert_eq!((game.get_charges(&bob).radar, game.get_charges(&bob).torpedo), (1, 2));
+}
+
+/// A running two-player game of Ann against Bob, in which Ann has the first turn
+fn two_player_game(rules: Rules) -> Game {
+    let mut game = Game::new(random_board(0), random_board(1), "Ann".to_owned(), "Bob".to_owned(), rules);
+    game.set_active_player(&"Ann".to_owned());
+    game.start();
+    return game;
+}
+
+#[test]
+fn cooldowns_tick_at_the_start_of_their_owners_turns() {
+    let ann = "Ann".to_owned();
+    let mut game = two_player_game(Rules { ship_abilities: true, ability_cooldown: 2, ..Rules::default() });
+    game.start_cooldown(&ann, 4);
+    assert_eq!(game.get_cooldown(&ann, 4), 2);
+    assert_eq!(game.pop_ability_updates()[&ann], vec![Message::AbilityCooldownUpdate { id: 4, turns: 2 }]);
+
+    for &turns in &[1, 0] {
+        // the opponent's turns do not count
+        game.switch_turns();
+        assert_eq!(game.get_cooldown(&ann, 4), turns + 1);
+        assert!(game.pop_ability_updates().is_empty());
+        game.switch_turns();
+        assert_eq!(game.get_cooldown(&ann, 4), turns);
+        assert_eq!(game.pop_ability_updates()[&ann], vec![Message::AbilityCooldownUpdate { id: 4, turns: turns }]);
+    }
+
+    game.switch_turns();
+    game.switch_turns();
+    assert_eq!(game.get_cooldown(&ann, 4), 0);
+    assert!(game.pop_ability_updates().is_empty());
+}
+
+#[test]
+fn submerged_ships_surface_at_the_start_of_their_owners_next_turn() {
+    let ann = "Ann".to_owned();
+    let mut game = two_player_game(Rules { ship_abilities: true, ..Rules::default() });
+    assert!(game.get_board(&ann).submerge_ship(4));
+    game.switch_turns();
+    assert!(game.get_board(&ann).get_ships()[4].submerged);
+    game.switch_turns();
+    assert!(!game.get_board(&ann).get_ships()[4].submerged);
+}
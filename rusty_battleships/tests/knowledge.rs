@@ -0,0 +1,105 @@
+//! Tests for `OpponentBoard`: what a player learns about an opponent's board from the server.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::knowledge::{CellKnowledge, OpponentBoard};
+use rusty_battleships::message::Message;
+
+#[test]
+fn messages_about_the_board_update_its_cells() {
+    let mut board = OpponentBoard::new();
+    assert!(board.ingest(&Message::HitResponse { x: 0, y: 0 }));
+    assert!(board.ingest(&Message::MissResponse { x: 1, y: 0 }));
+    assert!(board.ingest(&Message::EnemyVisibleUpdate { x: 2, y: 0 }));
+    assert!(board.ingest(&Message::BoardDamagedUpdate { nickname: "Bob".to_owned(), x: 3, y: 0 }));
+    assert!(board.ingest(&Message::PlayerShotUpdate { shooter: "Ann".to_owned(), target: "Bob".to_owned(), x: 4, y: 0,
+                                                      hit: false }));
+    assert_eq!(board.get(0, 0), CellKnowledge::Hit);
+    assert_eq!(board.get(1, 0), CellKnowledge::Water);
+    assert_eq!(board.get(2, 0), CellKnowledge::Ship);
+    assert_eq!(board.get(3, 0), CellKnowledge::Hit);
+    assert_eq!(board.get(4, 0), CellKnowledge::Water);
+    assert!(!board.is_visible_at(5, 0));
+
+    // messages about anything else leave the board alone
+    assert!(!board.ingest(&Message::OkResponse));
+    assert!(!board.ingest(&Message::EnemyHitUpdate { x: 5, y: 0 }));
+    assert!(!board.is_visible_at(5, 0));
+}
+
+#[test]
+fn ships_leaving_a_cell_are_remembered_until_the_next_turn() {
+    let mut board = OpponentBoard::new();
+    board.set_ship(0, 0);
+    board.set_damaged(1, 0);
+    board.ingest(&Message::EnemyInvisibleUpdate { x: 0, y: 0 });
+    board.ingest(&Message::EnemyInvisibleUpdate { x: 1, y: 0 });
+    assert_eq!(board.get(0, 0), CellKnowledge::LastSeen);
+    assert_eq!(board.get(1, 0), CellKnowledge::LastSeen);
+
+    board.next_turn();
+    assert_eq!(board.get(0, 0), CellKnowledge::Stale);
+    // a stale cell is still not known to be water
+    board.set_water(0, 0);
+    assert_eq!(board.get(0, 0), CellKnowledge::Stale);
+}
+
+#[test]
+fn sinking_marks_the_damaged_segments_in_line() {
+    let mut board = OpponentBoard::new();
+    for x in 2..5 {
+        board.set_damaged(x, 3);
+    }
+    // damaged, but not in line with the sunk segment
+    board.set_damaged(3, 4);
+    board.ingest(&Message::DestroyedResponse { x: 5, y: 3 });
+    for x in 2..6 {
+        assert_eq!(board.get(x, 3), CellKnowledge::Sunk);
+    }
+    assert_eq!(board.get(3, 4), CellKnowledge::Hit);
+    assert_eq!(board.get_destroyed(), 1);
+    assert_eq!(board.remaining_lengths(), vec![5, 3, 2, 2]);
+
+    // the walk stops at the edge of the board
+    board.set_damaged(0, 1);
+    board.set_sunk(0, 0);
+    assert_eq!(board.get(0, 1), CellKnowledge::Sunk);
+    assert_eq!(board.remaining_lengths(), vec![5, 3, 2]);
+}
+
+#[test]
+fn miscounted_ships_take_the_longest_shorter_one_with_them() {
+    let mut board = OpponentBoard::new();
+    // two ships of length 3, but the fleet has only one
+    board.set_damaged(0, 0);
+    board.set_damaged(1, 0);
+    board.set_sunk(2, 0);
+    board.set_damaged(0, 2);
+    board.set_damaged(1, 2);
+    board.set_sunk(2, 2);
+    assert_eq!(board.remaining_lengths(), vec![5, 4, 2]);
+
+    // a single segment is shorter than any ship, so the last one goes
+    board.set_sunk(9, 9);
+    assert_eq!(board.remaining_lengths(), vec![5, 4]);
+    assert_eq!(board.get_destroyed(), 3);
+}
+
+#[test]
+fn ships_are_placed_where_none_is_known_to_be_missing() {
+    let mut board = OpponentBoard::new();
+    assert_eq!(board.possible_placements(5).len(), 2 * 6 * 10);
+    // single cells are not counted twice
+    assert_eq!(board.possible_placements(1).len(), 100);
+
+    board.set_water(5, 5);
+    board.set_ship(0, 0);
+    board.set_damaged(1, 0);
+    let placements = board.possible_placements(5);
+    assert_eq!(placements.len(), 2 * 6 * 10 - 5 - 5);
+    assert!(placements.iter().all(|placement| !placement.get(5, 5)));
+    assert!(placements.iter().any(|placement| placement.get(0, 0) && placement.get(1, 0)));
+
+    board.set_sunk(9, 9);
+    assert!(board.possible_placements(2).iter().all(|placement| !placement.get(9, 9)));
+}
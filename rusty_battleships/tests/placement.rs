@@ -0,0 +1,73 @@
+//! Tests for the problems `placement::validate` reports about a fleet.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::message::Direction;
+use rusty_battleships::placement::{self, PlacementError};
+use rusty_battleships::rules::Rules;
+use rusty_battleships::ship::Ship;
+
+fn ship(x: isize, y: isize, length: usize, direction: Direction) -> Ship {
+    Ship {
+        x: x,
+        y: y,
+        length: length,
+        direction: direction,
+        health_points: length,
+        damage: 0,
+        ability: None,
+        submerged: false,
+    }
+}
+
+#[test]
+fn every_ship_off_the_board_is_reported() {
+    let fleet = vec![
+        ship(0, 0, 5, Direction::East),
+        ship(8, 5, 4, Direction::East),
+        ship(5, -1, 3, Direction::South),
+        ship(2, 9, 2, Direction::South),
+        ship(0, 3, 2, Direction::West),
+    ];
+    assert_eq!(placement::validate(&fleet, &Rules::default()), vec![
+        PlacementError::OutOfBounds { ship: 1 },
+        PlacementError::OutOfBounds { ship: 2 },
+        PlacementError::OutOfBounds { ship: 3 },
+        PlacementError::OutOfBounds { ship: 4 },
+    ]);
+}
+
+#[test]
+fn every_overlapping_pair_is_reported() {
+    let fleet = vec![
+        ship(0, 0, 5, Direction::East),
+        ship(2, 0, 4, Direction::South),
+        ship(4, 0, 3, Direction::South),
+        ship(1, 3, 2, Direction::East),
+        ship(9, 9, 2, Direction::North),
+    ];
+    let expected = vec![
+        PlacementError::Overlap { ship: 0, other: 1 },
+        PlacementError::Overlap { ship: 0, other: 2 },
+        PlacementError::Overlap { ship: 1, other: 3 },
+    ];
+    assert_eq!(placement::validate(&fleet, &Rules::default()), expected);
+    // overlapping ships are not reported as touching as well
+    assert_eq!(placement::validate(&fleet, &Rules { no_touching: true, ..Rules::default() }), expected);
+}
+
+#[test]
+fn touching_ships_are_reported_if_the_rules_forbid_them() {
+    let fleet = vec![
+        ship(0, 0, 5, Direction::East),
+        ship(0, 1, 4, Direction::East),
+        ship(5, 1, 3, Direction::South),
+        ship(9, 9, 2, Direction::West),
+        ship(0, 9, 2, Direction::East),
+    ];
+    assert!(placement::validate(&fleet, &Rules::default()).is_empty());
+    assert_eq!(placement::validate(&fleet, &Rules { no_touching: true, ..Rules::default() }), vec![
+        PlacementError::Touching { ship: 0, other: 1 },
+        PlacementError::Touching { ship: 0, other: 2 },
+    ]);
+}
@@ -0,0 +1,44 @@
+//! Tests for the Elo ratings of tournament players.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::rating::{elo_ratings, GameResult, BASE_ELO};
+
+fn games(first: usize, second: usize, score: f64, count: usize) -> Vec<GameResult> {
+    (0..count).map(|_| GameResult { first: first, second: second, score: score }).collect()
+}
+
+#[test]
+fn even_results_give_equal_ratings() {
+    let mut results = games(0, 1, 1.0, 10);
+    results.extend(games(1, 0, 1.0, 10));
+    results.extend(games(0, 1, 0.5, 4));
+    let ratings = elo_ratings(2, &results);
+    assert!((ratings[0].elo - BASE_ELO).abs() < 1e-6);
+    assert!((ratings[1].elo - BASE_ELO).abs() < 1e-6);
+}
+
+#[test]
+fn winning_more_gives_a_higher_rating() {
+    let mut results = games(0, 1, 1.0, 15);
+    results.extend(games(0, 1, 0.0, 5));
+    let ratings = elo_ratings(2, &results);
+    assert!(ratings[0].elo > ratings[1].elo);
+    // a 75% score corresponds to roughly 190 points difference
+    let difference = ratings[0].elo - ratings[1].elo;
+    assert!(difference > 120.0 && difference < 200.0, "difference of {}", difference);
+}
+
+#[test]
+fn unbeaten_players_have_finite_ratings() {
+    let ratings = elo_ratings(2, &games(0, 1, 1.0, 20));
+    assert!(ratings[0].elo.is_finite() && ratings[1].elo.is_finite());
+    assert!(ratings[0].elo > ratings[1].elo);
+}
+
+#[test]
+fn more_games_narrow_the_confidence_interval() {
+    let few = elo_ratings(2, &games(0, 1, 0.5, 10));
+    let many = elo_ratings(2, &games(0, 1, 0.5, 1000));
+    assert!(many[0].error < few[0].error);
+}
@@ -0,0 +1,135 @@
+//! Tests for the built-in strategies, played on local boards without networking.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::board::{Board, HitResult, W, H};
+use rusty_battleships::knowledge::OpponentBoard;
+use rusty_battleships::message::Message;
+use rusty_battleships::placement::{self, Bias};
+use rusty_battleships::rules::Rules;
+use rusty_battleships::strategy::{self, Strategy, STRATEGY_NAMES};
+
+const SEEDS: usize = 50;
+
+fn create(name: &str, seed: usize) -> Box<Strategy> {
+    strategy::from_name(name, Some(seed)).expect("Built-in strategies can be created by name.")
+}
+
+#[test]
+fn strategies_sink_a_fleet_without_shooting_twice() {
+    for &name in STRATEGY_NAMES.iter() {
+        for seed in 0..SEEDS {
+            let rules = Rules::default();
+            let mut strategy = create(name, seed);
+            let fleet = placement::random_fleet(&rules, Bias::Uniform, Some(seed));
+            let mut board = Board::try_create(fleet, true).unwrap();
+            // the strategy's own board is never hit, so it has no reason to move
+            let my_board = Board::try_create(placement::random_fleet(&rules, Bias::Uniform, Some(seed + 1)), false).unwrap();
+            let mut knowledge = OpponentBoard::new();
+            let mut shot_at = vec![];
+
+            while !board.is_dead() {
+                assert!(shot_at.len() < W * H, "{} did not sink the fleet with {} shots", name, W * H);
+                let turn = strategy.choose_turn(&my_board, &knowledge, &rules);
+                assert_eq!(turn.movement, None);
+                assert!(!shot_at.contains(&(turn.x, turn.y)), "{} shot at {}:{} twice", name, turn.x, turn.y);
+                shot_at.push((turn.x, turn.y));
+
+                let (x, y) = (turn.x, turn.y);
+                let response = match board.shoot(x as usize, y as usize, &rules) {
+                    HitResult::Hit => Message::HitResponse { x: x, y: y },
+                    HitResult::Miss => Message::MissResponse { x: x, y: y },
+                    HitResult::Destroyed => Message::DestroyedResponse { x: x, y: y },
+                };
+                knowledge.ingest(&response);
+                for update in board.pop_updates() {
+                    knowledge.ingest(&update);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn local_games_are_played_to_the_end() {
+    for seed in 0..SEEDS {
+        for &first in STRATEGY_NAMES.iter() {
+            for &second in STRATEGY_NAMES.iter() {
+                let rules = Rules { no_touching: seed % 2 == 0, ..Rules::default() };
+                let outcome = strategy::play_local(&mut *create(first, seed), &mut *create(second, seed + 1), &rules, 1000);
+                assert!(outcome.winner.is_some(), "{} against {} was not decided", first, second);
+                assert!(!outcome.forfeited, "{} against {} was forfeited", first, second);
+                assert!(outcome.turns <= 2 * W * H);
+            }
+        }
+    }
+}
+
+#[test]
+fn turn_limit_decides_by_score() {
+    let rules = Rules { max_turns: Some(10), ..Rules::default() };
+    let outcome = strategy::play_local(&mut *create("random", 0), &mut *create("random", 1), &rules, 1000);
+    assert_eq!(outcome.turns, 10);
+}
+
+#[test]
+fn smarter_strategies_beat_random_shooting() {
+    for &name in &["hunt-target", "probability", "tracker"] {
+        let mut wins = 0;
+        for seed in 0..SEEDS {
+            let rules = Rules::default();
+            let outcome = strategy::play_local(&mut *create(name, seed), &mut *create("random", seed), &rules, 1000);
+            if outcome.winner == Some(0) {
+                wins += 1;
+            }
+        }
+        assert!(wins > SEEDS * 3 / 4, "{} won only {} of {} games against random", name, wins, SEEDS);
+    }
+}
+
+#[test]
+fn damaged_ships_evade_known_hits() {
+    let rules = Rules::default();
+    let fleet = placement::random_fleet(&rules, Bias::Uniform, Some(0));
+    let mut board = Board::try_create(fleet, false).unwrap();
+    let ship = board.get_ships()[0];
+    let (x, y) = Board::get_ship_dest_coords(&ship, 0);
+    board.hit(x as usize, y as usize);
+
+    let (index, direction) = strategy::evasive_move(&board).expect("A hit ship should move away.");
+    assert_eq!(index, 0);
+    assert!(board.move_ship(index, direction));
+}
+
+#[test]
+fn undamaged_ships_stay_in_place() {
+    let rules = Rules::default();
+    let fleet = placement::random_fleet(&rules, Bias::Uniform, Some(0));
+    let board = Board::try_create(fleet, false).unwrap();
+    assert_eq!(strategy::evasive_move(&board), None);
+}
+
+#[test]
+fn tracker_follows_a_ship_that_moved() {
+    let rules = Rules::default();
+    let mut knowledge = OpponentBoard::new();
+    // a ship was seen at 5:5 and left the cell during the opponent's turn
+    knowledge.ingest(&Message::EnemyVisibleUpdate { x: 5, y: 5 });
+    knowledge.ingest(&Message::EnemyInvisibleUpdate { x: 5, y: 5 });
+
+    let my_board = Board::try_create(placement::random_fleet(&rules, Bias::Uniform, Some(0)), false).unwrap();
+    for seed in 0..SEEDS {
+        let turn = create("tracker", seed).choose_turn(&my_board, &knowledge, &rules);
+        let distance = (turn.x as isize - 5).abs() + (turn.y as isize - 5).abs();
+        assert_eq!(distance, 1, "tracker shot at {}:{} instead of next to the ship", turn.x, turn.y);
+    }
+}
+
+#[test]
+fn starting_strategy_can_be_chosen() {
+    let rules = Rules::default();
+    for first in 0..2 {
+        let outcome = strategy::play_local_starting(&mut *create("random", 0), &mut *create("random", 1), &rules, 1000, first);
+        assert_eq!(outcome.first, first);
+    }
+}
@@ -0,0 +1,49 @@
+//! Tests for the text protocol spoken by external bots.
+
+extern crate rusty_battleships;
+
+use rusty_battleships::message::{Direction, Message, ShipPlacement};
+use rusty_battleships::rules::Rules;
+use rusty_battleships::strategy::Turn;
+use rusty_battleships::text_protocol;
+
+#[test]
+fn turns_are_parsed() {
+    assert_eq!(text_protocol::parse_turn("shoot 3 4").unwrap(), Turn::shot(3, 4));
+    let turn = text_protocol::parse_turn("move 2 N shoot 3 4").unwrap();
+    assert_eq!(turn.movement, Some((2, Direction::North)));
+    assert_eq!((turn.x, turn.y), (3, 4));
+}
+
+#[test]
+fn invalid_turns_are_rejected() {
+    for line in &["", "shoot", "shoot 3", "shoot 3 4 5", "shoot a 4", "shoot -1 4", "move 2 shoot 3 4",
+                  "move 2 X shoot 3 4", "move 2 N 3 4", "fire 3 4"] {
+        assert!(text_protocol::parse_turn(line).is_err(), "{:?} was accepted", line);
+    }
+}
+
+#[test]
+fn placements_survive_a_round_trip() {
+    let placement = [
+        ShipPlacement { x: 0, y: 0, direction: Direction::East },
+        ShipPlacement { x: 9, y: 9, direction: Direction::North },
+        ShipPlacement { x: 2, y: 5, direction: Direction::South },
+        ShipPlacement { x: 7, y: 1, direction: Direction::West },
+        ShipPlacement { x: 4, y: 8, direction: Direction::East },
+    ];
+    let line = text_protocol::placement_line(&placement);
+    assert_eq!(line, "place 0 0 E 9 9 N 2 5 S 7 1 W 4 8 E");
+    assert_eq!(text_protocol::parse_placement(&line).unwrap(), placement);
+    assert!(text_protocol::parse_placement("place 0 0 E").is_err());
+}
+
+#[test]
+fn game_messages_become_lines() {
+    assert_eq!(text_protocol::to_line(&Message::HitResponse { x: 3, y: 4 }), Some("hit 3 4".to_owned()));
+    assert_eq!(text_protocol::to_line(&Message::EnemyMissUpdate { x: 0, y: 9 }), Some("enemy miss 0 9".to_owned()));
+    assert_eq!(text_protocol::to_line(&Message::EnemyInvisibleUpdate { x: 1, y: 2 }), Some("invisible 1 2".to_owned()));
+    assert_eq!(text_protocol::to_line(&Message::YourTurnUpdate), None);
+    let rules = Rules { no_touching: true, max_turns: Some(100), ..Rules::default() };
+    assert_eq!(text_protocol::rules_line(&rules), "rules no-touching turn-limit 100");
+}